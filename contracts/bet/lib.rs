@@ -0,0 +1,500 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[ink::contract]
+mod betting {
+    use ink::prelude::vec::Vec;
+    use ink::storage::Mapping;
+
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum BetOption {
+        #[default]
+        Option1,
+        Option2,
+    }
+
+    /// Where the outcome of a market may come from
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum ResolutionSource {
+        /// Outcome reported by the oracle with `report_outcome`
+        Oracle,
+        /// Outcome chosen by the owner with `select_winner`
+        OwnerOverride,
+        /// No outcome: every bet is refunded
+        Void,
+    }
+
+    /// Sources in priority order. Once betting closes, source `i` has until
+    /// `(i + 1) * source_timeout` blocks later to answer before the next one is used.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct ResolutionPolicy {
+        pub sources: Vec<ResolutionSource>,
+        pub source_timeout: BlockNumber,
+    }
+
+    #[derive(Debug, Default, Clone, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Bet {
+        amount: Balance,
+        option: BetOption,
+    }
+
+    #[ink(storage)]
+    pub struct Betting {
+        owner: AccountId,
+        bets: Mapping<AccountId, Bet>,
+        // Accounts holding a bet in the current round
+        bettors: Vec<AccountId>,
+        total_amount: Balance,
+        option1_amount: Balance,
+        option2_amount: Balance,
+        betting_open: bool,
+        winner: Option<BetOption>,
+        // Cumulative payouts per account, kept across rounds
+        winnings: Mapping<AccountId, Balance>,
+        // Accounts that have won at least once, for the leaderboard
+        winners: Vec<AccountId>,
+        oracle: AccountId,
+        resolution_policy: ResolutionPolicy,
+        // Block betting was closed at; resolution windows count from here
+        closed_at: BlockNumber,
+        // Outcomes reported for the current round by each source
+        oracle_outcome: Option<BetOption>,
+        owner_outcome: Option<BetOption>,
+        // Whether the current round was voided and its bets refunded
+        voided: bool,
+    }
+
+    impl Betting {
+        /// `resolution_policy` must list each source at most once and end with `Void`,
+        /// so every market eventually settles
+        #[ink(constructor)]
+        pub fn new(oracle: AccountId, resolution_policy: ResolutionPolicy) -> Self {
+            let sources = &resolution_policy.sources;
+            assert!(
+                sources.last() == Some(&ResolutionSource::Void),
+                "Resolution policy must end with Void"
+            );
+            for (position, source) in sources.iter().enumerate() {
+                assert!(!sources[..position].contains(source), "Duplicate resolution source");
+            }
+            Self {
+                owner: Self::env().caller(),
+                bets: Mapping::new(),
+                bettors: Vec::new(),
+                total_amount: 0,
+                option1_amount: 0,
+                option2_amount: 0,
+                betting_open: true,
+                winner: None,
+                winnings: Mapping::new(),
+                winners: Vec::new(),
+                oracle,
+                resolution_policy,
+                closed_at: 0,
+                oracle_outcome: None,
+                owner_outcome: None,
+                voided: false,
+            }
+        }
+
+        #[ink(message, payable)]
+        pub fn place_bet(&mut self, option: BetOption) {
+            let caller = self.env().caller();
+            let value = self.env().transferred_value();
+
+            assert!(self.betting_open, "Betting is closed");
+            assert!(value > 0, "Bet amount must be greater than zero");
+
+            let bet = Bet { amount: value, option };
+
+            self.total_amount += value;
+            match option {
+                BetOption::Option1 => self.option1_amount += value,
+                BetOption::Option2 => self.option2_amount += value,
+            }
+
+            if !self.bets.contains(caller) {
+                self.bettors.push(caller);
+            }
+            self.bets.insert(caller, &bet);
+        }
+
+        #[ink(message)]
+        pub fn close_betting(&mut self) {
+            assert_eq!(self.env().caller(), self.owner, "Only the owner can close betting");
+            assert!(self.betting_open, "Betting is already closed");
+            self.betting_open = false;
+            self.closed_at = self.env().block_number();
+        }
+
+        /// Records the oracle's outcome; it is used by `resolve` according to the policy
+        #[ink(message)]
+        pub fn report_outcome(&mut self, option: BetOption) {
+            assert_eq!(self.env().caller(), self.oracle, "Only the oracle can report the outcome");
+            assert!(!self.betting_open, "Betting must be closed before reporting the outcome");
+            assert!(!self.is_resolved(), "Market is already resolved");
+
+            self.oracle_outcome = Some(option);
+        }
+
+        /// Records the owner's outcome, used by `resolve` if the policy reaches
+        /// `OwnerOverride`
+        #[ink(message)]
+        pub fn select_winner(&mut self, option: BetOption) {
+            assert_eq!(self.env().caller(), self.owner, "Only the owner can select the winner");
+            assert!(!self.betting_open, "Betting must be closed before selecting a winner");
+            assert!(!self.is_resolved(), "Market is already resolved");
+
+            self.owner_outcome = Some(option);
+        }
+
+        /// Settles the market from the first source in the policy that answered. A
+        /// silent source is skipped only once its window has passed, so the outcome
+        /// does not depend on when `resolve` is called. Callable by anyone.
+        #[ink(message)]
+        pub fn resolve(&mut self) {
+            assert!(!self.betting_open, "Betting must be closed before resolving");
+            assert!(!self.is_resolved(), "Market is already resolved");
+
+            let block = self.env().block_number();
+            let sources = self.resolution_policy.sources.clone();
+            for (position, source) in sources.into_iter().enumerate() {
+                let outcome = match source {
+                    ResolutionSource::Oracle => self.oracle_outcome,
+                    ResolutionSource::OwnerOverride => self.owner_outcome,
+                    ResolutionSource::Void => {
+                        self.void_market();
+                        return;
+                    }
+                };
+                if let Some(option) = outcome {
+                    // Nobody backed the outcome, so there is no one to pay out to
+                    if self.amount_on(option) == 0 {
+                        self.void_market();
+                        return;
+                    }
+                    self.winner = Some(option);
+                    self.distribute_rewards();
+                    return;
+                }
+                let window_end = self.closed_at + (position as BlockNumber + 1) * self.resolution_policy.source_timeout;
+                assert!(block >= window_end, "Waiting for resolution source");
+            }
+        }
+
+        #[ink(message)]
+        pub fn resolution_policy(&self) -> ResolutionPolicy {
+            self.resolution_policy.clone()
+        }
+
+        #[ink(message)]
+        pub fn is_voided(&self) -> bool {
+            self.voided
+        }
+
+        #[ink(message)]
+        pub fn withdraw(&mut self) {
+            let caller = self.env().caller();
+            assert!(self.winner.is_some(), "Winner not selected yet");
+
+            if let Some(bet) = self.bets.get(caller) {
+                if bet.option == self.winner.unwrap() {
+                    let payout = self.payout(&bet);
+                    self.env().transfer(caller, payout).expect("Transfer failed");
+                    self.record_winnings(caller, payout);
+                    self.bets.remove(caller); // Remove the bet after payout
+                }
+            }
+        }
+
+        /// Clears the resolved market so a new round can be bet on. Winnings are kept.
+        #[ink(message)]
+        pub fn start_new_round(&mut self) {
+            assert_eq!(self.env().caller(), self.owner, "Only the owner can start a new round");
+            assert!(self.is_resolved(), "Current round is not resolved yet");
+
+            for bettor in core::mem::take(&mut self.bettors) {
+                self.bets.remove(bettor);
+            }
+            self.total_amount = 0;
+            self.option1_amount = 0;
+            self.option2_amount = 0;
+            self.betting_open = true;
+            self.winner = None;
+            self.oracle_outcome = None;
+            self.owner_outcome = None;
+            self.voided = false;
+        }
+
+        #[ink(message)]
+        pub fn get_winnings(&self, account: AccountId) -> Balance {
+            self.winnings.get(account).unwrap_or(0)
+        }
+
+        /// Top `n` accounts by cumulative winnings, highest first
+        #[ink(message)]
+        pub fn leaderboard(&self, n: u32) -> Vec<(AccountId, Balance)> {
+            let mut board: Vec<(AccountId, Balance)> = self
+                .winners
+                .iter()
+                .map(|account| (*account, self.winnings.get(account).unwrap_or(0)))
+                .collect();
+            board.sort_by_key(|(_, total)| core::cmp::Reverse(*total));
+            board.truncate(n as usize);
+            board
+        }
+
+        fn distribute_rewards(&mut self) {
+            if let Some(winning_option) = self.winner {
+                let payouts: Vec<(AccountId, Balance)> = self
+                    .bettors
+                    .iter()
+                    .filter_map(|account| self.bets.get(account).map(|bet| (*account, bet)))
+                    .filter(|(_, bet)| bet.option == winning_option)
+                    .map(|(account, bet)| (account, self.payout(&bet)))
+                    .collect();
+                for (account, payout) in payouts {
+                    self.env().transfer(account, payout).expect("Transfer failed");
+                    self.record_winnings(account, payout);
+                    // Paid out already, so `withdraw` finds nothing left to pay
+                    self.bets.remove(account);
+                }
+            }
+        }
+
+        /// Winning `bet`'s share of the whole pot. Multiplying first keeps uneven
+        /// pools from rounding the payout down to the stake.
+        fn payout(&self, bet: &Bet) -> Balance {
+            bet.amount * self.total_amount / self.amount_on(bet.option)
+        }
+
+        fn amount_on(&self, option: BetOption) -> Balance {
+            match option {
+                BetOption::Option1 => self.option1_amount,
+                BetOption::Option2 => self.option2_amount,
+            }
+        }
+
+        fn is_resolved(&self) -> bool {
+            self.winner.is_some() || self.voided
+        }
+
+        /// Refunds every bet of the current round
+        fn void_market(&mut self) {
+            self.voided = true;
+            let refunds: Vec<(AccountId, Balance)> = self
+                .bettors
+                .iter()
+                .filter_map(|account| self.bets.get(account).map(|bet| (*account, bet.amount)))
+                .collect();
+            for (account, amount) in refunds {
+                self.env().transfer(account, amount).expect("Transfer failed");
+                self.bets.remove(account);
+            }
+        }
+
+        fn record_winnings(&mut self, account: AccountId, payout: Balance) {
+            let total = match self.winnings.get(account) {
+                Some(total) => total,
+                None => {
+                    self.winners.push(account);
+                    0
+                }
+            };
+            self.winnings.insert(account, &(total + payout));
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            test::set_caller::<DefaultEnvironment>(caller);
+        }
+
+        /// Market owned by Alice with Eve as the oracle, resolved by the oracle or
+        /// else voided after 10 blocks
+        fn setup() -> Betting {
+            set_caller(accounts().alice);
+            let policy = ResolutionPolicy {
+                sources: vec![ResolutionSource::Oracle, ResolutionSource::Void],
+                source_timeout: 10,
+            };
+            let betting = Betting::new(accounts().eve, policy);
+            test::set_account_balance::<DefaultEnvironment>(test::callee::<DefaultEnvironment>(), 1_000_000);
+            betting
+        }
+
+        fn bet_as(betting: &mut Betting, bettor: AccountId, option: BetOption, amount: Balance) {
+            set_caller(bettor);
+            test::set_value_transferred::<DefaultEnvironment>(amount);
+            betting.place_bet(option);
+        }
+
+        #[ink::test]
+        fn oracle_outcome_pays_the_winners() {
+            let mut betting = setup();
+            bet_as(&mut betting, accounts().bob, BetOption::Option1, 100);
+            bet_as(&mut betting, accounts().charlie, BetOption::Option2, 100);
+
+            set_caller(accounts().alice);
+            betting.close_betting();
+            set_caller(accounts().eve);
+            betting.report_outcome(BetOption::Option1);
+            betting.resolve();
+
+            assert_eq!(betting.get_winnings(accounts().bob), 200);
+            assert_eq!(betting.get_winnings(accounts().charlie), 0);
+            assert_eq!(betting.leaderboard(5), vec![(accounts().bob, 200)]);
+        }
+
+        #[ink::test]
+        fn leaderboard_ranks_winnings_across_rounds() {
+            let mut betting = setup();
+            let balance = |account| test::get_account_balance::<DefaultEnvironment>(account).unwrap();
+            let resolve_as_oracle = |betting: &mut Betting, option| {
+                set_caller(accounts().alice);
+                betting.close_betting();
+                set_caller(accounts().eve);
+                betting.report_outcome(option);
+                betting.resolve();
+            };
+
+            bet_as(&mut betting, accounts().bob, BetOption::Option1, 100);
+            bet_as(&mut betting, accounts().charlie, BetOption::Option2, 100);
+            resolve_as_oracle(&mut betting, BetOption::Option1);
+
+            // Bob was paid on resolution; withdrawing does not pay him again
+            let bob_before = balance(accounts().bob);
+            set_caller(accounts().bob);
+            betting.withdraw();
+            assert_eq!(balance(accounts().bob), bob_before);
+
+            set_caller(accounts().alice);
+            betting.start_new_round();
+            bet_as(&mut betting, accounts().charlie, BetOption::Option2, 300);
+            bet_as(&mut betting, accounts().django, BetOption::Option1, 100);
+            resolve_as_oracle(&mut betting, BetOption::Option2);
+
+            set_caller(accounts().alice);
+            betting.start_new_round();
+            bet_as(&mut betting, accounts().django, BetOption::Option1, 100);
+            bet_as(&mut betting, accounts().bob, BetOption::Option2, 200);
+            resolve_as_oracle(&mut betting, BetOption::Option1);
+
+            assert_eq!(
+                betting.leaderboard(5),
+                vec![(accounts().charlie, 400), (accounts().django, 300), (accounts().bob, 200)]
+            );
+            assert_eq!(betting.leaderboard(1), vec![(accounts().charlie, 400)]);
+        }
+
+        #[ink::test]
+        fn silent_oracle_voids_the_market_after_its_window() {
+            set_caller(accounts().alice);
+            let policy = ResolutionPolicy {
+                sources: vec![ResolutionSource::Oracle, ResolutionSource::OwnerOverride, ResolutionSource::Void],
+                source_timeout: 10,
+            };
+            let mut betting = Betting::new(accounts().eve, policy);
+            test::set_account_balance::<DefaultEnvironment>(test::callee::<DefaultEnvironment>(), 1_000_000);
+            bet_as(&mut betting, accounts().bob, BetOption::Option1, 100);
+            bet_as(&mut betting, accounts().charlie, BetOption::Option2, 50);
+            set_caller(accounts().alice);
+            betting.close_betting();
+            let balance = |account| test::get_account_balance::<DefaultEnvironment>(account).unwrap();
+            let (bob_before, charlie_before) = (balance(accounts().bob), balance(accounts().charlie));
+
+            // Neither the oracle nor the owner speaks within their windows
+            for _ in 0..20 {
+                test::advance_block::<DefaultEnvironment>();
+            }
+            betting.resolve();
+
+            assert!(betting.is_voided());
+            assert_eq!(balance(accounts().bob) - bob_before, 100);
+            assert_eq!(balance(accounts().charlie) - charlie_before, 50);
+            assert_eq!(betting.get_winnings(accounts().bob), 0);
+            assert_eq!(betting.get_winnings(accounts().charlie), 0);
+        }
+
+        #[ink::test]
+        fn owner_overrides_a_silent_oracle() {
+            set_caller(accounts().alice);
+            let policy = ResolutionPolicy {
+                sources: vec![ResolutionSource::Oracle, ResolutionSource::OwnerOverride, ResolutionSource::Void],
+                source_timeout: 10,
+            };
+            let mut betting = Betting::new(accounts().eve, policy);
+            test::set_account_balance::<DefaultEnvironment>(test::callee::<DefaultEnvironment>(), 1_000_000);
+            bet_as(&mut betting, accounts().bob, BetOption::Option1, 100);
+            bet_as(&mut betting, accounts().charlie, BetOption::Option2, 100);
+
+            set_caller(accounts().alice);
+            betting.close_betting();
+            betting.select_winner(BetOption::Option2);
+            for _ in 0..10 {
+                test::advance_block::<DefaultEnvironment>();
+            }
+            betting.resolve();
+
+            assert!(!betting.is_voided());
+            assert_eq!(betting.get_winnings(accounts().charlie), 200);
+            assert_eq!(betting.get_winnings(accounts().bob), 0);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Waiting for resolution source")]
+        fn owner_override_waits_for_the_oracle_window() {
+            set_caller(accounts().alice);
+            let policy = ResolutionPolicy {
+                sources: vec![ResolutionSource::Oracle, ResolutionSource::OwnerOverride, ResolutionSource::Void],
+                source_timeout: 10,
+            };
+            let mut betting = Betting::new(accounts().eve, policy);
+            bet_as(&mut betting, accounts().bob, BetOption::Option1, 100);
+
+            set_caller(accounts().alice);
+            betting.close_betting();
+            betting.select_winner(BetOption::Option1);
+            betting.resolve();
+        }
+
+        #[ink::test]
+        fn outcome_nobody_backed_voids_the_market() {
+            let mut betting = setup();
+            bet_as(&mut betting, accounts().bob, BetOption::Option1, 100);
+            let bob_before = test::get_account_balance::<DefaultEnvironment>(accounts().bob).unwrap();
+
+            set_caller(accounts().alice);
+            betting.close_betting();
+            set_caller(accounts().eve);
+            betting.report_outcome(BetOption::Option2);
+            betting.resolve();
+
+            assert!(betting.is_voided());
+            let bob_after = test::get_account_balance::<DefaultEnvironment>(accounts().bob).unwrap();
+            assert_eq!(bob_after - bob_before, 100);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Resolution policy must end with Void")]
+        fn policy_must_end_with_void() {
+            let policy = ResolutionPolicy {
+                sources: vec![ResolutionSource::Oracle],
+                source_timeout: 10,
+            };
+            Betting::new(accounts().eve, policy);
+        }
+    }
+}
@@ -0,0 +1,1103 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::prelude::vec::Vec;
+use ink::storage::Mapping;
+
+#[ink::contract]
+mod bridge {
+    use super::*;
+    use ink::codegen::TraitCallBuilder;
+    use ink::contract_ref;
+    use ink::env::hash::{Blake2x256, HashOutput};
+    use psp22_traits::PSP22;
+    use shared::errors::flatten_call;
+
+    /// Maximum number of records returned by a single `get_lock_history` call.
+    pub const MAX_HISTORY_PAGE: u32 = 100;
+
+    /// Maximum number of signatures accepted by a single `report_remote_state` call.
+    pub const MAX_SIGNATURES: u32 = 32;
+
+    /// Maximum number of locks made by a single `batch_lock` call.
+    pub const MAX_BATCH_LOCKS: u32 = 32;
+
+    /// Denominator for values expressed in basis points
+    pub const BPS_DENOMINATOR: Balance = 10_000;
+
+    /// Time a lock has to wait before its owner may cancel it, in milliseconds
+    pub const DEFAULT_CANCEL_DELAY: Timestamp = 24 * 60 * 60 * 1_000;
+
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct LockRecord {
+        pub lock_id: u64,
+        pub amount: Balance,
+        pub target_chain: u32,
+        pub timestamp: Timestamp,
+        pub refunded: bool,
+    }
+
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct LockInfo {
+        pub from: AccountId,
+        pub amount: Balance,
+        pub target_chain: u32,
+        pub target_address: [u8; 32],
+        pub timestamp: Timestamp,
+    }
+
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct UnlockInfo {
+        pub to: AccountId,
+        pub amount: Balance,
+        pub admin: AccountId,
+        pub timestamp: Timestamp,
+    }
+
+    /// Counterpart chain's locked total as last attested by the validators
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct RemoteState {
+        pub remote_locked: Balance,
+        pub reported_at: Timestamp,
+    }
+
+    /// Local and remote accounting of the peg. `healthy` is false once they drift
+    /// apart by more than the tolerance; it stays true until a first report arrives.
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct PegHealth {
+        pub local_locked: Balance,
+        pub remote: Option<RemoteState>,
+        pub divergence: Balance,
+        pub tolerance: Balance,
+        pub healthy: bool,
+    }
+
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum BridgeError {
+        NotAdmin,
+        AlreadyUnlocked,
+        TooManySignatures,
+        InvalidSignature,
+        NotEnoughSignatures,
+        InvalidThreshold,
+        InvalidTolerance,
+        TreasuryEmpty,
+        FeeTokenCallFailed,
+        FeeTransferRejected,
+        TokenCallFailed,
+        TransferRejected,
+        LockNotFound,
+        NotLocker,
+        LockAlreadyCanceled,
+        CancelTooEarly,
+        LockStatusUnknown,
+        LockProcessed,
+        StaleAttestation,
+        FutureAttestation,
+        EmptyBatch,
+        BatchTooLarge,
+    }
+
+    impl BridgeError {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                BridgeError::NotAdmin => "Caller is not an admin",
+                BridgeError::AlreadyUnlocked => "Nonce has already been unlocked",
+                BridgeError::TooManySignatures => "Too many signatures",
+                BridgeError::InvalidSignature => "Invalid signature",
+                BridgeError::NotEnoughSignatures => "Not enough admin signatures",
+                BridgeError::InvalidThreshold => "Invalid attestation threshold",
+                BridgeError::InvalidTolerance => "Tolerance exceeds 100%",
+                BridgeError::TreasuryEmpty => "Treasury is empty",
+                BridgeError::FeeTokenCallFailed => "Fee token call failed",
+                BridgeError::FeeTransferRejected => "Fee token transfer rejected",
+                BridgeError::TokenCallFailed => "Bridged token call failed",
+                BridgeError::TransferRejected => "Bridged token transfer rejected",
+                BridgeError::LockNotFound => "No lock with this nonce",
+                BridgeError::NotLocker => "Caller did not make this lock",
+                BridgeError::LockAlreadyCanceled => "Lock has already been canceled",
+                BridgeError::CancelTooEarly => "Cancel delay has not passed",
+                BridgeError::LockStatusUnknown => "Remote status of the lock has not been attested",
+                BridgeError::LockProcessed => "Lock has been processed on the remote chain",
+                BridgeError::StaleAttestation => "Attestation predates the end of the cancel delay",
+                BridgeError::FutureAttestation => "Attestation is dated in the future",
+                BridgeError::EmptyBatch => "Empty lock batch",
+                BridgeError::BatchTooLarge => "Too many locks in one batch",
+            }
+        }
+    }
+
+    impl core::fmt::Display for BridgeError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    #[ink(storage)]
+    pub struct Bridge {
+        pub locked_tokens: Mapping<AccountId, Balance>,
+        pub admins: Mapping<AccountId, bool>,
+        // PSP22 token held while locked; without one locks and unlocks are only recorded
+        pub token: Option<AccountId>,
+        // Global counter used to assign lock ids
+        pub next_lock_id: u64,
+        // Per-account history of locks, keyed by (account, seq)
+        pub lock_history: Mapping<(AccountId, u32), LockRecord>,
+        // Number of history records stored per account
+        pub lock_history_len: Mapping<AccountId, u32>,
+        // PSP22 token in which bridge fees are charged
+        pub fee_token: AccountId,
+        // Flat fee charged in `fee_token` on every lock
+        pub bridge_fee: Balance,
+        // Account receiving the accrued fees on `collect_treasury`
+        pub treasury: AccountId,
+        // Fees accrued by the bridge and not yet collected
+        pub treasury_balance: Balance,
+        // Locks by nonce (the lock id)
+        pub lock_records: Mapping<u64, LockInfo>,
+        // Unlocks by the nonce of the lock on the source chain
+        pub unlock_records: Mapping<u64, UnlockInfo>,
+        // Tokens currently held by the bridge: locks minus unlocks
+        pub total_locked: Balance,
+        // Last attested state of the counterpart chain
+        pub remote_state: Option<RemoteState>,
+        // Signed into every report so an attestation cannot be replayed
+        pub report_nonce: u64,
+        // Number of distinct admin signatures a report needs
+        pub attestation_threshold: u32,
+        // Allowed drift between local and remote locked totals, in bps of `total_locked`
+        pub peg_tolerance_bps: u16,
+        // Time after a lock before its owner may cancel it
+        pub cancel_delay: Timestamp,
+        // Attested remote status of locks by nonce: whether the remote chain minted them,
+        // and the time the admins attested it
+        pub remote_lock_status: Mapping<u64, (bool, Timestamp)>,
+        // Canceled locks by nonce, with the time of cancellation
+        pub canceled_locks: Mapping<u64, Timestamp>,
+        // Position of each lock in its owner's history, by nonce
+        pub lock_seqs: Mapping<u64, u32>,
+    }
+
+    #[ink(event)]
+    pub struct Locked {
+        nonce: u64,
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        amount: Balance,
+        #[ink(topic)]
+        target_chain: u32,
+        #[ink(topic)]
+        target_address: [u8; 32],
+    }
+
+    #[ink(event)]
+    pub struct Unlocked {
+        #[ink(topic)]
+        nonce: u64,
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LockCanceled {
+        #[ink(topic)]
+        nonce: u64,
+        #[ink(topic)]
+        from: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RemoteStateReported {
+        #[ink(topic)]
+        report_nonce: u64,
+        remote_locked: Balance,
+        divergence: Balance,
+        #[ink(topic)]
+        healthy: bool,
+    }
+
+    #[ink(event)]
+    pub struct TreasuryCollected {
+        #[ink(topic)]
+        treasury: AccountId,
+        amount: Balance,
+    }
+
+    impl Bridge {
+        #[ink(constructor)]
+        pub fn new(
+            admin: AccountId,
+            token: Option<AccountId>,
+            fee_token: AccountId,
+            treasury: AccountId,
+            bridge_fee: Balance,
+        ) -> Self {
+            let mut admins = Mapping::new();
+            admins.insert(&admin, &true);
+            Self {
+                locked_tokens: Mapping::new(),
+                admins,
+                token,
+                next_lock_id: 0,
+                lock_history: Mapping::new(),
+                lock_history_len: Mapping::new(),
+                fee_token,
+                bridge_fee,
+                treasury,
+                treasury_balance: 0,
+                lock_records: Mapping::new(),
+                unlock_records: Mapping::new(),
+                total_locked: 0,
+                remote_state: None,
+                report_nonce: 0,
+                attestation_threshold: 1,
+                peg_tolerance_bps: 0,
+                cancel_delay: DEFAULT_CANCEL_DELAY,
+                remote_lock_status: Mapping::new(),
+                canceled_locks: Mapping::new(),
+                lock_seqs: Mapping::new(),
+            }
+        }
+
+        #[ink(message)]
+        pub fn lock(&mut self, target_chain: u32, target_address: [u8; 32], amount: Balance) -> Result<u64, BridgeError> {
+            self.lock_for(self.env().caller(), target_chain, target_address, amount)
+        }
+
+        /// Makes one lock per `(target_chain, target_address, amount)` entry, in order,
+        /// each charged the bridge fee and appended to the caller's history. Returns
+        /// the nonces; if any lock fails, none is made.
+        #[ink(message)]
+        pub fn batch_lock(&mut self, locks: Vec<(u32, [u8; 32], Balance)>) -> Result<Vec<u64>, BridgeError> {
+            if locks.is_empty() {
+                return Err(BridgeError::EmptyBatch);
+            }
+            if locks.len() as u32 > MAX_BATCH_LOCKS {
+                return Err(BridgeError::BatchTooLarge);
+            }
+            let caller = self.env().caller();
+            locks
+                .into_iter()
+                .map(|(target_chain, target_address, amount)| {
+                    self.lock_for(caller, target_chain, target_address, amount)
+                })
+                .collect()
+        }
+
+        fn lock_for(
+            &mut self,
+            caller: AccountId,
+            target_chain: u32,
+            target_address: [u8; 32],
+            amount: Balance,
+        ) -> Result<u64, BridgeError> {
+            // The fee is paid in the fee token, separately from the bridged amount
+            if self.bridge_fee > 0 {
+                self.fee_transfer_from(caller, self.env().account_id(), self.bridge_fee)?;
+                self.treasury_balance += self.bridge_fee;
+            }
+
+            if let Some(token) = self.token {
+                let mut token: contract_ref!(PSP22) = token.into();
+                let result = token
+                    .call_mut()
+                    .transfer_from(caller, self.env().account_id(), amount, Vec::new())
+                    .try_invoke();
+                flatten_call(result, BridgeError::TokenCallFailed, BridgeError::TransferRejected)?;
+            }
+
+            let current_locked = self.locked_tokens.get(&caller).unwrap_or(0);
+            self.locked_tokens.insert(&caller, &(current_locked + amount));
+            self.total_locked += amount;
+
+            let nonce = self.record_lock(caller, amount, target_chain);
+            self.lock_records.insert(
+                &nonce,
+                &LockInfo {
+                    from: caller,
+                    amount,
+                    target_chain,
+                    target_address,
+                    timestamp: self.env().block_timestamp(),
+                },
+            );
+
+            self.env().emit_event(Locked {
+                nonce,
+                from: caller,
+                amount,
+                target_chain,
+                target_address,
+            });
+
+            Ok(nonce)
+        }
+
+        #[ink(message)]
+        pub fn unlock(&mut self, nonce: u64, to: AccountId, amount: Balance) -> Result<(), BridgeError> {
+            let caller = self.env().caller();
+            let is_admin = self.admins.get(&caller).unwrap_or(false);
+
+            if !is_admin {
+                return Err(BridgeError::NotAdmin);
+            }
+
+            if self.unlock_records.contains(&nonce) {
+                return Err(BridgeError::AlreadyUnlocked);
+            }
+
+            if let Some(token) = self.token {
+                let mut token: contract_ref!(PSP22) = token.into();
+                let result = token.call_mut().transfer(to, amount, Vec::new()).try_invoke();
+                flatten_call(result, BridgeError::TokenCallFailed, BridgeError::TransferRejected)?;
+            }
+            self.total_locked = self.total_locked.saturating_sub(amount);
+
+            self.unlock_records.insert(
+                &nonce,
+                &UnlockInfo {
+                    to,
+                    amount,
+                    admin: caller,
+                    timestamp: self.env().block_timestamp(),
+                },
+            );
+
+            self.env().emit_event(Unlocked { nonce, to, amount });
+
+            Ok(())
+        }
+
+        /// Returns a lock to its owner once `cancel_delay` has passed and the validators
+        /// have attested that the remote chain did not process it. The nonce is marked
+        /// canceled, so the remote side must never mint it. The bridge fee is kept.
+        #[ink(message)]
+        pub fn cancel_lock(&mut self, nonce: u64) -> Result<(), BridgeError> {
+            let lock = self.lock_records.get(&nonce).ok_or(BridgeError::LockNotFound)?;
+            let caller = self.env().caller();
+            if caller != lock.from {
+                return Err(BridgeError::NotLocker);
+            }
+            if self.canceled_locks.contains(&nonce) {
+                return Err(BridgeError::LockAlreadyCanceled);
+            }
+            let now = self.env().block_timestamp();
+            if now < lock.timestamp.saturating_add(self.cancel_delay) {
+                return Err(BridgeError::CancelTooEarly);
+            }
+            match self.remote_lock_status.get(&nonce) {
+                None => return Err(BridgeError::LockStatusUnknown),
+                Some((true, _)) => return Err(BridgeError::LockProcessed),
+                // The delay may have been raised since the report
+                Some((false, attested_at)) if attested_at < lock.timestamp.saturating_add(self.cancel_delay) => {
+                    return Err(BridgeError::StaleAttestation)
+                }
+                Some((false, _)) => {}
+            }
+
+            if let Some(token) = self.token {
+                let mut token: contract_ref!(PSP22) = token.into();
+                let result = token.call_mut().transfer(caller, lock.amount, Vec::new()).try_invoke();
+                flatten_call(result, BridgeError::TokenCallFailed, BridgeError::TransferRejected)?;
+            }
+
+            let locked = self.locked_tokens.get(&caller).unwrap_or(0);
+            self.locked_tokens.insert(&caller, &locked.saturating_sub(lock.amount));
+            self.total_locked = self.total_locked.saturating_sub(lock.amount);
+            self.canceled_locks.insert(&nonce, &now);
+            if let Some(seq) = self.lock_seqs.get(&nonce) {
+                if let Some(mut record) = self.lock_history.get(&(caller, seq)) {
+                    record.refunded = true;
+                    self.lock_history.insert(&(caller, seq), &record);
+                }
+            }
+
+            self.env().emit_event(LockCanceled {
+                nonce,
+                from: caller,
+                amount: lock.amount,
+            });
+
+            Ok(())
+        }
+
+        /// Records whether the remote chain processed the lock `nonce`, as observed by the
+        /// admins at `attested_at`. `signatures` are admin signatures over
+        /// `lock_status_hash(nonce, processed, attested_at)`, as for `report_remote_state`.
+        /// A lock reported processed stays processed. A "not processed" report only counts
+        /// if it was attested once the cancel delay had passed, so the remote chain had the
+        /// whole delay to mint.
+        #[ink(message)]
+        pub fn report_lock_status(
+            &mut self,
+            nonce: u64,
+            processed: bool,
+            attested_at: Timestamp,
+            signatures: Vec<[u8; 65]>,
+        ) -> Result<(), BridgeError> {
+            let lock = self.lock_records.get(&nonce).ok_or(BridgeError::LockNotFound)?;
+            if matches!(self.remote_lock_status.get(&nonce), Some((true, _))) {
+                return Err(BridgeError::LockProcessed);
+            }
+            if attested_at > self.env().block_timestamp() {
+                return Err(BridgeError::FutureAttestation);
+            }
+            if !processed && attested_at < lock.timestamp.saturating_add(self.cancel_delay) {
+                return Err(BridgeError::StaleAttestation);
+            }
+            self.verify_attestation(&self.lock_status_hash(nonce, processed, attested_at), &signatures)?;
+
+            self.remote_lock_status.insert(&nonce, &(processed, attested_at));
+            Ok(())
+        }
+
+        /// Hash the admins sign to attest the remote status of the lock `nonce` at `attested_at`
+        #[ink(message)]
+        pub fn lock_status_hash(&self, nonce: u64, processed: bool, attested_at: Timestamp) -> [u8; 32] {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(
+                &(self.env().account_id(), b"lock", nonce, processed, attested_at),
+                &mut output,
+            );
+            output
+        }
+
+        #[ink(message)]
+        pub fn is_lock_canceled(&self, nonce: u64) -> bool {
+            self.canceled_locks.contains(&nonce)
+        }
+
+        #[ink(message)]
+        pub fn set_cancel_delay(&mut self, cancel_delay: Timestamp) -> Result<(), BridgeError> {
+            let caller = self.env().caller();
+            let is_admin = self.admins.get(&caller).unwrap_or(false);
+
+            if !is_admin {
+                return Err(BridgeError::NotAdmin);
+            }
+
+            self.cancel_delay = cancel_delay;
+            Ok(())
+        }
+
+        /// Returns up to `limit` (capped at `MAX_HISTORY_PAGE`) lock records of `account`,
+        /// oldest first, starting at `offset`
+        #[ink(message)]
+        pub fn get_lock_history(&self, account: AccountId, offset: u32, limit: u32) -> Vec<LockRecord> {
+            let len = self.lock_history_len.get(&account).unwrap_or(0);
+            let end = offset.saturating_add(limit.min(MAX_HISTORY_PAGE)).min(len);
+            (offset..end)
+                .filter_map(|seq| self.lock_history.get(&(account, seq)))
+                .collect()
+        }
+
+        #[ink(message)]
+        pub fn lock_record(&self, nonce: u64) -> Option<LockInfo> {
+            self.lock_records.get(&nonce)
+        }
+
+        #[ink(message)]
+        pub fn unlock_record(&self, nonce: u64) -> Option<UnlockInfo> {
+            self.unlock_records.get(&nonce)
+        }
+
+        #[ink(message)]
+        pub fn get_lock_history_len(&self, account: AccountId) -> u32 {
+            self.lock_history_len.get(&account).unwrap_or(0)
+        }
+
+        fn record_lock(&mut self, account: AccountId, amount: Balance, target_chain: u32) -> u64 {
+            let lock_id = self.next_lock_id;
+            self.next_lock_id += 1;
+
+            let seq = self.lock_history_len.get(&account).unwrap_or(0);
+            self.lock_history.insert(
+                &(account, seq),
+                &LockRecord {
+                    lock_id,
+                    amount,
+                    target_chain,
+                    timestamp: self.env().block_timestamp(),
+                    refunded: false,
+                },
+            );
+            self.lock_history_len.insert(&account, &(seq + 1));
+            self.lock_seqs.insert(&lock_id, &seq);
+            lock_id
+        }
+
+        /// Checks that at least `attestation_threshold` of `signatures` over `message`
+        /// recover to distinct admins
+        fn verify_attestation(&self, message: &[u8; 32], signatures: &[[u8; 65]]) -> Result<(), BridgeError> {
+            if signatures.len() as u32 > MAX_SIGNATURES {
+                return Err(BridgeError::TooManySignatures);
+            }
+
+            let mut signers: Vec<AccountId> = Vec::new();
+            for signature in signatures {
+                let public_key = self
+                    .env()
+                    .ecdsa_recover(signature, message)
+                    .map_err(|_| BridgeError::InvalidSignature)?;
+                let mut account = <Blake2x256 as HashOutput>::Type::default();
+                ink::env::hash_bytes::<Blake2x256>(&public_key, &mut account);
+                let signer = AccountId::from(account);
+                if self.admins.get(&signer).unwrap_or(false) && !signers.contains(&signer) {
+                    signers.push(signer);
+                }
+            }
+            if (signers.len() as u32) < self.attestation_threshold {
+                return Err(BridgeError::NotEnoughSignatures);
+            }
+            Ok(())
+        }
+
+        /// Records the counterpart chain's locked total. `signatures` are 65-byte ECDSA
+        /// signatures over `report_hash(remote_locked)`; at least `attestation_threshold`
+        /// of them must recover to distinct admins. Callable by anyone holding enough
+        /// signatures. Returns whether the peg is healthy after the report.
+        #[ink(message)]
+        pub fn report_remote_state(&mut self, remote_locked: Balance, signatures: Vec<[u8; 65]>) -> Result<bool, BridgeError> {
+            self.verify_attestation(&self.report_hash(remote_locked), &signatures)?;
+
+            let report_nonce = self.report_nonce;
+            self.report_nonce += 1;
+            self.remote_state = Some(RemoteState {
+                remote_locked,
+                reported_at: self.env().block_timestamp(),
+            });
+
+            let health = self.peg_health();
+            self.env().emit_event(RemoteStateReported {
+                report_nonce,
+                remote_locked,
+                divergence: health.divergence,
+                healthy: health.healthy,
+            });
+
+            Ok(health.healthy)
+        }
+
+        /// Compares the local locked total with the last attested remote one
+        #[ink(message)]
+        pub fn peg_health(&self) -> PegHealth {
+            let tolerance = self.total_locked * self.peg_tolerance_bps as Balance / BPS_DENOMINATOR;
+            let divergence = self
+                .remote_state
+                .as_ref()
+                .map(|remote| self.total_locked.abs_diff(remote.remote_locked))
+                .unwrap_or(0);
+            PegHealth {
+                local_locked: self.total_locked,
+                remote: self.remote_state.clone(),
+                divergence,
+                tolerance,
+                healthy: divergence <= tolerance,
+            }
+        }
+
+        /// Hash the admins sign to attest `remote_locked` for the next report
+        #[ink(message)]
+        pub fn report_hash(&self, remote_locked: Balance) -> [u8; 32] {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(
+                &(self.env().account_id(), self.report_nonce, remote_locked),
+                &mut output,
+            );
+            output
+        }
+
+        #[ink(message)]
+        pub fn set_peg_params(&mut self, attestation_threshold: u32, peg_tolerance_bps: u16) -> Result<(), BridgeError> {
+            let caller = self.env().caller();
+            let is_admin = self.admins.get(&caller).unwrap_or(false);
+
+            if !is_admin {
+                return Err(BridgeError::NotAdmin);
+            }
+            if attestation_threshold == 0 || attestation_threshold > MAX_SIGNATURES {
+                return Err(BridgeError::InvalidThreshold);
+            }
+            if peg_tolerance_bps as Balance > BPS_DENOMINATOR {
+                return Err(BridgeError::InvalidTolerance);
+            }
+
+            self.attestation_threshold = attestation_threshold;
+            self.peg_tolerance_bps = peg_tolerance_bps;
+            Ok(())
+        }
+
+        /// Sends all accrued fees to the treasury and returns the collected amount
+        #[ink(message)]
+        pub fn collect_treasury(&mut self) -> Result<Balance, BridgeError> {
+            let caller = self.env().caller();
+            let is_admin = self.admins.get(&caller).unwrap_or(false);
+
+            if !is_admin {
+                return Err(BridgeError::NotAdmin);
+            }
+
+            let amount = self.treasury_balance;
+            if amount == 0 {
+                return Err(BridgeError::TreasuryEmpty);
+            }
+
+            self.treasury_balance = 0;
+            self.fee_transfer(self.treasury, amount)?;
+
+            self.env().emit_event(TreasuryCollected {
+                treasury: self.treasury,
+                amount,
+            });
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        pub fn set_bridge_fee(&mut self, bridge_fee: Balance) -> Result<(), BridgeError> {
+            let caller = self.env().caller();
+            let is_admin = self.admins.get(&caller).unwrap_or(false);
+
+            if !is_admin {
+                return Err(BridgeError::NotAdmin);
+            }
+
+            self.bridge_fee = bridge_fee;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_treasury_balance(&self) -> Balance {
+            self.treasury_balance
+        }
+
+        fn fee_transfer_from(&self, from: AccountId, to: AccountId, amount: Balance) -> Result<(), BridgeError> {
+            let mut fee_token: contract_ref!(PSP22) = self.fee_token.into();
+            let result = fee_token.call_mut().transfer_from(from, to, amount, Vec::new()).try_invoke();
+            flatten_call(result, BridgeError::FeeTokenCallFailed, BridgeError::FeeTransferRejected)
+        }
+
+        fn fee_transfer(&self, to: AccountId, amount: Balance) -> Result<(), BridgeError> {
+            let mut fee_token: contract_ref!(PSP22) = self.fee_token.into();
+            let result = fee_token.call_mut().transfer(to, amount, Vec::new()).try_invoke();
+            flatten_call(result, BridgeError::FeeTokenCallFailed, BridgeError::FeeTransferRejected)
+        }
+
+        #[ink(message)]
+        pub fn add_admin(&mut self, new_admin: AccountId) -> Result<(), BridgeError> {
+            let caller = self.env().caller();
+            let is_admin = self.admins.get(&caller).unwrap_or(false);
+
+            if !is_admin {
+                return Err(BridgeError::NotAdmin);
+            }
+
+            self.admins.insert(&new_admin, &true);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_admin(&mut self, admin: AccountId) -> Result<(), BridgeError> {
+            let caller = self.env().caller();
+            let is_admin = self.admins.get(&caller).unwrap_or(false);
+
+            if !is_admin {
+                return Err(BridgeError::NotAdmin);
+            }
+
+            self.admins.insert(&admin, &false);
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            test::set_caller::<DefaultEnvironment>(caller);
+        }
+
+        /// Secret key of the validator admin added by `setup_with_validator`
+        const VALIDATOR_KEY: [u8; 32] = [7; 32];
+
+        /// 65-byte recoverable signature of `message` by `VALIDATOR_KEY`
+        fn validator_sign(message: [u8; 32]) -> [u8; 65] {
+            let secret = secp256k1::SecretKey::from_slice(&VALIDATOR_KEY).unwrap();
+            let message = secp256k1::Message::from_slice(&message).unwrap();
+            let (recovery_id, compact) = secp256k1::SECP256K1
+                .sign_ecdsa_recoverable(&message, &secret)
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            signature
+        }
+
+        /// `setup` with the account of `VALIDATOR_KEY` added as an admin
+        fn setup_with_validator() -> Bridge {
+            let mut bridge = setup();
+            let secret = secp256k1::SecretKey::from_slice(&VALIDATOR_KEY).unwrap();
+            let public_key = secp256k1::PublicKey::from_secret_key(secp256k1::SECP256K1, &secret).serialize();
+            let mut account = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Blake2x256>(&public_key, &mut account);
+            bridge.add_admin(AccountId::from(account)).unwrap();
+            bridge
+        }
+
+        /// Fee-free bridge without a bridged token, administered by Alice
+        fn setup() -> Bridge {
+            let accounts = accounts();
+            set_caller(accounts.alice);
+            Bridge::new(accounts.alice, None, accounts.frank, accounts.eve, 0)
+        }
+
+        #[ink::test]
+        fn lock_is_recorded() {
+            let mut bridge = setup();
+            set_caller(accounts().bob);
+            assert_eq!(bridge.lock(7, [1; 32], 500), Ok(0));
+            assert_eq!(bridge.lock(7, [1; 32], 200), Ok(1));
+
+            assert_eq!(bridge.get_lock_history_len(accounts().bob), 2);
+            let history = bridge.get_lock_history(accounts().bob, 0, 10);
+            assert_eq!(history.iter().map(|record| record.amount).collect::<Vec<_>>(), vec![500, 200]);
+            assert_eq!(bridge.lock_record(1).map(|lock| lock.from), Some(accounts().bob));
+            assert_eq!(bridge.peg_health().local_locked, 700);
+        }
+
+        #[ink::test]
+        fn batch_lock_appends_to_the_history() {
+            let mut bridge = setup();
+            set_caller(accounts().bob);
+            bridge.lock(7, [1; 32], 100).unwrap();
+            assert_eq!(bridge.batch_lock(vec![(8, [2; 32], 200), (9, [3; 32], 300)]), Ok(vec![1, 2]));
+            assert_eq!(bridge.batch_lock(Vec::new()), Err(BridgeError::EmptyBatch));
+            let too_many = vec![(8, [2; 32], 1); MAX_BATCH_LOCKS as usize + 1];
+            assert_eq!(bridge.batch_lock(too_many), Err(BridgeError::BatchTooLarge));
+
+            let history = bridge.get_lock_history(accounts().bob, 0, 10);
+            let summary: Vec<(u64, Balance, u32)> =
+                history.iter().map(|record| (record.lock_id, record.amount, record.target_chain)).collect();
+            assert_eq!(summary, vec![(0, 100, 7), (1, 200, 8), (2, 300, 9)]);
+            assert_eq!(bridge.lock_record(2).map(|lock| lock.target_address), Some([3; 32]));
+            assert_eq!(bridge.peg_health().local_locked, 600);
+        }
+
+        #[ink::test]
+        fn history_pages_stop_at_the_ends() {
+            let mut bridge = setup();
+            set_caller(accounts().bob);
+            for amount in 1..=105 {
+                bridge.lock(7, [1; 32], amount).unwrap();
+            }
+
+            let page = bridge.get_lock_history(accounts().bob, 0, 500);
+            assert_eq!(page.len(), MAX_HISTORY_PAGE as usize);
+            assert_eq!((page[0].amount, page[99].amount), (1, 100));
+            let tail = bridge.get_lock_history(accounts().bob, 100, MAX_HISTORY_PAGE);
+            assert_eq!(tail.iter().map(|record| record.amount).collect::<Vec<_>>(), vec![101, 102, 103, 104, 105]);
+            assert_eq!(bridge.get_lock_history(accounts().bob, 104, 10).len(), 1);
+            assert!(bridge.get_lock_history(accounts().bob, 105, 10).is_empty());
+            assert!(bridge.get_lock_history(accounts().bob, u32::MAX, u32::MAX).is_empty());
+            assert!(bridge.get_lock_history(accounts().bob, 3, 0).is_empty());
+            assert!(bridge.get_lock_history(accounts().charlie, 0, 10).is_empty());
+        }
+
+        #[ink::test]
+        fn unlock_is_admin_only_and_single_use() {
+            let mut bridge = setup();
+            set_caller(accounts().bob);
+            bridge.lock(7, [1; 32], 500).unwrap();
+            assert_eq!(bridge.unlock(0, accounts().bob, 500), Err(BridgeError::NotAdmin));
+
+            set_caller(accounts().alice);
+            assert_eq!(bridge.unlock(0, accounts().bob, 500), Ok(()));
+            assert_eq!(bridge.unlock(0, accounts().bob, 500), Err(BridgeError::AlreadyUnlocked));
+            assert_eq!(bridge.peg_health().local_locked, 0);
+        }
+
+        #[ink::test]
+        fn lock_and_unlock_records_keep_every_field() {
+            let mut bridge = setup();
+            let (alice, bob, charlie) = (accounts().alice, accounts().bob, accounts().charlie);
+            for (timestamp, locker, target_chain, amount) in [(10, bob, 7, 500), (20, charlie, 8, 300), (30, bob, 9, 100)] {
+                test::set_block_timestamp::<DefaultEnvironment>(timestamp);
+                set_caller(locker);
+                bridge.lock(target_chain, [target_chain as u8; 32], amount).unwrap();
+            }
+
+            set_caller(alice);
+            test::set_block_timestamp::<DefaultEnvironment>(40);
+            bridge.unlock(0, charlie, 500).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(50);
+            bridge.unlock(2, bob, 100).unwrap();
+
+            let lock = |from, amount, target_chain: u32, timestamp| LockInfo {
+                from,
+                amount,
+                target_chain,
+                target_address: [target_chain as u8; 32],
+                timestamp,
+            };
+            assert_eq!(bridge.lock_record(0), Some(lock(bob, 500, 7, 10)));
+            assert_eq!(bridge.lock_record(1), Some(lock(charlie, 300, 8, 20)));
+            assert_eq!(bridge.lock_record(2), Some(lock(bob, 100, 9, 30)));
+            assert_eq!(bridge.lock_record(3), None);
+
+            let unlock = |to, amount, timestamp| UnlockInfo {
+                to,
+                amount,
+                admin: alice,
+                timestamp,
+            };
+            assert_eq!(bridge.unlock_record(0), Some(unlock(charlie, 500, 40)));
+            assert_eq!(bridge.unlock_record(1), None);
+            assert_eq!(bridge.unlock_record(2), Some(unlock(bob, 100, 50)));
+            assert_eq!(bridge.peg_health().local_locked, 300);
+        }
+
+        #[ink::test]
+        fn peg_health_follows_the_attested_remote_state() {
+            let mut bridge = setup_with_validator();
+            assert_eq!(bridge.set_peg_params(1, 100), Ok(()));
+            set_caller(accounts().bob);
+            bridge.lock(7, [1; 32], 1_000).unwrap();
+            let health = bridge.peg_health();
+            assert!(health.healthy && health.remote.is_none());
+
+            let signature = validator_sign(bridge.report_hash(1_000));
+            assert_eq!(bridge.report_remote_state(1_000, vec![signature]), Ok(true));
+            assert_eq!(bridge.peg_health().divergence, 0);
+
+            // 20 apart with a 1% tolerance of 10
+            let drifted = validator_sign(bridge.report_hash(980));
+            assert_eq!(bridge.report_remote_state(980, vec![drifted]), Ok(false));
+            let health = bridge.peg_health();
+            assert_eq!((health.divergence, health.tolerance, health.healthy), (20, 10, false));
+            assert_eq!(health.remote.map(|remote| remote.remote_locked), Some(980));
+
+            // Each report signs a new nonce, so the healthy attestation cannot be replayed
+            assert_eq!(
+                bridge.report_remote_state(1_000, vec![signature]),
+                Err(BridgeError::NotEnoughSignatures)
+            );
+            assert!(!bridge.peg_health().healthy);
+
+            let recovered = validator_sign(bridge.report_hash(995));
+            assert_eq!(bridge.report_remote_state(995, vec![recovered]), Ok(true));
+            assert!(bridge.peg_health().healthy);
+        }
+
+        #[ink::test]
+        fn peg_params_are_validated() {
+            let mut bridge = setup();
+            assert_eq!(bridge.set_peg_params(0, 100), Err(BridgeError::InvalidThreshold));
+            assert_eq!(bridge.set_peg_params(2, 10_001), Err(BridgeError::InvalidTolerance));
+            assert_eq!(bridge.set_peg_params(2, 100), Ok(()));
+            assert_eq!(bridge.report_remote_state(0, Vec::new()), Err(BridgeError::NotEnoughSignatures));
+        }
+
+        #[ink::test]
+        fn unprocessed_lock_can_be_canceled_after_the_delay() {
+            let mut bridge = setup_with_validator();
+            set_caller(accounts().bob);
+            let nonce = bridge.lock(7, [1; 32], 500).unwrap();
+            assert_eq!(bridge.cancel_lock(nonce), Err(BridgeError::CancelTooEarly));
+
+            test::set_block_timestamp::<DefaultEnvironment>(DEFAULT_CANCEL_DELAY);
+            assert_eq!(bridge.cancel_lock(nonce), Err(BridgeError::LockStatusUnknown));
+            let signature = validator_sign(bridge.lock_status_hash(nonce, false, DEFAULT_CANCEL_DELAY));
+            assert_eq!(bridge.report_lock_status(nonce, false, DEFAULT_CANCEL_DELAY, vec![signature]), Ok(()));
+
+            set_caller(accounts().charlie);
+            assert_eq!(bridge.cancel_lock(nonce), Err(BridgeError::NotLocker));
+            set_caller(accounts().bob);
+            assert_eq!(bridge.cancel_lock(nonce), Ok(()));
+            assert_eq!(bridge.cancel_lock(nonce), Err(BridgeError::LockAlreadyCanceled));
+
+            assert!(bridge.is_lock_canceled(nonce));
+            assert!(bridge.get_lock_history(accounts().bob, 0, 1)[0].refunded);
+            assert_eq!(bridge.peg_health().local_locked, 0);
+        }
+
+        #[ink::test]
+        fn processed_lock_cannot_be_canceled() {
+            let mut bridge = setup_with_validator();
+            set_caller(accounts().bob);
+            let nonce = bridge.lock(7, [1; 32], 500).unwrap();
+
+            // A signature over the other status does not count
+            let unprocessed = validator_sign(bridge.lock_status_hash(nonce, false, 0));
+            assert_eq!(
+                bridge.report_lock_status(nonce, true, 0, vec![unprocessed]),
+                Err(BridgeError::NotEnoughSignatures)
+            );
+            let processed = validator_sign(bridge.lock_status_hash(nonce, true, 0));
+            assert_eq!(bridge.report_lock_status(nonce, true, 0, vec![processed]), Ok(()));
+
+            test::set_block_timestamp::<DefaultEnvironment>(DEFAULT_CANCEL_DELAY);
+            let unprocessed = validator_sign(bridge.lock_status_hash(nonce, false, DEFAULT_CANCEL_DELAY));
+            assert_eq!(
+                bridge.report_lock_status(nonce, false, DEFAULT_CANCEL_DELAY, vec![unprocessed]),
+                Err(BridgeError::LockProcessed)
+            );
+            assert_eq!(bridge.cancel_lock(nonce), Err(BridgeError::LockProcessed));
+            assert!(!bridge.is_lock_canceled(nonce));
+            assert_eq!(bridge.peg_health().local_locked, 500);
+        }
+
+        #[ink::test]
+        fn stale_unprocessed_attestation_is_rejected() {
+            let mut bridge = setup_with_validator();
+            set_caller(accounts().bob);
+            let nonce = bridge.lock(7, [1; 32], 500).unwrap();
+
+            // Signed right after the lock, before the remote chain had the delay to mint
+            let stale = validator_sign(bridge.lock_status_hash(nonce, false, 1));
+            test::set_block_timestamp::<DefaultEnvironment>(DEFAULT_CANCEL_DELAY);
+            assert_eq!(
+                bridge.report_lock_status(nonce, false, 1, vec![stale]),
+                Err(BridgeError::StaleAttestation)
+            );
+            let early = validator_sign(bridge.lock_status_hash(nonce, false, DEFAULT_CANCEL_DELAY + 1));
+            assert_eq!(
+                bridge.report_lock_status(nonce, false, DEFAULT_CANCEL_DELAY + 1, vec![early]),
+                Err(BridgeError::FutureAttestation)
+            );
+            assert_eq!(bridge.cancel_lock(nonce), Err(BridgeError::LockStatusUnknown));
+
+            // A fresh attestation goes stale again if the delay is raised before the cancel
+            let fresh = validator_sign(bridge.lock_status_hash(nonce, false, DEFAULT_CANCEL_DELAY));
+            assert_eq!(bridge.report_lock_status(nonce, false, DEFAULT_CANCEL_DELAY, vec![fresh]), Ok(()));
+            set_caller(accounts().alice);
+            assert_eq!(bridge.set_cancel_delay(DEFAULT_CANCEL_DELAY + 10), Ok(()));
+            set_caller(accounts().bob);
+            test::set_block_timestamp::<DefaultEnvironment>(DEFAULT_CANCEL_DELAY + 10);
+            assert_eq!(bridge.cancel_lock(nonce), Err(BridgeError::StaleAttestation));
+            assert_eq!(bridge.peg_health().local_locked, 500);
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+        use mock_psp22::MockPsp22Ref;
+        use psp22_traits::PSP22;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn lock_and_unlock_move_real_tokens(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let constructor = BridgeRef::new(alice.clone(), Some(token.clone()), token.clone(), alice.clone(), 0);
+            let bridge = client
+                .instantiate("bridge", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("bridge instantiate failed")
+                .account_id;
+
+            let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(bridge.clone(), 300));
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+
+            let lock = build_message::<BridgeRef>(bridge.clone()).call(|bridge| bridge.lock(2, [7; 32], 300));
+            let nonce = client
+                .call(&ink_e2e::alice(), lock, 0, None)
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+
+            let unlock = build_message::<BridgeRef>(bridge.clone()).call(|bridge| bridge.unlock(nonce, bob.clone(), 100));
+            client
+                .call(&ink_e2e::alice(), unlock, 0, None)
+                .await
+                .expect("unlock failed")
+                .return_value()
+                .expect("unlock rejected");
+
+            let bridge_balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(bridge.clone()));
+            let bob_balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(bob.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &bridge_balance, 0, None).await.return_value(), 200);
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &bob_balance, 0, None).await.return_value(), 100);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn fees_accrue_in_the_fee_token_until_collected(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let fee_token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("fee token instantiate failed")
+                .account_id;
+
+            // Bob is the treasury; the bridged amounts are only recorded
+            let constructor = BridgeRef::new(alice.clone(), None, fee_token.clone(), bob.clone(), 25);
+            let bridge = client
+                .instantiate("bridge", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("bridge instantiate failed")
+                .account_id;
+
+            let approve = build_message::<MockPsp22Ref>(fee_token.clone()).call(|t| t.approve(bridge.clone(), 50));
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+            for amount in [300, 700] {
+                let lock = build_message::<BridgeRef>(bridge.clone()).call(|bridge| bridge.lock(2, [7; 32], amount));
+                client
+                    .call(&ink_e2e::alice(), lock, 0, None)
+                    .await
+                    .expect("lock failed")
+                    .return_value()
+                    .expect("lock rejected");
+            }
+
+            let bridge_fees = build_message::<MockPsp22Ref>(fee_token.clone()).call(|t| t.balance_of(bridge.clone()));
+            let treasury_fees = build_message::<MockPsp22Ref>(fee_token.clone()).call(|t| t.balance_of(bob.clone()));
+            let accrued = build_message::<BridgeRef>(bridge.clone()).call(|bridge| bridge.get_treasury_balance());
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &bridge_fees, 0, None).await.return_value(), 50);
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &accrued, 0, None).await.return_value(), 50);
+
+            let collect = build_message::<BridgeRef>(bridge.clone()).call(|bridge| bridge.collect_treasury());
+            let collected = client
+                .call(&ink_e2e::alice(), collect, 0, None)
+                .await
+                .expect("collect_treasury failed")
+                .return_value();
+            assert_eq!(collected, Ok(50));
+
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &bridge_fees, 0, None).await.return_value(), 0);
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &treasury_fees, 0, None).await.return_value(), 50);
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &accrued, 0, None).await.return_value(), 0);
+            let fee_payer = build_message::<MockPsp22Ref>(fee_token.clone()).call(|t| t.balance_of(alice.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &fee_payer, 0, None).await.return_value(), 9_950);
+
+            Ok(())
+        }
+    }
+}
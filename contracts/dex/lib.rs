@@ -0,0 +1,1276 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::prelude::vec::Vec;
+use ink::storage::Mapping;
+
+#[ink::contract]
+mod simple_dex {
+    use super::*;
+    use ink::codegen::TraitCallBuilder;
+    use ink::contract_ref;
+    use psp22_traits::PSP22;
+    use shared::errors::flatten_call;
+    use shared::math::full_mul_div;
+
+    /// Smallest amount a swap may leave in either reserve
+    pub const MINIMUM_RESERVE: Balance = 1_000;
+
+    /// Fixed-point precision of prices (TokenB per TokenA)
+    pub const PRICE_PRECISION: Balance = 1_000_000;
+
+    /// Swap fee paid to the range positions in range at the time of the swap
+    pub const RANGE_FEE_BPS: Balance = 30;
+
+    /// Denominator for values expressed in basis points
+    pub const BPS_DENOMINATOR: Balance = 10_000;
+
+    /// Maximum number of open range positions, as every swap walks them all
+    pub const MAX_RANGE_POSITIONS: u32 = 64;
+
+    /// Maximum number of open range positions of one owner
+    pub const MAX_POSITIONS_PER_OWNER: u32 = 8;
+
+    /// Smallest liquidity a range position may hold, so the shared slots cannot be
+    /// filled with dust
+    pub const MIN_RANGE_LIQUIDITY: Balance = 1_000;
+
+    /// Number of past prices kept to measure volatility for the dynamic fee
+    pub const MAX_OBSERVATIONS: usize = 16;
+
+    /// Liquidity that only earns swap fees while the pool price is within
+    /// `[p_low, p_high]`, both in `PRICE_PRECISION` units
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct RangePosition {
+        pub owner: AccountId,
+        pub liquidity: Balance,
+        pub p_low: Balance,
+        pub p_high: Balance,
+        // Fees earned and not yet paid out
+        pub fees_a: Balance,
+        pub fees_b: Balance,
+    }
+
+    /// All-time swap counters. Volumes are gross swap inputs per token, fees the
+    /// part of them paid to range positions. Counters saturate instead of wrapping.
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct DexStats {
+        pub total_swaps: u64,
+        pub total_volume_a: Balance,
+        pub total_volume_b: Balance,
+        pub total_fees_a: Balance,
+        pub total_fees_b: Balance,
+    }
+
+    /// Bounds of the swap fee while it follows the price volatility, in basis points
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct DynamicFee {
+        pub min_fee_bps: Balance,
+        pub max_fee_bps: Balance,
+    }
+
+    /// One swap of a `batch_swap`
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct SwapStep {
+        // Swaps TokenA for TokenB if set, TokenB for TokenA otherwise
+        pub a_for_b: bool,
+        // Input amount, or `None` to swap the whole output of the previous step
+        pub amount_in: Option<Balance>,
+        // The batch fails if this step yields less
+        pub min_amount_out: Balance,
+    }
+
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum DexError {
+        InsufficientLiquidity,
+        SlippageExceeded,
+        Expired,
+        EmptyBatch,
+        InvalidChain,
+        VolumeCapExceeded,
+        ReserveDrained,
+        Overflow,
+        TokenCallFailed,
+        TransferRejected,
+        EmptyPool,
+        ZeroWithdrawal,
+        InvalidRange,
+        TooManyPositions,
+        PositionTooSmall,
+        PositionNotFound,
+        NotPositionOwner,
+    }
+
+    impl DexError {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                DexError::InsufficientLiquidity => "Insufficient liquidity",
+                DexError::SlippageExceeded => "Slippage: too little output",
+                DexError::Expired => "Deadline has passed",
+                DexError::EmptyBatch => "Empty swap batch",
+                DexError::InvalidChain => "Step cannot swap the previous output",
+                DexError::VolumeCapExceeded => "Block volume cap exceeded",
+                DexError::ReserveDrained => "Swap would drain the reserve",
+                DexError::Overflow => "Swap amount overflow",
+                DexError::TokenCallFailed => "Token call failed",
+                DexError::TransferRejected => "Token transfer rejected",
+                DexError::EmptyPool => "Pool has no liquidity",
+                DexError::ZeroWithdrawal => "Withdrawal rounds down to nothing",
+                DexError::InvalidRange => "Invalid price range",
+                DexError::TooManyPositions => "Too many range positions",
+                DexError::PositionTooSmall => "Range position liquidity is below the minimum",
+                DexError::PositionNotFound => "Position not found",
+                DexError::NotPositionOwner => "Not the position owner",
+            }
+        }
+    }
+
+    #[ink(event)]
+    pub struct LiquidityRemoved {
+        #[ink(topic)]
+        provider: AccountId,
+        amount_a: Balance,
+        amount_b: Balance,
+        liquidity: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LiquidityTransferred {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        shares: Balance,
+    }
+
+    #[ink(storage)]
+    pub struct SimpleDex {
+        // Simulated token balances within the DEX
+        token_a_balance: Balance,
+        token_b_balance: Balance,
+        // Total liquidity provided
+        total_liquidity: Balance,
+        // Mapping of user address to their liquidity shares
+        liquidity_providers: Mapping<AccountId, Balance>,
+        owner: AccountId,
+        // Maximum swap input volume accepted per block, 0 for no cap
+        max_volume_per_block: Balance,
+        // Block the tracked volume belongs to and the volume swapped in it
+        volume_block: BlockNumber,
+        volume_in_block: Balance,
+        range_positions: Mapping<u32, RangePosition>,
+        // Ids of the open range positions, checked on every swap
+        open_positions: Vec<u32>,
+        // Number of open range positions of each owner
+        owner_positions: Mapping<AccountId, u32>,
+        next_position_id: u32,
+        stats: DexStats,
+        // PSP22 contracts of TokenA and TokenB; balances are only simulated if `None`
+        tokens: Option<(AccountId, AccountId)>,
+        // Price before the first swap of each of the last `MAX_OBSERVATIONS` blocks
+        // with swaps, oldest first
+        observations: Vec<(BlockNumber, Balance)>,
+        // Fee bounds if the swap fee follows the volatility, `RANGE_FEE_BPS` if `None`
+        dynamic_fee: Option<DynamicFee>,
+        // Trading fee kept in the reserves for the liquidity providers, in basis points
+        fee_bps: u16,
+    }
+
+    impl SimpleDex {
+        /// Pool with simulated balances, seeded with `initial_a` and `initial_b`, that
+        /// charges `fee_bps` on every swap
+        #[ink(constructor)]
+        pub fn new(initial_a: Balance, initial_b: Balance, fee_bps: u16) -> Self {
+            Self::init(initial_a, initial_b, None, fee_bps)
+        }
+
+        /// Empty pool trading the PSP22 tokens `token_a` and `token_b`. Deposits are
+        /// pulled with `transfer_from`, so callers approve the pool first.
+        #[ink(constructor)]
+        pub fn new_with_tokens(token_a: AccountId, token_b: AccountId, fee_bps: u16) -> Self {
+            Self::init(0, 0, Some((token_a, token_b)), fee_bps)
+        }
+
+        fn init(
+            initial_a: Balance,
+            initial_b: Balance,
+            tokens: Option<(AccountId, AccountId)>,
+            fee_bps: u16,
+        ) -> Self {
+            assert!(Balance::from(fee_bps) <= BPS_DENOMINATOR, "Fee exceeds 100%");
+            let caller = Self::env().caller();
+            let total_liquidity = Self::calculate_liquidity(initial_a, initial_b);
+            let mut liquidity_providers = Mapping::new();
+            liquidity_providers.insert(&caller, &total_liquidity);
+            Self {
+                token_a_balance: initial_a,
+                token_b_balance: initial_b,
+                total_liquidity,
+                liquidity_providers,
+                owner: caller,
+                max_volume_per_block: 0,
+                volume_block: Self::env().block_number(),
+                volume_in_block: 0,
+                range_positions: Mapping::new(),
+                open_positions: Vec::new(),
+                owner_positions: Mapping::new(),
+                next_position_id: 0,
+                stats: DexStats::default(),
+                tokens,
+                observations: Vec::new(),
+                dynamic_fee: None,
+                fee_bps,
+            }
+        }
+
+        /// Adds liquidity to the pool and returns the amount of liquidity tokens minted.
+        /// Once the pool is seeded, only the amounts matching the reserve ratio are
+        /// deposited; the excess of the over-supplied token stays with the caller.
+        #[ink(message)]
+        pub fn add_liquidity(&mut self, amount_a: Balance, amount_b: Balance) -> Result<Balance, DexError> {
+            let caller = self.env().caller();
+            let (amount_a, amount_b, liquidity_minted) = self.deposit(amount_a, amount_b)?;
+            self.pull(true, caller, amount_a)?;
+            self.pull(false, caller, amount_b)?;
+            self.token_a_balance += amount_a;
+            self.token_b_balance += amount_b;
+            self.total_liquidity += liquidity_minted;
+            let user_liquidity = self.liquidity_providers.get(&caller).unwrap_or(0);
+            self.liquidity_providers.insert(&caller, &(user_liquidity + liquidity_minted));
+            Ok(liquidity_minted)
+        }
+
+        /// Removes liquidity from the pool and returns the amounts of tokens withdrawn.
+        /// Amounts are rounded down, so the reserves always cover the remaining shares.
+        #[ink(message)]
+        pub fn remove_liquidity(&mut self, liquidity: Balance) -> Result<(Balance, Balance), DexError> {
+            if self.total_liquidity == 0 {
+                return Err(DexError::EmptyPool);
+            }
+            let caller = self.env().caller();
+            let user_liquidity = self.liquidity_providers.get(&caller).unwrap_or(0);
+            if user_liquidity < liquidity {
+                return Err(DexError::InsufficientLiquidity);
+            }
+            let amount_a =
+                full_mul_div(liquidity, self.token_a_balance, self.total_liquidity).ok_or(DexError::Overflow)?;
+            let amount_b =
+                full_mul_div(liquidity, self.token_b_balance, self.total_liquidity).ok_or(DexError::Overflow)?;
+            // Burning shares for nothing would only hand their value to the other providers
+            if amount_a == 0 || amount_b == 0 {
+                return Err(DexError::ZeroWithdrawal);
+            }
+            self.token_a_balance -= amount_a;
+            self.token_b_balance -= amount_b;
+            self.total_liquidity -= liquidity;
+            self.liquidity_providers.insert(&caller, &(user_liquidity - liquidity));
+            self.pay(true, caller, amount_a)?;
+            self.pay(false, caller, amount_b)?;
+            self.env().emit_event(LiquidityRemoved {
+                provider: caller,
+                amount_a,
+                amount_b,
+                liquidity,
+            });
+            Ok((amount_a, amount_b))
+        }
+
+        /// Moves `shares` of the caller's liquidity to `to`, so positions can be sold
+        /// or gifted. The pool and its total liquidity are unchanged.
+        #[ink(message)]
+        pub fn transfer_liquidity(&mut self, to: AccountId, shares: Balance) -> Result<(), DexError> {
+            let caller = self.env().caller();
+            let from_liquidity = self.liquidity_providers.get(&caller).unwrap_or(0);
+            if from_liquidity < shares {
+                return Err(DexError::InsufficientLiquidity);
+            }
+            self.liquidity_providers.insert(&caller, &(from_liquidity - shares));
+            // Read after the debit, so a transfer to oneself changes nothing
+            let to_liquidity = self.liquidity_providers.get(&to).unwrap_or(0);
+            self.liquidity_providers.insert(&to, &(to_liquidity + shares));
+            self.env().emit_event(LiquidityTransferred {
+                from: caller,
+                to,
+                shares,
+            });
+            Ok(())
+        }
+
+        /// Swaps `amount_a` of TokenA for TokenB, failing if it yields less than
+        /// `min_amount_out` or once `deadline` has passed
+        #[ink(message)]
+        pub fn swap_a_for_b(
+            &mut self,
+            amount_a: Balance,
+            min_amount_out: Balance,
+            deadline: Option<Timestamp>,
+        ) -> Result<Balance, DexError> {
+            self.ensure_not_expired(deadline)?;
+            let caller = self.env().caller();
+            self.pull(true, caller, amount_a)?;
+            let amount_b = self.execute_swap(amount_a, min_amount_out, true)?;
+            self.pay(false, caller, amount_b)?;
+            Ok(amount_b)
+        }
+
+        /// Swaps `amount_b` of TokenB for TokenA, failing if it yields less than
+        /// `min_amount_out` or once `deadline` has passed
+        #[ink(message)]
+        pub fn swap_b_for_a(
+            &mut self,
+            amount_b: Balance,
+            min_amount_out: Balance,
+            deadline: Option<Timestamp>,
+        ) -> Result<Balance, DexError> {
+            self.ensure_not_expired(deadline)?;
+            let caller = self.env().caller();
+            self.pull(false, caller, amount_b)?;
+            let amount_a = self.execute_swap(amount_b, min_amount_out, false)?;
+            self.pay(true, caller, amount_a)?;
+            Ok(amount_a)
+        }
+
+        /// Executes `swaps` in order and returns the output of the last one. A step
+        /// without `amount_in` swaps the previous step's output back, which is then not
+        /// paid out. The whole batch is quoted first, so if any step would miss its
+        /// `min_amount_out` it fails before anything is swapped.
+        #[ink(message)]
+        pub fn batch_swap(&mut self, swaps: Vec<SwapStep>, deadline: Option<Timestamp>) -> Result<Balance, DexError> {
+            self.ensure_not_expired(deadline)?;
+            if swaps.is_empty() {
+                return Err(DexError::EmptyBatch);
+            }
+            self.quote_batch(&swaps)?;
+
+            let caller = self.env().caller();
+            let mut amount_out = 0;
+            for (index, step) in swaps.iter().enumerate() {
+                let amount_in = match step.amount_in {
+                    Some(amount_in) => {
+                        self.pull(step.a_for_b, caller, amount_in)?;
+                        amount_in
+                    }
+                    None => amount_out,
+                };
+                amount_out = self.execute_swap(amount_in, step.min_amount_out, step.a_for_b)?;
+
+                let chained = swaps.get(index + 1).is_some_and(|next| next.amount_in.is_none());
+                if !chained {
+                    self.pay(!step.a_for_b, caller, amount_out)?;
+                }
+            }
+            Ok(amount_out)
+        }
+
+        /// Adds liquidity that only earns swap fees while the price is within
+        /// `[p_low, p_high]` and returns the position id. The tokens join the pool
+        /// reserves like regular liquidity, so they are taken at the reserve ratio as
+        /// in `add_liquidity`. Each owner may hold up to `MAX_POSITIONS_PER_OWNER`
+        /// positions of at least `MIN_RANGE_LIQUIDITY`.
+        #[ink(message)]
+        pub fn open_range_position(
+            &mut self,
+            amount_a: Balance,
+            amount_b: Balance,
+            p_low: Balance,
+            p_high: Balance,
+        ) -> Result<u32, DexError> {
+            if p_low >= p_high {
+                return Err(DexError::InvalidRange);
+            }
+            let caller = self.env().caller();
+            let owned = self.owner_positions.get(&caller).unwrap_or(0);
+            if owned >= MAX_POSITIONS_PER_OWNER || self.open_positions.len() as u32 >= MAX_RANGE_POSITIONS {
+                return Err(DexError::TooManyPositions);
+            }
+            let (amount_a, amount_b, liquidity) = self.deposit(amount_a, amount_b)?;
+            if liquidity < MIN_RANGE_LIQUIDITY {
+                return Err(DexError::PositionTooSmall);
+            }
+            self.pull(true, caller, amount_a)?;
+            self.pull(false, caller, amount_b)?;
+
+            self.token_a_balance += amount_a;
+            self.token_b_balance += amount_b;
+            self.total_liquidity += liquidity;
+
+            let position_id = self.next_position_id;
+            self.next_position_id += 1;
+            self.range_positions.insert(
+                &position_id,
+                &RangePosition {
+                    owner: caller,
+                    liquidity,
+                    p_low,
+                    p_high,
+                    fees_a: 0,
+                    fees_b: 0,
+                },
+            );
+            self.open_positions.push(position_id);
+            self.owner_positions.insert(&caller, &(owned + 1));
+            Ok(position_id)
+        }
+
+        /// Closes a range position and returns its share of the reserves plus the
+        /// fees it earned
+        #[ink(message)]
+        pub fn close_range_position(&mut self, position_id: u32) -> Result<(Balance, Balance), DexError> {
+            let position = self.range_positions.get(&position_id).ok_or(DexError::PositionNotFound)?;
+            if position.owner != self.env().caller() {
+                return Err(DexError::NotPositionOwner);
+            }
+
+            let amount_a = full_mul_div(position.liquidity, self.token_a_balance, self.total_liquidity)
+                .ok_or(DexError::Overflow)?;
+            let amount_b = full_mul_div(position.liquidity, self.token_b_balance, self.total_liquidity)
+                .ok_or(DexError::Overflow)?;
+            let out_a = amount_a.checked_add(position.fees_a).ok_or(DexError::Overflow)?;
+            let out_b = amount_b.checked_add(position.fees_b).ok_or(DexError::Overflow)?;
+            self.token_a_balance -= amount_a;
+            self.token_b_balance -= amount_b;
+            self.total_liquidity -= position.liquidity;
+
+            self.range_positions.remove(&position_id);
+            self.open_positions.retain(|id| *id != position_id);
+            let owned = self.owner_positions.get(&position.owner).unwrap_or(0);
+            self.owner_positions.insert(&position.owner, &owned.saturating_sub(1));
+            self.pay(true, position.owner, out_a)?;
+            self.pay(false, position.owner, out_b)?;
+            Ok((out_a, out_b))
+        }
+
+        /// Sets the per-block swap volume cap (0 disables it)
+        #[ink(message)]
+        pub fn set_max_volume_per_block(&mut self, max_volume: Balance) {
+            assert!(self.env().caller() == self.owner, "Only owner can set the volume cap");
+            self.max_volume_per_block = max_volume;
+        }
+
+        /// Makes the swap fee follow the price volatility within the given bounds, or
+        /// returns to the fixed `RANGE_FEE_BPS` with `None`. Owner-only.
+        #[ink(message)]
+        pub fn set_dynamic_fee(&mut self, dynamic_fee: Option<DynamicFee>) {
+            assert!(self.env().caller() == self.owner, "Only owner can set the dynamic fee");
+            if let Some(bounds) = &dynamic_fee {
+                assert!(
+                    bounds.min_fee_bps <= bounds.max_fee_bps && bounds.max_fee_bps <= BPS_DENOMINATOR,
+                    "Invalid fee bounds"
+                );
+            }
+            self.dynamic_fee = dynamic_fee;
+        }
+
+        fn ensure_not_expired(&self, deadline: Option<Timestamp>) -> Result<(), DexError> {
+            match deadline {
+                Some(deadline) if self.env().block_timestamp() > deadline => Err(DexError::Expired),
+                _ => Ok(()),
+            }
+        }
+
+        /// The current block's volume after adding `amount_in` to it
+        fn check_volume(&self, volume: Balance, amount_in: Balance) -> Result<Balance, DexError> {
+            let volume = volume.checked_add(amount_in).ok_or(DexError::Overflow)?;
+            if self.max_volume_per_block != 0 && volume > self.max_volume_per_block {
+                return Err(DexError::VolumeCapExceeded);
+            }
+            Ok(volume)
+        }
+
+        /// Swaps `amount_in` of the pool's reserves and returns the output, leaving the
+        /// token transfers to the caller. Nothing changes if the swap fails, including
+        /// when the output is below `min_amount_out`.
+        fn execute_swap(
+            &mut self,
+            amount_in: Balance,
+            min_amount_out: Balance,
+            is_token_a: bool,
+        ) -> Result<Balance, DexError> {
+            let volume = self.check_volume(self.volume_this_block(), amount_in)?;
+            let fee_shares = self.range_fee_shares(amount_in, self.current_price());
+            let fee: Balance = fee_shares.iter().map(|(_, _, share)| share).sum();
+            let net_in = amount_in - fee;
+            let (_, reserve_out) = self.reserves(is_token_a);
+            let amount_out = self.quote(amount_in, is_token_a)?;
+            Self::ensure_minimum_reserve(reserve_out, amount_out)?;
+            if amount_out < min_amount_out {
+                return Err(DexError::SlippageExceeded);
+            }
+
+            self.record_observation();
+            self.volume_block = self.env().block_number();
+            self.volume_in_block = volume;
+            self.credit_range_fees(fee_shares, is_token_a);
+            self.record_swap(amount_in, fee, is_token_a);
+            if is_token_a {
+                self.token_a_balance += net_in;
+                self.token_b_balance -= amount_out;
+            } else {
+                self.token_b_balance += net_in;
+                self.token_a_balance -= amount_out;
+            }
+            Ok(amount_out)
+        }
+
+        /// Output of swapping `amount_in` against the current reserves, after the range
+        /// fee and the trading fee, exactly as `execute_swap` computes it
+        fn quote(&self, amount_in: Balance, is_token_a: bool) -> Result<Balance, DexError> {
+            let fee: Balance = self
+                .range_fee_shares(amount_in, self.current_price())
+                .iter()
+                .map(|(_, _, share)| share)
+                .sum();
+            let (reserve_in, reserve_out) = self.reserves(is_token_a);
+            self.get_amount_out(amount_in - fee, reserve_in, reserve_out)
+        }
+
+        /// Input and output reserves of a swap of TokenA, or of TokenB
+        fn reserves(&self, is_token_a: bool) -> (Balance, Balance) {
+            if is_token_a {
+                (self.token_a_balance, self.token_b_balance)
+            } else {
+                (self.token_b_balance, self.token_a_balance)
+            }
+        }
+
+        /// Runs `swaps` against copies of the reserves and returns the error of the
+        /// first step that would fail, without changing any state
+        fn quote_batch(&self, swaps: &[SwapStep]) -> Result<(), DexError> {
+            let (mut reserve_a, mut reserve_b) = (self.token_a_balance, self.token_b_balance);
+            let mut volume = self.volume_this_block();
+            let mut previous: Option<(bool, Balance)> = None;
+            for step in swaps {
+                let amount_in = match (step.amount_in, previous) {
+                    (Some(amount_in), _) => amount_in,
+                    (None, Some((a_for_b, amount_out))) if a_for_b != step.a_for_b => amount_out,
+                    _ => return Err(DexError::InvalidChain),
+                };
+                volume = self.check_volume(volume, amount_in)?;
+
+                let price = Self::price(reserve_a, reserve_b);
+                let fee: Balance = self.range_fee_shares(amount_in, price).iter().map(|(_, _, share)| share).sum();
+                let (reserve_in, reserve_out) = if step.a_for_b {
+                    (&mut reserve_a, &mut reserve_b)
+                } else {
+                    (&mut reserve_b, &mut reserve_a)
+                };
+                let amount_out = self.get_amount_out(amount_in - fee, *reserve_in, *reserve_out)?;
+                Self::ensure_minimum_reserve(*reserve_out, amount_out)?;
+                if amount_out < step.min_amount_out {
+                    return Err(DexError::SlippageExceeded);
+                }
+
+                *reserve_in += amount_in - fee;
+                *reserve_out -= amount_out;
+                previous = Some((step.a_for_b, amount_out));
+            }
+            Ok(())
+        }
+
+        /// Records the price before the first swap of the block
+        fn record_observation(&mut self) {
+            let block = self.env().block_number();
+            if self.observations.last().map(|(observed_at, _)| *observed_at) == Some(block) {
+                return;
+            }
+            if self.observations.len() == MAX_OBSERVATIONS {
+                self.observations.remove(0);
+            }
+            self.observations.push((block, self.current_price()));
+        }
+
+        /// Mean absolute change between consecutive observed prices, in basis points
+        /// of the earlier one. The current price counts as observed if no swap has
+        /// recorded this block yet, so the result stays the same for every swap of a
+        /// block and cannot be moved by trading within it.
+        fn volatility_bps(&self) -> Balance {
+            let block = self.env().block_number();
+            let mut prices: Vec<Balance> = self.observations.iter().map(|(_, price)| *price).collect();
+            if self.observations.last().map(|(observed_at, _)| *observed_at) != Some(block) {
+                if prices.len() == MAX_OBSERVATIONS {
+                    prices.remove(0);
+                }
+                prices.push(self.current_price());
+            }
+
+            let changes: Vec<Balance> = prices
+                .windows(2)
+                .filter(|pair| pair[0] > 0)
+                .map(|pair| pair[0].abs_diff(pair[1]).saturating_mul(BPS_DENOMINATOR) / pair[0])
+                .collect();
+            if changes.is_empty() {
+                return 0;
+            }
+            changes.iter().fold(0, |sum: Balance, change| sum.saturating_add(*change)) / changes.len() as Balance
+        }
+
+        /// Credits the range positions their shares of a swap fee paid in TokenA or TokenB
+        fn credit_range_fees(&mut self, fee_shares: Vec<(u32, RangePosition, Balance)>, is_token_a: bool) {
+            for (id, mut position, share) in fee_shares {
+                if is_token_a {
+                    position.fees_a += share;
+                } else {
+                    position.fees_b += share;
+                }
+                self.range_positions.insert(&id, &position);
+            }
+        }
+
+        /// The range positions containing `price`, each with its share of the
+        /// `current_dynamic_fee_bps` fee on `amount_in`, pro rata to their liquidity.
+        /// Nothing is charged while no position is in range.
+        fn range_fee_shares(&self, amount_in: Balance, price: Balance) -> Vec<(u32, RangePosition, Balance)> {
+            let in_range: Vec<(u32, RangePosition)> = self
+                .open_positions
+                .iter()
+                .filter_map(|id| self.range_positions.get(id).map(|position| (*id, position)))
+                .filter(|(_, position)| position.p_low <= price && price <= position.p_high)
+                .collect();
+            let in_range_liquidity: Balance = in_range.iter().map(|(_, position)| position.liquidity).sum();
+            if in_range_liquidity == 0 {
+                return Vec::new();
+            }
+
+            let fee = amount_in * self.current_dynamic_fee_bps() / BPS_DENOMINATOR;
+            in_range
+                .into_iter()
+                .map(|(id, position)| {
+                    // Rounding dust stays with the trader
+                    let share = fee * position.liquidity / in_range_liquidity;
+                    (id, position, share)
+                })
+                .collect()
+        }
+
+        fn record_swap(&mut self, amount_in: Balance, fee: Balance, is_token_a: bool) {
+            let stats = &mut self.stats;
+            stats.total_swaps = stats.total_swaps.saturating_add(1);
+            if is_token_a {
+                stats.total_volume_a = stats.total_volume_a.saturating_add(amount_in);
+                stats.total_fees_a = stats.total_fees_a.saturating_add(fee);
+            } else {
+                stats.total_volume_b = stats.total_volume_b.saturating_add(amount_in);
+                stats.total_fees_b = stats.total_fees_b.saturating_add(fee);
+            }
+        }
+
+        /// PSP22 contract of TokenA or TokenB, `None` for a simulated pool
+        fn token(&self, is_token_a: bool) -> Option<contract_ref!(PSP22)> {
+            self.tokens
+                .map(|(token_a, token_b)| if is_token_a { token_a } else { token_b }.into())
+        }
+
+        /// Pulls `amount` of TokenA or TokenB from `from` into the pool
+        fn pull(&self, is_token_a: bool, from: AccountId, amount: Balance) -> Result<(), DexError> {
+            match self.token(is_token_a) {
+                Some(mut token) => {
+                    let result = token
+                        .call_mut()
+                        .transfer_from(from, self.env().account_id(), amount, Vec::new())
+                        .try_invoke();
+                    flatten_call(result, DexError::TokenCallFailed, DexError::TransferRejected)
+                }
+                None => Ok(()),
+            }
+        }
+
+        /// Sends `amount` of TokenA or TokenB from the pool to `to`
+        fn pay(&self, is_token_a: bool, to: AccountId, amount: Balance) -> Result<(), DexError> {
+            match self.token(is_token_a) {
+                Some(mut token) => {
+                    let result = token.call_mut().transfer(to, amount, Vec::new()).try_invoke();
+                    flatten_call(result, DexError::TokenCallFailed, DexError::TransferRejected)
+                }
+                None => Ok(()),
+            }
+        }
+
+        /// Rejects swaps that would leave `reserve_out` below `MINIMUM_RESERVE`
+        fn ensure_minimum_reserve(reserve_out: Balance, amount_out: Balance) -> Result<(), DexError> {
+            if reserve_out.saturating_sub(amount_out) < MINIMUM_RESERVE {
+                return Err(DexError::ReserveDrained);
+            }
+            Ok(())
+        }
+
+        /// Output for `amount_in` against the reserves, after the trading fee. The fee
+        /// stays in the reserves, so the constant product grows with every swap.
+        fn get_amount_out(
+            &self,
+            amount_in: Balance,
+            reserve_in: Balance,
+            reserve_out: Balance,
+        ) -> Result<Balance, DexError> {
+            let fee_factor = BPS_DENOMINATOR - Balance::from(self.fee_bps);
+            let amount_in_with_fee =
+                full_mul_div(amount_in, fee_factor, BPS_DENOMINATOR).ok_or(DexError::Overflow)?;
+            // Constant product formula: (x + Δx) * (y - Δy) = k
+            // Δy = (Δx * y) / (x + Δx)
+            let denominator = reserve_in.checked_add(amount_in_with_fee).ok_or(DexError::Overflow)?;
+            full_mul_div(amount_in_with_fee, reserve_out, denominator).ok_or(DexError::Overflow)
+        }
+
+        /// Amounts taken from a deposit of up to `amount_a` and `amount_b`, and the
+        /// liquidity they mint: `min(amount_a * L / reserve_a, amount_b * L / reserve_b)`
+        /// for a total liquidity L, with the over-supplied amount cut to the reserve ratio.
+        /// Fails with `InsufficientLiquidity` if no liquidity would be minted.
+        fn deposit(&self, amount_a: Balance, amount_b: Balance) -> Result<(Balance, Balance, Balance), DexError> {
+            if self.total_liquidity == 0 {
+                let liquidity = Self::calculate_liquidity(amount_a, amount_b);
+                if liquidity == 0 {
+                    return Err(DexError::InsufficientLiquidity);
+                }
+                return Ok((amount_a, amount_b, liquidity));
+            }
+            let (reserve_a, reserve_b) = (self.token_a_balance, self.token_b_balance);
+            if reserve_a == 0 || reserve_b == 0 {
+                return Err(DexError::InsufficientLiquidity);
+            }
+            let optimal_b = full_mul_div(amount_a, reserve_b, reserve_a).ok_or(DexError::Overflow)?;
+            let (amount_a, amount_b) = if optimal_b <= amount_b {
+                (amount_a, optimal_b)
+            } else {
+                // amount_b * reserve_a / reserve_b < amount_a, as optimal_b > amount_b
+                (full_mul_div(amount_b, reserve_a, reserve_b).ok_or(DexError::Overflow)?, amount_b)
+            };
+            let liquidity_a = full_mul_div(amount_a, self.total_liquidity, reserve_a).ok_or(DexError::Overflow)?;
+            let liquidity_b = full_mul_div(amount_b, self.total_liquidity, reserve_b).ok_or(DexError::Overflow)?;
+            // A deposit too small to mint a share would be a gift to the other providers
+            if liquidity_a.min(liquidity_b) == 0 {
+                return Err(DexError::InsufficientLiquidity);
+            }
+            Ok((amount_a, amount_b, liquidity_a.min(liquidity_b)))
+        }
+
+        /// Liquidity minted by the first deposit into an empty pool
+        fn calculate_liquidity(amount_a: Balance, amount_b: Balance) -> Balance {
+            // In a real DEX, this would be more sophisticated
+            // For simplicity, we'll take the minimum of the ratios
+            if amount_a == 0 || amount_b == 0 {
+                0
+            } else {
+                amount_a.min(amount_b)
+            }
+        }
+
+        /// TokenB a swap of `amount_a` TokenA would yield now, fees included, so
+        /// front-ends can preview it. The volume cap and the minimum reserve are not
+        /// checked; 0 if the amount is too large to price.
+        #[ink(message)]
+        pub fn quote_a_for_b(&self, amount_a: Balance) -> Balance {
+            self.quote(amount_a, true).unwrap_or(0)
+        }
+
+        /// TokenA a swap of `amount_b` TokenB would yield now, as `quote_a_for_b`
+        #[ink(message)]
+        pub fn quote_b_for_a(&self, amount_b: Balance) -> Balance {
+            self.quote(amount_b, false).unwrap_or(0)
+        }
+
+        // Getter functions
+        #[ink(message)]
+        pub fn get_reserves(&self) -> (Balance, Balance) {
+            (self.token_a_balance, self.token_b_balance)
+        }
+
+        /// Price of TokenA in TokenB, in `PRICE_PRECISION` units
+        #[ink(message)]
+        pub fn current_price(&self) -> Balance {
+            Self::price(self.token_a_balance, self.token_b_balance)
+        }
+
+        fn price(reserve_a: Balance, reserve_b: Balance) -> Balance {
+            if reserve_a == 0 {
+                return 0;
+            }
+            reserve_b * PRICE_PRECISION / reserve_a
+        }
+
+        /// Swap fee paid to the range positions, in basis points: the price volatility
+        /// bounded by the dynamic fee settings, or `RANGE_FEE_BPS` while they are unset
+        #[ink(message)]
+        pub fn current_dynamic_fee_bps(&self) -> Balance {
+            match &self.dynamic_fee {
+                Some(bounds) => self.volatility_bps().clamp(bounds.min_fee_bps, bounds.max_fee_bps),
+                None => RANGE_FEE_BPS,
+            }
+        }
+
+        /// Trading fee for the liquidity providers, in basis points
+        #[ink(message)]
+        pub fn get_fee_bps(&self) -> u16 {
+            self.fee_bps
+        }
+
+        #[ink(message)]
+        pub fn get_dynamic_fee(&self) -> Option<DynamicFee> {
+            self.dynamic_fee.clone()
+        }
+
+        #[ink(message)]
+        pub fn stats(&self) -> DexStats {
+            self.stats.clone()
+        }
+
+        #[ink(message)]
+        pub fn get_range_position(&self, position_id: u32) -> Option<RangePosition> {
+            self.range_positions.get(&position_id)
+        }
+
+        #[ink(message)]
+        pub fn get_total_liquidity(&self) -> Balance {
+            self.total_liquidity
+        }
+
+        /// Swap input volume accepted so far in the current block
+        #[ink(message)]
+        pub fn volume_this_block(&self) -> Balance {
+            if self.env().block_number() == self.volume_block {
+                self.volume_in_block
+            } else {
+                0
+            }
+        }
+
+        #[ink(message)]
+        pub fn get_max_volume_per_block(&self) -> Balance {
+            self.max_volume_per_block
+        }
+
+        #[ink(message)]
+        pub fn get_user_liquidity(&self, user: AccountId) -> Balance {
+            self.liquidity_providers.get(&user).unwrap_or(0)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+        use ink::env::DefaultEnvironment;
+
+        type Event = <SimpleDex as ::ink::reflect::ContractEventBase>::Type;
+
+        fn alice() -> AccountId {
+            test::default_accounts::<DefaultEnvironment>().alice
+        }
+
+        #[ink::test]
+        fn constructor_seeds_the_pool() {
+            let dex = SimpleDex::new(10_000, 20_000, 0);
+            assert_eq!(dex.get_reserves(), (10_000, 20_000));
+            assert_eq!(dex.get_total_liquidity(), 10_000);
+            assert_eq!(dex.get_user_liquidity(alice()), 10_000);
+            assert_eq!(dex.current_price(), 2 * PRICE_PRECISION);
+        }
+
+        #[ink::test]
+        fn swap_follows_the_constant_product() {
+            let mut dex = SimpleDex::new(10_000, 10_000, 0);
+            // 1_000 * 10_000 / (10_000 + 1_000)
+            assert_eq!(dex.swap_a_for_b(1_000, 0, None), Ok(909));
+            assert_eq!(dex.get_reserves(), (11_000, 9_091));
+            assert_eq!(dex.stats().total_swaps, 1);
+            assert_eq!(dex.stats().total_volume_a, 1_000);
+        }
+
+        #[ink::test]
+        fn trading_fee_stays_with_the_pool() {
+            let mut dex = SimpleDex::new(10_000, 10_000, 30);
+            assert_eq!(dex.get_fee_bps(), 30);
+            // 997 * 10_000 / (10_000 + 997), below the 909 the fee-free formula gives
+            assert_eq!(dex.swap_a_for_b(1_000, 0, None), Ok(906));
+
+            let (a, b) = dex.get_reserves();
+            let mut product = a * b;
+            for _ in 0..5 {
+                let amount_b = dex.swap_a_for_b(1_000, 0, None).unwrap();
+                dex.swap_b_for_a(amount_b, 0, None).unwrap();
+                let (a, b) = dex.get_reserves();
+                assert!(a * b > product);
+                product = a * b;
+            }
+            // Every round trip cost the trader part of the 1_000 put in
+            assert!(dex.get_reserves().0 > 11_000);
+        }
+
+        #[ink::test]
+        fn quotes_match_the_swaps_that_follow() {
+            let mut dex = SimpleDex::new(10_000, 30_000, 30);
+            dex.open_range_position(1_000, 3_000, 1, Balance::MAX).unwrap();
+            for round in 1..=4 {
+                let (reserves, stats) = (dex.get_reserves(), dex.stats());
+                let quote_a = dex.quote_a_for_b(round * 700);
+                let quote_b = dex.quote_b_for_a(round * 900);
+                assert_eq!((dex.get_reserves(), dex.stats()), (reserves, stats));
+
+                assert_eq!(dex.swap_a_for_b(round * 700, 0, None), Ok(quote_a));
+                let quote_b_after = dex.quote_b_for_a(round * 900);
+                assert_ne!(quote_b_after, quote_b);
+                assert_eq!(dex.swap_b_for_a(round * 900, 0, None), Ok(quote_b_after));
+            }
+        }
+
+        #[ink::test]
+        fn liquidity_is_withdrawn_pro_rata() {
+            let mut dex = SimpleDex::new(10_000, 20_000, 0);
+            assert_eq!(dex.remove_liquidity(2_500), Ok((2_500, 5_000)));
+            assert_eq!(dex.get_user_liquidity(alice()), 7_500);
+        }
+
+        #[ink::test]
+        fn liquidity_is_minted_in_proportion_to_the_reserves() {
+            let mut dex = SimpleDex::new(10_000, 20_000, 0);
+            // min(1_000 * 10_000 / 10_000, 5_000 * 10_000 / 20_000); only 2_000 TokenB is needed
+            assert_eq!(dex.add_liquidity(1_000, 5_000), Ok(1_000));
+            assert_eq!(dex.get_reserves(), (11_000, 22_000));
+            assert_eq!(dex.get_total_liquidity(), 11_000);
+
+            dex.swap_a_for_b(3_000, 0, None).unwrap();
+            let (reserve_a, reserve_b) = dex.get_reserves();
+            assert_eq!((reserve_a, reserve_b), (14_000, 17_286));
+            // TokenA is over-supplied now: 5_000 TokenB mints 5_000 * 11_000 / 17_286
+            // and takes 5_000 * 14_000 / 17_286 TokenA
+            let minted = dex.add_liquidity(10_000, 5_000).unwrap();
+            assert_eq!(minted, (10_000 * 11_000 / reserve_a).min(5_000 * 11_000 / reserve_b));
+            assert_eq!(minted, 3_181);
+            assert_eq!(dex.get_reserves(), (14_000 + 4_049, 17_286 + 5_000));
+            assert_eq!(dex.get_user_liquidity(alice()), 11_000 + 3_181);
+
+            // Withdrawing the new shares cannot return more than was deposited
+            let (amount_a, amount_b) = dex.remove_liquidity(minted).unwrap();
+            assert!(amount_a <= 4_049 && amount_b <= 5_000);
+        }
+
+        #[ink::test]
+        fn deposits_minting_nothing_are_rejected() {
+            let mut dex = SimpleDex::new(10_000, 20_000, 0);
+            // 1 TokenB is worth half a share
+            assert_eq!(dex.add_liquidity(1_000, 1), Err(DexError::InsufficientLiquidity));
+            assert_eq!(dex.add_liquidity(0, 1_000), Err(DexError::InsufficientLiquidity));
+            assert_eq!(dex.get_reserves(), (10_000, 20_000));
+            assert_eq!(dex.get_total_liquidity(), 10_000);
+
+            dex.remove_liquidity(10_000).unwrap();
+            assert_eq!(dex.add_liquidity(1_000, 0), Err(DexError::InsufficientLiquidity));
+            assert_eq!(dex.get_reserves(), (0, 0));
+            assert_eq!(dex.add_liquidity(1_000, 2_000), Ok(1_000));
+        }
+
+        #[ink::test]
+        fn removing_all_liquidity_empties_the_pool() {
+            let mut dex = SimpleDex::new(10_000, 20_000, 0);
+            dex.swap_a_for_b(3_000, 0, None).unwrap();
+            let reserves = dex.get_reserves();
+            assert_eq!(dex.remove_liquidity(0), Err(DexError::ZeroWithdrawal));
+            assert_eq!(dex.remove_liquidity(10_000), Ok(reserves));
+            assert_eq!(dex.get_reserves(), (0, 0));
+            assert_eq!(dex.get_total_liquidity(), 0);
+            assert_eq!(dex.remove_liquidity(0), Err(DexError::EmptyPool));
+            assert_eq!(dex.remove_liquidity(1), Err(DexError::EmptyPool));
+
+            let events: Vec<Event> = test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).expect("event decodes"))
+                .collect();
+            assert!(
+                matches!(
+                    events[..],
+                    [Event::LiquidityRemoved(LiquidityRemoved { provider, amount_a: 13_000, amount_b, .. })]
+                        if provider == alice() && amount_b == reserves.1
+                ),
+                "unexpected events"
+            );
+        }
+
+        #[ink::test]
+        fn liquidity_can_be_transferred_in_part() {
+            let mut dex = SimpleDex::new(10_000, 20_000, 0);
+            let bob = test::default_accounts::<DefaultEnvironment>().bob;
+            assert_eq!(dex.transfer_liquidity(bob, 10_001), Err(DexError::InsufficientLiquidity));
+            dex.transfer_liquidity(bob, 4_000).unwrap();
+            dex.transfer_liquidity(alice(), 1_000).unwrap();
+            assert_eq!((dex.get_user_liquidity(alice()), dex.get_user_liquidity(bob)), (6_000, 4_000));
+            assert_eq!(dex.get_total_liquidity(), 10_000);
+            assert_eq!(dex.get_reserves(), (10_000, 20_000));
+
+            test::set_caller::<DefaultEnvironment>(bob);
+            assert_eq!(dex.remove_liquidity(4_000), Ok((4_000, 8_000)));
+            assert_eq!(dex.get_user_liquidity(bob), 0);
+            assert_eq!(dex.get_total_liquidity(), dex.get_user_liquidity(alice()));
+            assert_eq!(test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn removing_more_than_owned_is_insufficient_liquidity() {
+            let mut dex = SimpleDex::new(10_000, 20_000, 0);
+            assert_eq!(dex.remove_liquidity(10_001), Err(DexError::InsufficientLiquidity));
+            assert_eq!(dex.get_reserves(), (10_000, 20_000));
+            assert_eq!(dex.get_user_liquidity(alice()), 10_000);
+        }
+
+        #[ink::test]
+        fn swap_below_the_minimum_output_is_rejected() {
+            let mut dex = SimpleDex::new(10_000, 10_000, 0);
+            assert_eq!(dex.swap_a_for_b(1_000, 910, None), Err(DexError::SlippageExceeded));
+            assert_eq!(dex.swap_b_for_a(1_000, 910, None), Err(DexError::SlippageExceeded));
+            assert_eq!(dex.get_reserves(), (10_000, 10_000));
+            assert_eq!(dex.stats(), DexStats::default());
+            assert_eq!(dex.volume_this_block(), 0);
+            assert_eq!(dex.swap_a_for_b(1_000, 909, None), Ok(909));
+        }
+
+        #[ink::test]
+        fn swap_cannot_drain_a_reserve() {
+            let mut dex = SimpleDex::new(2_000, 2_000, 0);
+            assert_eq!(dex.swap_a_for_b(1_000_000, 0, None), Err(DexError::ReserveDrained));
+            assert_eq!(dex.get_reserves(), (2_000, 2_000));
+            assert_eq!(dex.stats(), DexStats::default());
+        }
+
+        #[ink::test]
+        fn volume_cap_resets_every_block() {
+            let mut dex = SimpleDex::new(100_000, 100_000, 0);
+            dex.set_max_volume_per_block(3_000);
+            dex.swap_a_for_b(1_000, 0, None).unwrap();
+            dex.swap_b_for_a(1_000, 0, None).unwrap();
+            dex.swap_a_for_b(1_000, 0, None).unwrap();
+            assert_eq!(dex.volume_this_block(), 3_000);
+
+            let reserves = dex.get_reserves();
+            assert_eq!(dex.swap_b_for_a(1, 0, None), Err(DexError::VolumeCapExceeded));
+            assert_eq!(dex.get_reserves(), reserves);
+            assert_eq!(dex.stats().total_swaps, 3);
+
+            test::advance_block::<DefaultEnvironment>();
+            assert_eq!(dex.volume_this_block(), 0);
+            assert!(dex.swap_b_for_a(3_000, 0, None).is_ok());
+            assert_eq!(dex.swap_a_for_b(1, 0, None), Err(DexError::VolumeCapExceeded));
+            assert_eq!(dex.volume_this_block(), 3_000);
+        }
+
+        #[ink::test]
+        fn only_the_range_holding_the_price_earns_fees() {
+            let mut dex = SimpleDex::new(100_000, 100_000, 0);
+            let around_one = dex.open_range_position(1_000, 1_000, 900_000, 1_100_000).unwrap();
+            let around_two = dex.open_range_position(1_000, 1_000, 2_000_000, 3_000_000).unwrap();
+            let fees = |dex: &SimpleDex, id| {
+                let position = dex.get_range_position(id).unwrap();
+                (position.fees_a, position.fees_b)
+            };
+
+            // 30 bps of 1_000, all to the position around the current price of 1
+            dex.swap_a_for_b(1_000, 0, None).unwrap();
+            assert_eq!(fees(&dex, around_one), (3, 0));
+            assert_eq!(fees(&dex, around_two), (0, 0));
+
+            // Still priced around 1 when this swap starts, so the first range earns it
+            dex.swap_b_for_a(50_000, 0, None).unwrap();
+            assert_eq!(fees(&dex, around_one), (3, 150));
+            assert!((2_000_000..=3_000_000).contains(&dex.current_price()));
+
+            dex.swap_b_for_a(1_000, 0, None).unwrap();
+            assert_eq!(fees(&dex, around_one), (3, 150));
+            assert_eq!(fees(&dex, around_two), (0, 3));
+
+            let bob = test::default_accounts::<DefaultEnvironment>().bob;
+            test::set_caller::<DefaultEnvironment>(bob);
+            assert_eq!(dex.close_range_position(around_two), Err(DexError::NotPositionOwner));
+            test::set_caller::<DefaultEnvironment>(alice());
+            let (reserve_a, reserve_b) = dex.get_reserves();
+            let total = dex.get_total_liquidity();
+            assert_eq!(
+                dex.close_range_position(around_two),
+                Ok((1_000 * reserve_a / total, 1_000 * reserve_b / total + 3))
+            );
+            assert_eq!(dex.close_range_position(around_two), Err(DexError::PositionNotFound));
+        }
+
+        #[ink::test]
+        fn stats_add_up_swaps_in_both_directions() {
+            let mut dex = SimpleDex::new(100_000, 100_000, 0);
+            let position = dex.open_range_position(1_000, 1_000, 1, Balance::MAX).unwrap();
+            // 30 bps range fee on each input
+            dex.swap_a_for_b(10_000, 0, None).unwrap();
+            dex.swap_b_for_a(5_000, 0, None).unwrap();
+            dex.swap_a_for_b(2_000, 0, None).unwrap();
+            dex.swap_b_for_a(1_000, 0, None).unwrap();
+            assert_eq!(dex.swap_b_for_a(1_000, 1_000_000, None), Err(DexError::SlippageExceeded));
+
+            assert_eq!(
+                dex.stats(),
+                DexStats {
+                    total_swaps: 4,
+                    total_volume_a: 12_000,
+                    total_volume_b: 6_000,
+                    total_fees_a: 30 + 6,
+                    total_fees_b: 15 + 3,
+                }
+            );
+            let position = dex.get_range_position(position).unwrap();
+            assert_eq!((position.fees_a, position.fees_b), (36, 18));
+        }
+
+        #[ink::test]
+        fn invalid_range_positions_are_rejected() {
+            let mut dex = SimpleDex::new(100_000, 100_000, 0);
+            assert_eq!(dex.open_range_position(1_000, 1_000, 5, 5), Err(DexError::InvalidRange));
+            assert_eq!(dex.open_range_position(0, 1_000, 1, 5), Err(DexError::InsufficientLiquidity));
+            assert_eq!(dex.open_range_position(10, 10, 1, 5), Err(DexError::PositionTooSmall));
+            for _ in 0..MAX_POSITIONS_PER_OWNER {
+                dex.open_range_position(1_000, 1_000, 1, 5).unwrap();
+            }
+            assert_eq!(dex.open_range_position(1_000, 1_000, 1, 5), Err(DexError::TooManyPositions));
+            // Closing one frees a slot of its owner
+            dex.close_range_position(0).unwrap();
+            dex.open_range_position(1_000, 1_000, 1, 5).unwrap();
+
+            // Other owners still get the remaining shared slots
+            for i in MAX_POSITIONS_PER_OWNER..MAX_RANGE_POSITIONS {
+                test::set_caller::<DefaultEnvironment>(AccountId::from([100 + i as u8; 32]));
+                dex.open_range_position(1_000, 1_000, 1, 5).unwrap();
+            }
+            test::set_caller::<DefaultEnvironment>(test::default_accounts::<DefaultEnvironment>().bob);
+            assert_eq!(dex.open_range_position(1_000, 1_000, 1, 5), Err(DexError::TooManyPositions));
+        }
+
+        #[ink::test]
+        fn swap_after_the_deadline_is_expired() {
+            let mut dex = SimpleDex::new(10_000, 10_000, 0);
+            test::set_block_timestamp::<DefaultEnvironment>(1_000);
+            assert_eq!(dex.swap_a_for_b(1_000, 0, Some(999)), Err(DexError::Expired));
+            assert_eq!(dex.swap_b_for_a(1_000, 0, Some(999)), Err(DexError::Expired));
+            assert_eq!(dex.batch_swap(vec![step(true, Some(1_000), 0)], Some(999)), Err(DexError::Expired));
+            assert_eq!(dex.get_reserves(), (10_000, 10_000));
+            assert_eq!(dex.swap_a_for_b(1_000, 0, Some(1_000)), Ok(909));
+        }
+
+        #[ink::test]
+        fn dynamic_fee_rises_with_volatility() {
+            let mut dex = SimpleDex::new(1_000_000, 1_000_000, 0);
+            dex.open_range_position(1_000, 1_000, 1, Balance::MAX).unwrap();
+            dex.set_dynamic_fee(Some(DynamicFee {
+                min_fee_bps: 10,
+                max_fee_bps: 100,
+            }));
+            assert_eq!(dex.current_dynamic_fee_bps(), 10);
+
+            // Small swaps in alternating directions barely move the price
+            for block in 0..4 {
+                test::advance_block::<DefaultEnvironment>();
+                if block % 2 == 0 {
+                    dex.swap_a_for_b(100, 0, None).unwrap();
+                } else {
+                    dex.swap_b_for_a(100, 0, None).unwrap();
+                }
+            }
+            assert_eq!(dex.current_dynamic_fee_bps(), 10);
+            let calm_fees = dex.stats().total_fees_a;
+            test::advance_block::<DefaultEnvironment>();
+            dex.swap_a_for_b(10_000, 0, None).unwrap();
+            assert_eq!(dex.stats().total_fees_a - calm_fees, 10);
+
+            // A large swap moves the price by about 40%, which shows from the next block
+            dex.swap_a_for_b(250_000, 0, None).unwrap();
+            test::advance_block::<DefaultEnvironment>();
+            assert_eq!(dex.current_dynamic_fee_bps(), 100);
+            let fees_before = dex.stats().total_fees_a;
+            dex.swap_a_for_b(10_000, 0, None).unwrap();
+            assert_eq!(dex.stats().total_fees_a - fees_before, 100);
+        }
+
+        fn step(a_for_b: bool, amount_in: Option<Balance>, min_amount_out: Balance) -> SwapStep {
+            SwapStep {
+                a_for_b,
+                amount_in,
+                min_amount_out,
+            }
+        }
+
+        #[ink::test]
+        fn batch_swap_chains_steps() {
+            let mut dex = SimpleDex::new(10_000, 10_000, 0);
+            // 1_000 A -> 909 B, then the 909 B back -> 999 A
+            let out = dex.batch_swap(vec![step(true, Some(1_000), 900), step(false, None, 990)], None);
+            assert_eq!(out, Ok(999));
+            assert_eq!(dex.get_reserves(), (10_001, 10_000));
+            assert_eq!(dex.stats().total_swaps, 2);
+        }
+
+        #[ink::test]
+        fn failing_step_fails_the_whole_batch_with_slippage_exceeded() {
+            let mut dex = SimpleDex::new(10_000, 10_000, 0);
+            let batch = vec![
+                step(true, Some(1_000), 900),
+                step(false, Some(500), 400),
+                step(true, Some(1_000), 1_000),
+            ];
+            assert_eq!(dex.batch_swap(batch, None), Err(DexError::SlippageExceeded));
+            assert_eq!(dex.get_reserves(), (10_000, 10_000));
+            assert_eq!(dex.stats(), DexStats::default());
+            assert_eq!(dex.volume_this_block(), 0);
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+        use mock_psp22::MockPsp22Ref;
+        use psp22_traits::PSP22;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn swap_moves_real_tokens(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let mut tokens = Vec::new();
+            for _ in 0..2 {
+                let token = client
+                    .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(100_000), 0, None)
+                    .await
+                    .expect("token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let (token_a, token_b) = (tokens[0].clone(), tokens[1].clone());
+
+            let constructor = DexRef::new_with_tokens(token_a.clone(), token_b.clone(), 0);
+            let dex = client
+                .instantiate("dex", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("dex instantiate failed")
+                .account_id;
+
+            for token in [&token_a, &token_b] {
+                let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(dex.clone(), 20_000));
+                client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+            }
+
+            // Deep enough that the swap leaves both reserves above `MINIMUM_RESERVE`
+            let add = build_message::<DexRef>(dex.clone()).call(|dex| dex.add_liquidity(10_000, 10_000));
+            client.call(&ink_e2e::alice(), add, 0, None).await.expect("add_liquidity failed");
+
+            let swap = build_message::<DexRef>(dex.clone()).call(|dex| dex.swap_a_for_b(100, 0, None));
+            let amount_out = client
+                .call(&ink_e2e::alice(), swap, 0, None)
+                .await
+                .expect("swap failed")
+                .return_value()
+                .expect("swap rejected");
+            assert!(amount_out > 0);
+
+            let balance_a = build_message::<MockPsp22Ref>(token_a.clone()).call(|t| t.balance_of(dex.clone()));
+            let balance_b = build_message::<MockPsp22Ref>(token_b.clone()).call(|t| t.balance_of(dex.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &balance_a, 0, None).await.return_value(), 10_100);
+            assert_eq!(
+                client.call_dry_run(&ink_e2e::alice(), &balance_b, 0, None).await.return_value(),
+                10_000 - amount_out
+            );
+
+            Ok(())
+        }
+    }
+}
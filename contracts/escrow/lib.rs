@@ -0,0 +1,616 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[ink::contract]
+mod escrow {
+    use ink::codegen::TraitCallBuilder;
+    use ink::contract_ref;
+    use ink::prelude::vec::Vec;
+    use psp22_traits::PSP22;
+    use shared::errors::flatten_call;
+
+    /// Denominator for fees expressed in basis points
+    const BPS_DENOMINATOR: Balance = 10_000;
+
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum EscrowError {
+        AlreadyFunded,
+        NotFunded,
+        AlreadyReleased,
+        NotArbiter,
+        NotBuyer,
+        NotParty,
+        InvalidSettlement,
+        TransferFailed,
+        WrongCurrency,
+    }
+
+    impl EscrowError {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                EscrowError::AlreadyFunded => "Escrow is already funded.",
+                EscrowError::NotFunded => "Escrow is not funded.",
+                EscrowError::AlreadyReleased => "Funds have already been released.",
+                EscrowError::NotArbiter => "Only the arbiter can rule on the funds.",
+                EscrowError::NotBuyer => "Only the buyer can confirm receipt.",
+                EscrowError::NotParty => "Only the buyer or the seller can do this.",
+                EscrowError::InvalidSettlement => "Settlement must split exactly the escrowed amount.",
+                EscrowError::TransferFailed => "Transfer failed.",
+                EscrowError::WrongCurrency => "Escrow is held in the other currency.",
+            }
+        }
+    }
+
+    impl core::fmt::Display for EscrowError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    #[ink(storage)]
+    pub struct Escrow {
+        pub buyer: AccountId,
+        pub seller: AccountId,
+        pub arbiter: AccountId,
+        pub amount: Balance,
+        pub is_funded: bool,
+        pub is_released: bool,
+        // Split (buyer_amount, seller_amount) each party agreed to via `settle`
+        pub buyer_settlement: Option<(Balance, Balance)>,
+        pub seller_settlement: Option<(Balance, Balance)>,
+        // Fees paid to the arbiter and the platform out of the escrow when the
+        // arbiter rules on a dispute
+        pub arbiter_fee_bps: u16,
+        pub platform: AccountId,
+        pub platform_fee_bps: u16,
+        pub is_disputed: bool,
+        // PSP22 token the escrow is held in; native currency if `None`
+        pub token: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct Funded {
+        #[ink(topic)]
+        buyer: AccountId,
+        #[ink(topic)]
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Released {
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Refunded {
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Disputed {
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Settled {
+        buyer_amount: Balance,
+        seller_amount: Balance,
+    }
+
+    impl Escrow {
+        #[ink(constructor)]
+        pub fn new(
+            seller: AccountId,
+            arbiter: AccountId,
+            arbiter_fee_bps: u16,
+            platform: AccountId,
+            platform_fee_bps: u16,
+            token: Option<AccountId>,
+        ) -> Self {
+            assert!(
+                arbiter_fee_bps as Balance + platform_fee_bps as Balance <= BPS_DENOMINATOR,
+                "Fees exceed 100%"
+            );
+            Self {
+                buyer: AccountId::from([0; 32]),
+                seller,
+                arbiter,
+                amount: 0,
+                is_funded: false,
+                is_released: false,
+                buyer_settlement: None,
+                seller_settlement: None,
+                arbiter_fee_bps,
+                platform,
+                platform_fee_bps,
+                is_disputed: false,
+                token,
+            }
+        }
+
+        /// Funds a native-currency escrow with the transferred value
+        #[ink(message, payable)]
+        pub fn fund(&mut self) -> Result<(), EscrowError> {
+            if self.token.is_some() {
+                return Err(EscrowError::WrongCurrency);
+            }
+            self.record_funding(self.env().caller(), self.env().transferred_value())
+        }
+
+        /// Funds a token escrow by pulling `amount` from the caller, who must have
+        /// approved the escrow for it
+        #[ink(message)]
+        pub fn fund_with_tokens(&mut self, amount: Balance) -> Result<(), EscrowError> {
+            let Some(token) = self.token else {
+                return Err(EscrowError::WrongCurrency);
+            };
+            if self.is_funded {
+                return Err(EscrowError::AlreadyFunded);
+            }
+
+            let caller = self.env().caller();
+            let mut token: contract_ref!(PSP22) = token.into();
+            let result = token
+                .call_mut()
+                .transfer_from(caller, self.env().account_id(), amount, Vec::new())
+                .try_invoke();
+            flatten_call(result, EscrowError::TransferFailed, EscrowError::TransferFailed)?;
+
+            self.record_funding(caller, amount)
+        }
+
+        fn record_funding(&mut self, buyer: AccountId, amount: Balance) -> Result<(), EscrowError> {
+            if self.is_funded {
+                return Err(EscrowError::AlreadyFunded);
+            }
+
+            self.buyer = buyer;
+            self.amount = amount;
+            self.is_funded = true;
+
+            self.env().emit_event(Funded { buyer, amount });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn release(&mut self) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+
+            if caller != self.arbiter {
+                return Err(EscrowError::NotArbiter);
+            }
+
+            if !self.is_funded {
+                return Err(EscrowError::NotFunded);
+            }
+
+            if self.is_released {
+                return Err(EscrowError::AlreadyReleased);
+            }
+
+            self.is_released = true;
+
+            let amount = self.pay_out_ruling(self.seller)?;
+
+            self.env().emit_event(Released {
+                to: self.seller,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn refund(&mut self) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+
+            if caller != self.arbiter {
+                return Err(EscrowError::NotArbiter);
+            }
+
+            if !self.is_funded {
+                return Err(EscrowError::NotFunded);
+            }
+
+            if self.is_released {
+                return Err(EscrowError::AlreadyReleased);
+            }
+
+            self.is_funded = false;
+
+            let amount = self.pay_out_ruling(self.buyer)?;
+
+            self.env().emit_event(Refunded {
+                to: self.buyer,
+                amount,
+            });
+
+            Ok(())
+        }
+
+        /// Buyer confirms the goods were received and releases the full amount to the
+        /// seller. No arbiter fee is charged.
+        #[ink(message)]
+        pub fn confirm_receipt(&mut self) -> Result<(), EscrowError> {
+            if self.env().caller() != self.buyer {
+                return Err(EscrowError::NotBuyer);
+            }
+
+            if !self.is_funded {
+                return Err(EscrowError::NotFunded);
+            }
+
+            if self.is_released {
+                return Err(EscrowError::AlreadyReleased);
+            }
+
+            self.is_released = true;
+
+            self.pay(self.seller, self.amount)?;
+
+            self.env().emit_event(Released {
+                to: self.seller,
+                amount: self.amount,
+            });
+
+            Ok(())
+        }
+
+        /// Lets the buyer or the seller hand the escrow to the arbiter. A ruling on a
+        /// disputed escrow costs `arbiter_fee_bps` of the amount for the arbiter and
+        /// `platform_fee_bps` for the platform.
+        #[ink(message)]
+        pub fn open_dispute(&mut self) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+
+            if caller != self.buyer && caller != self.seller {
+                return Err(EscrowError::NotParty);
+            }
+
+            if !self.is_funded {
+                return Err(EscrowError::NotFunded);
+            }
+
+            if self.is_released {
+                return Err(EscrowError::AlreadyReleased);
+            }
+
+            self.is_disputed = true;
+
+            self.env().emit_event(Disputed { by: caller });
+
+            Ok(())
+        }
+
+        /// Records the caller's agreement to split the held amount. Once buyer and seller
+        /// have both agreed to the same split, the funds are paid out without the arbiter.
+        #[ink(message)]
+        pub fn settle(&mut self, buyer_amount: Balance, seller_amount: Balance) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+
+            if caller != self.buyer && caller != self.seller {
+                return Err(EscrowError::NotParty);
+            }
+
+            if !self.is_funded {
+                return Err(EscrowError::NotFunded);
+            }
+
+            if self.is_released {
+                return Err(EscrowError::AlreadyReleased);
+            }
+
+            if buyer_amount.checked_add(seller_amount) != Some(self.amount) {
+                return Err(EscrowError::InvalidSettlement);
+            }
+
+            let split = Some((buyer_amount, seller_amount));
+            if caller == self.buyer {
+                self.buyer_settlement = split;
+            } else {
+                self.seller_settlement = split;
+            }
+
+            if self.buyer_settlement != self.seller_settlement {
+                return Ok(());
+            }
+
+            self.is_released = true;
+
+            if buyer_amount > 0 {
+                self.pay(self.buyer, buyer_amount)?;
+            }
+            if seller_amount > 0 {
+                self.pay(self.seller, seller_amount)?;
+            }
+
+            self.env().emit_event(Settled {
+                buyer_amount,
+                seller_amount,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_arbiter_fee_bps(&self) -> u16 {
+            self.arbiter_fee_bps
+        }
+
+        #[ink(message)]
+        pub fn get_platform_fee_bps(&self) -> u16 {
+            self.platform_fee_bps
+        }
+
+        #[ink(message)]
+        pub fn is_disputed(&self) -> bool {
+            self.is_disputed
+        }
+
+        /// Pays out an arbiter ruling: the arbiter and platform fees are deducted only
+        /// if the escrow was disputed. Both fees round down and `to` receives the rest,
+        /// so the escrow is always paid out in full. Returns the amount sent to `to`.
+        fn pay_out_ruling(&mut self, to: AccountId) -> Result<Balance, EscrowError> {
+            let (arbiter_fee, platform_fee) = if self.is_disputed {
+                (
+                    self.amount * self.arbiter_fee_bps as Balance / BPS_DENOMINATOR,
+                    self.amount * self.platform_fee_bps as Balance / BPS_DENOMINATOR,
+                )
+            } else {
+                (0, 0)
+            };
+
+            if arbiter_fee > 0 {
+                self.pay(self.arbiter, arbiter_fee)?;
+            }
+            if platform_fee > 0 {
+                self.pay(self.platform, platform_fee)?;
+            }
+            let amount = self.amount - arbiter_fee - platform_fee;
+            self.pay(to, amount)?;
+
+            Ok(amount)
+        }
+
+        /// Sends `amount` of the escrow's currency to `to`
+        fn pay(&self, to: AccountId, amount: Balance) -> Result<(), EscrowError> {
+            match self.token {
+                Some(token) => {
+                    let mut token: contract_ref!(PSP22) = token.into();
+                    let result = token.call_mut().transfer(to, amount, Vec::new()).try_invoke();
+                    flatten_call(result, EscrowError::TransferFailed, EscrowError::TransferFailed)
+                }
+                None => self.env().transfer(to, amount).map_err(|_| EscrowError::TransferFailed),
+            }
+        }
+
+        #[ink(message)]
+        pub fn get_status(&self) -> (AccountId, AccountId, AccountId, Balance, bool, bool) {
+            (
+                self.buyer,
+                self.seller,
+                self.arbiter,
+                self.amount,
+                self.is_funded,
+                self.is_released,
+            )
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        type Event = <Escrow as ::ink::reflect::ContractEventBase>::Type;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            test::set_caller::<DefaultEnvironment>(caller);
+        }
+
+        /// Escrow of 1_000 funded by Alice for Bob, with Charlie arbitrating for 5%
+        /// and no platform fee
+        fn setup() -> Escrow {
+            setup_with_fees(500, 0)
+        }
+
+        /// Escrow of 1_000 funded by Alice for Bob, with Charlie arbitrating and Eve
+        /// as the platform. The contract runs as Frank, since by default the off-chain
+        /// environment would run it as Alice and mix her balance with the escrow's.
+        fn setup_with_fees(arbiter_fee_bps: u16, platform_fee_bps: u16) -> Escrow {
+            test::set_callee::<DefaultEnvironment>(accounts().frank);
+            set_caller(accounts().alice);
+            let (bob, charlie, eve) = (accounts().bob, accounts().charlie, accounts().eve);
+            let mut escrow = Escrow::new(bob, charlie, arbiter_fee_bps, eve, platform_fee_bps, None);
+            test::set_account_balance::<DefaultEnvironment>(test::callee::<DefaultEnvironment>(), 1_000);
+            test::set_value_transferred::<DefaultEnvironment>(1_000);
+            escrow.fund().expect("escrow is not funded yet");
+            escrow
+        }
+
+        #[ink::test]
+        fn funding_records_the_buyer() {
+            let mut escrow = setup();
+            let (buyer, seller, arbiter, amount, is_funded, is_released) = escrow.get_status();
+            assert_eq!((buyer, seller, arbiter), (accounts().alice, accounts().bob, accounts().charlie));
+            assert_eq!((amount, is_funded, is_released), (1_000, true, false));
+            assert_eq!(escrow.fund(), Err(EscrowError::AlreadyFunded));
+        }
+
+        #[ink::test]
+        fn only_the_arbiter_rules() {
+            let mut escrow = setup();
+            set_caller(accounts().bob);
+            assert_eq!(escrow.release(), Err(EscrowError::NotArbiter));
+            set_caller(accounts().charlie);
+            assert_eq!(escrow.release(), Ok(()));
+            assert_eq!(escrow.release(), Err(EscrowError::AlreadyReleased));
+        }
+
+        #[ink::test]
+        fn disputed_ruling_pays_the_arbiter_fee() {
+            let mut escrow = setup();
+            set_caller(accounts().alice);
+            escrow.open_dispute().unwrap();
+            set_caller(accounts().charlie);
+            let arbiter_before = test::get_account_balance::<DefaultEnvironment>(accounts().charlie).unwrap();
+            escrow.release().unwrap();
+            let arbiter_after = test::get_account_balance::<DefaultEnvironment>(accounts().charlie).unwrap();
+            assert_eq!(arbiter_after - arbiter_before, 50);
+        }
+
+        #[ink::test]
+        fn cooperative_paths_pay_no_arbiter_fee() {
+            let balance = |account| test::get_account_balance::<DefaultEnvironment>(account).unwrap();
+            let (alice, bob, charlie) = (accounts().alice, accounts().bob, accounts().charlie);
+
+            let mut escrow = setup();
+            let before = (balance(bob), balance(charlie));
+            set_caller(alice);
+            escrow.confirm_receipt().unwrap();
+            assert_eq!((balance(bob) - before.0, balance(charlie) - before.1), (1_000, 0));
+
+            // Settling pays no fee even after a dispute was opened
+            let mut escrow = setup();
+            set_caller(alice);
+            escrow.open_dispute().unwrap();
+            let before = (balance(alice), balance(bob), balance(charlie));
+            escrow.settle(250, 750).unwrap();
+            set_caller(bob);
+            escrow.settle(250, 750).unwrap();
+            let received = (balance(alice) - before.0, balance(bob) - before.1, balance(charlie) - before.2);
+            assert_eq!(received, (250, 750, 0));
+        }
+
+        #[ink::test]
+        fn disputed_ruling_splits_the_fees_without_dust() {
+            // 3.33% and 1.67% of 1_000 round down to 33 and 16
+            let mut escrow = setup_with_fees(333, 167);
+            set_caller(accounts().bob);
+            escrow.open_dispute().unwrap();
+
+            let balance = |account| test::get_account_balance::<DefaultEnvironment>(account).unwrap();
+            let (bob, charlie, eve) = (accounts().bob, accounts().charlie, accounts().eve);
+            let before = [balance(bob), balance(charlie), balance(eve)];
+            set_caller(charlie);
+            escrow.release().unwrap();
+            let after = [balance(bob), balance(charlie), balance(eve)];
+
+            let received: Vec<Balance> = after.iter().zip(before).map(|(after, before)| after - before).collect();
+            assert_eq!(received, vec![951, 33, 16]);
+            assert_eq!(received.iter().sum::<Balance>(), 1_000);
+            assert_eq!(balance(test::callee::<DefaultEnvironment>()), 0);
+        }
+
+        #[ink::test]
+        fn matching_settlements_split_the_funds() {
+            let mut escrow = setup();
+            let balance = |account| test::get_account_balance::<DefaultEnvironment>(account).unwrap();
+            let (alice, bob) = (accounts().alice, accounts().bob);
+            let before = (balance(alice), balance(bob));
+
+            set_caller(alice);
+            assert_eq!(escrow.settle(700, 400), Err(EscrowError::InvalidSettlement));
+            escrow.settle(700, 300).unwrap();
+            assert!(!escrow.get_status().5);
+            assert_eq!((balance(alice), balance(bob)), before);
+
+            set_caller(bob);
+            escrow.settle(700, 300).unwrap();
+            assert!(escrow.get_status().5);
+            assert_eq!((balance(alice) - before.0, balance(bob) - before.1), (700, 300));
+            assert_eq!(balance(test::callee::<DefaultEnvironment>()), 0);
+        }
+
+        #[ink::test]
+        fn dispute_then_settle_emits_the_full_history() {
+            let mut escrow = setup();
+            set_caller(accounts().bob);
+            escrow.open_dispute().unwrap();
+            escrow.settle(400, 600).unwrap();
+            set_caller(accounts().alice);
+            escrow.settle(400, 600).unwrap();
+
+            let events: Vec<Event> = test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).expect("event decodes"))
+                .collect();
+            let (alice, bob) = (accounts().alice, accounts().bob);
+            assert!(
+                matches!(
+                    events[..],
+                    [
+                        Event::Funded(Funded { buyer, amount: 1_000 }),
+                        Event::Disputed(Disputed { by }),
+                        Event::Settled(Settled { buyer_amount: 400, seller_amount: 600 }),
+                    ] if buyer == alice && by == bob
+                ),
+                "unexpected events"
+            );
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+        use mock_psp22::MockPsp22Ref;
+        use psp22_traits::PSP22;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn token_escrow_pays_the_seller(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let constructor = EscrowRef::new(bob.clone(), charlie.clone(), 0, charlie.clone(), 0, Some(token.clone()));
+            let escrow = client
+                .instantiate("escrow", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("escrow instantiate failed")
+                .account_id;
+
+            let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(escrow.clone(), 400));
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+
+            let fund = build_message::<EscrowRef>(escrow.clone()).call(|escrow| escrow.fund_with_tokens(400));
+            client
+                .call(&ink_e2e::alice(), fund, 0, None)
+                .await
+                .expect("fund_with_tokens failed")
+                .return_value()
+                .expect("fund_with_tokens rejected");
+
+            let release = build_message::<EscrowRef>(escrow.clone()).call(|escrow| escrow.release());
+            client
+                .call(&ink_e2e::charlie(), release, 0, None)
+                .await
+                .expect("release failed")
+                .return_value()
+                .expect("release rejected");
+
+            let seller_balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(bob.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &seller_balance, 0, None).await.return_value(), 400);
+
+            Ok(())
+        }
+    }
+}
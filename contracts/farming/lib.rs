@@ -0,0 +1,1081 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[ink::contract]
+mod farming {
+    use ink::codegen::TraitCallBuilder;
+    use ink::contract_ref;
+    use ink::env::{
+        call::{build_call, ExecutionInput, Selector},
+        DefaultEnvironment,
+    };
+    use ink::prelude::{string::String, vec::Vec};
+    use ink::storage::Mapping;
+    use psp22_traits::PSP22;
+    use shared::errors::flatten_call;
+
+    /// Fixed-point precision of `acc_reward_per_share`
+    const ACC_PRECISION: Balance = 1_000_000_000_000;
+
+    /// Denominator for fees and multipliers expressed in basis points
+    const BPS_DENOMINATOR: Balance = 10_000;
+
+    /// Token id of a PSP34 collection
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum Id {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        U128(u128),
+        Bytes(Vec<u8>),
+    }
+
+    /// Error returned by the PSP34 collection
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP34Error {
+        Custom(String),
+        SelfApprove,
+        NotApproved,
+        TokenExists,
+        TokenNotExists,
+        SafeTransferCheckFailed(String),
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct StakeInfo {
+        amount: Balance,
+        // Rewards already accounted for at the current `acc_reward_per_share`
+        reward_debt: Balance,
+        // Fee share already accounted for at the current `acc_fee_per_share`
+        fee_debt: Balance,
+        // Rewards harvested on stake/withdraw but not yet claimed
+        unclaimed: Balance,
+        last_staked: BlockNumber,
+        // NFT of the boost collection locked in the farm, if any
+        nft: Option<Id>,
+    }
+
+    #[ink(storage)]
+    pub struct Farming {
+        total_staked: Balance,
+        // Sum of the stakers' boosted amounts; rewards are shared by weight
+        total_weight: Balance,
+        // Rewards emitted per block, shared pro-rata between stakers
+        reward_rate: Balance,
+        // Block emission stops at once rewards are funded with `notify_reward`;
+        // `None` while the constructor's rate emits indefinitely
+        period_finish: Option<BlockNumber>,
+        acc_reward_per_share: Balance,
+        last_reward_block: BlockNumber,
+        stakers: Mapping<AccountId, StakeInfo>,
+        // Fees skimmed on stake/withdraw, in basis points of the amount
+        deposit_fee_bps: u16,
+        withdraw_fee_bps: u16,
+        // Deposit/withdraw fees, in the stake token, not yet compounded into stakes
+        reward_pool: Balance,
+        // Fees per staked unit, shared by stake amount rather than boosted weight
+        acc_fee_per_share: Balance,
+        // PSP34 collection whose NFTs boost rewards, and the multiplier they grant
+        nft_collection: AccountId,
+        nft_boost_bps: u32,
+        // PSP22 token staked in the farm; stakes are only recorded if `None`
+        stake_token: Option<AccountId>,
+        owner: AccountId,
+        // Older farm allowed to move its stakers' positions into this one
+        migration_source: Option<AccountId>,
+        // Newer farm this one's stakers may move their positions to
+        migration_target: Option<AccountId>,
+        // Pool `claim_and_zap` sells rewards to for the staked asset
+        zap_pool: Option<AccountId>,
+    }
+
+    impl Farming {
+        #[ink(constructor)]
+        pub fn new(
+            reward_rate: Balance,
+            deposit_fee_bps: u16,
+            withdraw_fee_bps: u16,
+            nft_collection: AccountId,
+            nft_boost_bps: u32,
+            stake_token: Option<AccountId>,
+        ) -> Self {
+            assert!(
+                deposit_fee_bps as Balance <= BPS_DENOMINATOR && withdraw_fee_bps as Balance <= BPS_DENOMINATOR,
+                "Fee exceeds 100%"
+            );
+            assert!(nft_boost_bps as Balance >= BPS_DENOMINATOR, "Boost below 1x");
+            Self {
+                total_staked: 0,
+                total_weight: 0,
+                reward_rate,
+                period_finish: None,
+                acc_reward_per_share: 0,
+                last_reward_block: Self::env().block_number(),
+                stakers: Mapping::new(),
+                deposit_fee_bps,
+                withdraw_fee_bps,
+                reward_pool: 0,
+                acc_fee_per_share: 0,
+                nft_collection,
+                nft_boost_bps,
+                stake_token,
+                owner: Self::env().caller(),
+                migration_source: None,
+                migration_target: None,
+                zap_pool: None,
+            }
+        }
+
+        #[ink(message)]
+        pub fn stake(&mut self, amount: Balance) {
+            let caller = self.env().caller();
+            let block_number = self.env().block_number();
+
+            if let Some(token) = self.stake_token {
+                let mut token: contract_ref!(PSP22) = token.into();
+                let result = token
+                    .call_mut()
+                    .transfer_from(caller, self.env().account_id(), amount, Vec::new())
+                    .try_invoke();
+                flatten_call(result, "Stake token call failed", "Stake token transfer rejected")
+                    .unwrap_or_else(|error| panic!("{}", error));
+            }
+
+            self.update_pool();
+            let mut stake_info = self.stakers.get(caller).unwrap_or_default();
+
+            self.settle(&mut stake_info);
+
+            let fee = self.distribute_fee(amount * self.deposit_fee_bps as Balance / BPS_DENOMINATOR);
+            let net_amount = amount - fee;
+
+            self.total_weight -= self.weight(&stake_info);
+            stake_info.amount += net_amount;
+            self.total_weight += self.weight(&stake_info);
+            self.reset_debts(&mut stake_info);
+            stake_info.last_staked = block_number;
+
+            self.total_staked += net_amount;
+            self.stakers.insert(caller, &stake_info);
+        }
+
+        #[ink(message)]
+        pub fn withdraw(&mut self, amount: Balance) {
+            let caller = self.env().caller();
+            let block_number = self.env().block_number();
+
+            self.update_pool();
+            let mut stake_info = self.stakers.get(caller).expect("No stake found");
+            self.settle(&mut stake_info);
+
+            assert!(stake_info.amount >= amount, "Insufficient staked balance");
+
+            self.total_weight -= self.weight(&stake_info);
+            stake_info.amount -= amount;
+            self.total_weight += self.weight(&stake_info);
+            self.total_staked -= amount;
+
+            // The fee is taken out of the withdrawn amount and shared by the remaining stakers
+            let fee = self.distribute_fee(amount * self.withdraw_fee_bps as Balance / BPS_DENOMINATOR);
+
+            self.reset_debts(&mut stake_info);
+            stake_info.last_staked = block_number;
+
+            self.stakers.insert(caller, &stake_info);
+
+            if let Some(token) = self.stake_token {
+                let mut token: contract_ref!(PSP22) = token.into();
+                let result = token.call_mut().transfer(caller, amount - fee, Vec::new()).try_invoke();
+                flatten_call(result, "Stake token call failed", "Stake token transfer rejected")
+                    .unwrap_or_else(|error| panic!("{}", error));
+            }
+        }
+
+        #[ink(message)]
+        pub fn claim(&mut self) {
+            let caller = self.env().caller();
+
+            self.update_pool();
+            let mut stake_info = self.stakers.get(caller).expect("No stake found");
+
+            self.settle(&mut stake_info);
+            let pending = stake_info.unclaimed;
+
+            self.env()
+                .transfer(caller, pending)
+                .expect("Transfer failed");
+
+            stake_info.unclaimed = 0;
+            self.reset_debts(&mut stake_info);
+            self.stakers.insert(caller, &stake_info);
+        }
+
+        /// Claims the caller's rewards, swaps them for the staked asset through the zap
+        /// pool and stakes the proceeds, reverting if they fall below `min_restake`. The
+        /// pool is called like the market maker's `swap_eth_for_tokens`: the rewards are
+        /// sent with the call and it returns the amount bought for the farm. With a stake
+        /// token the pool must pay the tokens to the farm, and only the growth of the
+        /// farm's token balance is restaked, so a pool that merely credits its own
+        /// ledger restakes nothing. Returns the amount restaked.
+        #[ink(message)]
+        pub fn claim_and_zap(&mut self, pool: AccountId, min_restake: Balance) -> Balance {
+            assert!(Some(pool) == self.zap_pool, "Pool is not the zap pool");
+            let caller = self.env().caller();
+
+            self.update_pool();
+            let mut stake_info = self.stakers.get(caller).expect("No stake found");
+            self.settle(&mut stake_info);
+            let rewards = stake_info.unclaimed;
+            assert!(rewards > 0, "No rewards to zap");
+
+            let balance_before = self.stake_token.map(|token| self.stake_token_balance(token));
+            let bought = build_call::<DefaultEnvironment>()
+                .call(pool)
+                .transferred_value(rewards)
+                .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!("swap_eth_for_tokens"))))
+                .returns::<Balance>()
+                .try_invoke()
+                .expect("Zap swap failed")
+                .expect("Zap swap failed");
+            let restake = match (self.stake_token, balance_before) {
+                (Some(token), Some(before)) => self.stake_token_balance(token).saturating_sub(before),
+                _ => bought,
+            };
+            assert!(restake > 0, "Zap swap bought no stake tokens");
+            assert!(restake >= min_restake, "Slippage: too little restaked");
+
+            stake_info.unclaimed = 0;
+            self.total_weight -= self.weight(&stake_info);
+            stake_info.amount += restake;
+            self.total_weight += self.weight(&stake_info);
+            self.reset_debts(&mut stake_info);
+            stake_info.last_staked = self.env().block_number();
+
+            self.total_staked += restake;
+            self.stakers.insert(caller, &stake_info);
+            restake
+        }
+
+        /// Funds `amount` of rewards, sent with the call, to be emitted evenly over the
+        /// next `duration` blocks. Rewards left over from a running period are rolled
+        /// into the new one, so the rate becomes `(amount + leftover) / duration`.
+        /// Owner-only.
+        #[ink(message, payable)]
+        pub fn notify_reward(&mut self, amount: Balance, duration: BlockNumber) {
+            assert!(self.env().caller() == self.owner, "Only the owner can fund rewards");
+            assert!(self.env().transferred_value() == amount, "Transferred value must match the amount");
+            assert!(duration > 0, "Reward duration must be positive");
+
+            self.update_pool();
+            let block_number = self.env().block_number();
+            let leftover = match self.period_finish {
+                Some(finish) if finish > block_number => (finish - block_number) as Balance * self.reward_rate,
+                _ => 0,
+            };
+            self.reward_rate = (amount + leftover) / duration as Balance;
+            self.period_finish = Some(block_number + duration);
+        }
+
+        fn stake_token_balance(&self, token: AccountId) -> Balance {
+            let token: contract_ref!(PSP22) = token.into();
+            token.balance_of(self.env().account_id())
+        }
+
+        #[ink(message)]
+        pub fn set_zap_pool(&mut self, pool: Option<AccountId>) {
+            assert!(self.env().caller() == self.owner, "Only the owner can set the zap pool");
+            self.zap_pool = pool;
+        }
+
+        /// Locks an NFT of the boost collection in the farm, multiplying the caller's
+        /// reward weight by `nft_boost_bps` until it is unstaked. The caller must own
+        /// the NFT and have approved the farm for it.
+        #[ink(message)]
+        pub fn stake_nft(&mut self, id: Id) {
+            let caller = self.env().caller();
+
+            self.update_pool();
+            let mut stake_info = self.stakers.get(caller).unwrap_or_default();
+            assert!(stake_info.nft.is_none(), "An NFT is already staked");
+            assert!(self.nft_owner(id.clone()) == Some(caller), "Caller does not own the NFT");
+
+            self.nft_transfer(self.env().account_id(), id.clone());
+
+            self.settle(&mut stake_info);
+            self.total_weight -= self.weight(&stake_info);
+            stake_info.nft = Some(id);
+            self.total_weight += self.weight(&stake_info);
+            self.reset_debts(&mut stake_info);
+
+            self.stakers.insert(caller, &stake_info);
+        }
+
+        /// Returns the caller's staked NFT and removes the boost
+        #[ink(message)]
+        pub fn unstake_nft(&mut self) {
+            let caller = self.env().caller();
+
+            self.update_pool();
+            let mut stake_info = self.stakers.get(caller).expect("No stake found");
+            let id = stake_info.nft.clone().expect("No NFT staked");
+
+            self.settle(&mut stake_info);
+            self.total_weight -= self.weight(&stake_info);
+            stake_info.nft = None;
+            self.total_weight += self.weight(&stake_info);
+            self.reset_debts(&mut stake_info);
+
+            self.stakers.insert(caller, &stake_info);
+            self.nft_transfer(caller, id);
+        }
+
+        /// Lets `source` migrate positions into this farm, or nobody if `None`
+        #[ink(message)]
+        pub fn set_migration_source(&mut self, source: Option<AccountId>) {
+            assert!(self.env().caller() == self.owner, "Only the owner can set the migration source");
+            self.migration_source = source;
+        }
+
+        /// Lets stakers migrate their positions to `target`, or nowhere if `None`
+        #[ink(message)]
+        pub fn set_migration_target(&mut self, target: Option<AccountId>) {
+            assert!(self.env().caller() == self.owner, "Only the owner can set the migration target");
+            self.migration_target = target;
+        }
+
+        /// Moves the caller's whole position into `new_farm`, which must be this farm's
+        /// migration target and list this farm as its migration source. The staked
+        /// tokens and NFT are transferred to it and the unclaimed rewards are sent along
+        /// with the call. Returns the rewards carried over.
+        #[ink(message)]
+        pub fn migrate(&mut self, new_farm: AccountId) -> Balance {
+            // Otherwise a staker could name a contract of their own and leave without
+            // paying the withdraw fee
+            assert!(Some(new_farm) == self.migration_target, "Farm is not the migration target");
+            let caller = self.env().caller();
+
+            self.update_pool();
+            let mut stake_info = self.stakers.get(caller).expect("No stake found");
+            self.settle(&mut stake_info);
+            let rewards = stake_info.unclaimed;
+
+            self.total_weight -= self.weight(&stake_info);
+            self.total_staked -= stake_info.amount;
+            self.stakers.remove(caller);
+
+            if let Some(token) = self.stake_token {
+                let mut token: contract_ref!(PSP22) = token.into();
+                let result = token.call_mut().transfer(new_farm, stake_info.amount, Vec::new()).try_invoke();
+                flatten_call(result, "Stake token call failed", "Stake token transfer rejected")
+                    .unwrap_or_else(|error| panic!("{}", error));
+            }
+            if let Some(id) = stake_info.nft.clone() {
+                self.nft_transfer(new_farm, id);
+            }
+
+            build_call::<DefaultEnvironment>()
+                .call(new_farm)
+                .transferred_value(rewards)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("accept_migration")))
+                        .push_arg(caller)
+                        .push_arg(stake_info.amount)
+                        .push_arg(rewards)
+                        .push_arg(stake_info.last_staked)
+                        .push_arg(stake_info.nft),
+                )
+                .returns::<()>()
+                .try_invoke()
+                .expect("Migration call failed")
+                .expect("Migration call failed");
+
+            rewards
+        }
+
+        /// Receives a position migrated out of the migration source. The staked tokens
+        /// and NFT are already held by this farm when it is called; the carried-over
+        /// rewards come with the call.
+        #[ink(message, payable)]
+        pub fn accept_migration(
+            &mut self,
+            staker: AccountId,
+            amount: Balance,
+            rewards: Balance,
+            last_staked: BlockNumber,
+            nft: Option<Id>,
+        ) {
+            assert!(
+                Some(self.env().caller()) == self.migration_source,
+                "Caller is not the migration source"
+            );
+            assert!(self.env().transferred_value() == rewards, "Rewards were not transferred");
+            assert!(!self.stakers.contains(staker), "Staker already has a position");
+
+            self.update_pool();
+            let mut stake_info = StakeInfo {
+                amount,
+                reward_debt: 0,
+                fee_debt: 0,
+                unclaimed: rewards,
+                last_staked,
+                nft,
+            };
+            self.reset_debts(&mut stake_info);
+
+            self.total_weight += self.weight(&stake_info);
+            self.total_staked += amount;
+            self.stakers.insert(staker, &stake_info);
+        }
+
+        #[ink(message)]
+        pub fn pending_reward(&self, staker: AccountId) -> Balance {
+            if let Some(stake_info) = self.stakers.get(staker) {
+                let acc = self.current_acc_reward_per_share();
+                let accrued = self.weight(&stake_info) * acc / ACC_PRECISION - stake_info.reward_debt;
+                return stake_info.unclaimed + accrued;
+            }
+            0
+        }
+
+        #[ink(message)]
+        pub fn get_staked_amount(&self, staker: AccountId) -> Balance {
+            if let Some(stake_info) = self.stakers.get(staker) {
+                return stake_info.amount;
+            }
+            0
+        }
+
+        #[ink(message)]
+        pub fn get_stake_info(&self, staker: AccountId) -> Option<StakeInfo> {
+            self.stakers.get(staker)
+        }
+
+        #[ink(message)]
+        pub fn get_staked_nft(&self, staker: AccountId) -> Option<Id> {
+            self.stakers.get(staker).and_then(|stake_info| stake_info.nft)
+        }
+
+        #[ink(message)]
+        pub fn get_total_staked(&self) -> Balance {
+            self.total_staked
+        }
+
+        #[ink(message)]
+        pub fn get_reward_rate(&self) -> Balance {
+            self.reward_rate
+        }
+
+        #[ink(message)]
+        pub fn get_period_finish(&self) -> Option<BlockNumber> {
+            self.period_finish
+        }
+
+        #[ink(message)]
+        pub fn get_reward_pool(&self) -> Balance {
+            self.reward_pool
+        }
+
+        /// Share of the deposit/withdraw fees `staker` will have compounded into their
+        /// stake on their next interaction with the farm
+        #[ink(message)]
+        pub fn pending_fees(&self, staker: AccountId) -> Balance {
+            self.stakers.get(staker).map_or(0, |stake_info| self.accrued_fees(&stake_info))
+        }
+
+        #[ink(message)]
+        pub fn get_fees_bps(&self) -> (u16, u16) {
+            (self.deposit_fee_bps, self.withdraw_fee_bps)
+        }
+
+        /// Adds `fee` to the reward pool, shared pro-rata by the current stakes, and
+        /// returns the fee charged. The fee is in the stake token, so it is kept apart
+        /// from the native rewards. Nothing is charged while nothing else is staked, as
+        /// no stake would earn it.
+        fn distribute_fee(&mut self, fee: Balance) -> Balance {
+            if fee == 0 || self.total_staked == 0 {
+                return 0;
+            }
+            self.reward_pool += fee;
+            self.acc_fee_per_share += fee * ACC_PRECISION / self.total_staked;
+            fee
+        }
+
+        /// Settles what `stake_info` earned since its debts were last reset: rewards
+        /// become claimable and the fee share is compounded into the stake. Callers
+        /// reset the debts once they are done changing the stake.
+        fn settle(&mut self, stake_info: &mut StakeInfo) {
+            stake_info.unclaimed += self.accrued(stake_info);
+            // Rounding may credit a unit more than was distributed; the pool caps it
+            let fees = self.accrued_fees(stake_info).min(self.reward_pool);
+            if fees > 0 {
+                self.total_weight -= self.weight(stake_info);
+                stake_info.amount += fees;
+                self.total_weight += self.weight(stake_info);
+                self.total_staked += fees;
+                self.reward_pool -= fees;
+            }
+        }
+
+        fn reset_debts(&self, stake_info: &mut StakeInfo) {
+            stake_info.reward_debt = self.weight(stake_info) * self.acc_reward_per_share / ACC_PRECISION;
+            stake_info.fee_debt = stake_info.amount * self.acc_fee_per_share / ACC_PRECISION;
+        }
+
+        /// Brings `acc_reward_per_share` up to the current block
+        fn update_pool(&mut self) {
+            self.acc_reward_per_share = self.current_acc_reward_per_share();
+            self.last_reward_block = self.env().block_number();
+        }
+
+        fn current_acc_reward_per_share(&self) -> Balance {
+            let block_number = self.env().block_number();
+            let emitting_until = self.period_finish.map_or(block_number, |finish| finish.min(block_number));
+            // Emission for blocks in which nothing was staked is skipped instead of
+            // being divided by a zero total stake.
+            if emitting_until <= self.last_reward_block || self.total_weight == 0 {
+                return self.acc_reward_per_share;
+            }
+            let blocks = (emitting_until - self.last_reward_block) as Balance;
+            self.acc_reward_per_share + blocks * self.reward_rate * ACC_PRECISION / self.total_weight
+        }
+
+        /// Staked amount, multiplied by the NFT boost while one is staked
+        fn weight(&self, stake_info: &StakeInfo) -> Balance {
+            match stake_info.nft {
+                Some(_) => stake_info.amount * self.nft_boost_bps as Balance / BPS_DENOMINATOR,
+                None => stake_info.amount,
+            }
+        }
+
+        /// Rewards earned by `stake_info` since its debt was last settled
+        fn accrued(&self, stake_info: &StakeInfo) -> Balance {
+            self.weight(stake_info) * self.acc_reward_per_share / ACC_PRECISION - stake_info.reward_debt
+        }
+
+        /// Fee share earned by `stake_info` since its debt was last settled
+        fn accrued_fees(&self, stake_info: &StakeInfo) -> Balance {
+            stake_info.amount * self.acc_fee_per_share / ACC_PRECISION - stake_info.fee_debt
+        }
+
+        fn nft_owner(&self, id: Id) -> Option<AccountId> {
+            build_call::<DefaultEnvironment>()
+                .call(self.nft_collection)
+                .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP34::owner_of"))).push_arg(id))
+                .returns::<Option<AccountId>>()
+                .try_invoke()
+                .expect("NFT ownership check failed")
+                .expect("NFT ownership check failed")
+        }
+
+        fn nft_transfer(&self, to: AccountId, id: Id) {
+            build_call::<DefaultEnvironment>()
+                .call(self.nft_collection)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP34::transfer")))
+                        .push_arg(to)
+                        .push_arg(id)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP34Error>>()
+                .try_invoke()
+                .expect("NFT transfer call failed")
+                .expect("NFT transfer call failed")
+                .expect("NFT transfer rejected");
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            test::set_caller::<DefaultEnvironment>(caller);
+        }
+
+        fn advance_blocks(blocks: u32) {
+            for _ in 0..blocks {
+                test::advance_block::<DefaultEnvironment>();
+            }
+        }
+
+        /// Farm emitting 10 per block with the given deposit fee and no withdraw fee
+        fn setup(deposit_fee_bps: u16) -> Farming {
+            set_caller(accounts().alice);
+            Farming::new(10, deposit_fee_bps, 0, accounts().frank, 15_000, None)
+        }
+
+        #[ink::test]
+        fn deposit_fee_is_skimmed() {
+            let mut farming = setup(100);
+            farming.stake(1_000);
+            set_caller(accounts().bob);
+            farming.stake(1_000);
+            assert_eq!(farming.get_staked_amount(accounts().bob), 990);
+            assert_eq!(farming.get_total_staked(), 1_990);
+            assert_eq!(farming.get_reward_pool(), 10);
+            assert_eq!(farming.pending_fees(accounts().alice), 10);
+        }
+
+        #[ink::test]
+        fn fees_are_not_charged_on_an_empty_farm() {
+            set_caller(accounts().alice);
+            let mut farming = Farming::new(10, 100, 100, accounts().frank, 15_000, None);
+            // No other stake would earn the fees, so neither is taken
+            farming.stake(1_000);
+            assert_eq!(farming.get_staked_amount(accounts().alice), 1_000);
+            farming.withdraw(1_000);
+            assert_eq!(farming.get_total_staked(), 0);
+            assert_eq!(farming.get_reward_pool(), 0);
+
+            farming.stake(500);
+            set_caller(accounts().bob);
+            farming.stake(1_000);
+            assert_eq!(farming.get_reward_pool(), 10);
+            assert_eq!(farming.pending_fees(accounts().alice), 10);
+        }
+
+        #[ink::test]
+        fn fees_compound_into_the_other_stakes() {
+            let mut farming = setup(100);
+            farming.stake(1_000);
+            assert_eq!(farming.get_staked_amount(accounts().alice), 1_000);
+
+            // Bob's 20 fee goes to Alice in the stake token, not as native rewards
+            set_caller(accounts().bob);
+            farming.stake(2_000);
+            assert_eq!(farming.get_reward_pool(), 20);
+            assert_eq!(farming.pending_fees(accounts().alice), 20);
+            assert_eq!(farming.pending_fees(accounts().bob), 0);
+            assert_eq!(farming.pending_reward(accounts().alice), 0);
+
+            set_caller(accounts().alice);
+            farming.withdraw(0);
+            assert_eq!(farming.get_staked_amount(accounts().alice), 1_020);
+            assert_eq!(farming.get_total_staked(), 1_020 + 1_980);
+            assert_eq!(farming.get_reward_pool(), 0);
+            assert_eq!(farming.pending_fees(accounts().alice), 0);
+
+            // Native rewards are shared by the compounded stakes
+            advance_blocks(6);
+            assert_eq!(farming.pending_reward(accounts().alice), 20);
+            assert_eq!(farming.pending_reward(accounts().bob), 39);
+        }
+
+        #[ink::test]
+        fn rewards_accrue_per_block() {
+            let mut farming = setup(0);
+            farming.stake(1_000);
+            advance_blocks(3);
+            assert_eq!(farming.pending_reward(accounts().alice), 30);
+        }
+
+        #[ink::test]
+        fn emission_is_skipped_while_nothing_is_staked() {
+            let mut farming = setup(0);
+            farming.stake(1_000);
+            advance_blocks(2);
+            farming.withdraw(1_000);
+            assert_eq!(farming.get_total_staked(), 0);
+
+            // Nobody earns the five empty blocks
+            advance_blocks(5);
+            assert_eq!(farming.pending_reward(accounts().alice), 20);
+            farming.stake(1_000);
+            assert_eq!(farming.pending_reward(accounts().alice), 20);
+
+            advance_blocks(3);
+            assert_eq!(farming.pending_reward(accounts().alice), 50);
+        }
+
+        #[ink::test]
+        fn rewards_are_shared_by_stake() {
+            let mut farming = setup(0);
+            farming.stake(1_000);
+            set_caller(accounts().bob);
+            farming.stake(3_000);
+            advance_blocks(4);
+            assert_eq!(farming.pending_reward(accounts().alice), 10);
+            assert_eq!(farming.pending_reward(accounts().bob), 30);
+        }
+
+        #[ink::test]
+        fn top_up_rolls_leftover_rewards_into_the_new_period() {
+            let mut farming = setup(0);
+            farming.stake(1_000);
+
+            test::set_value_transferred::<DefaultEnvironment>(1_000);
+            farming.notify_reward(1_000, 100);
+            assert_eq!(farming.get_reward_rate(), 10);
+            advance_blocks(40);
+            assert_eq!(farming.pending_reward(accounts().alice), 400);
+
+            // 60 blocks at 10 are left over: (600 + 600) / 50
+            test::set_value_transferred::<DefaultEnvironment>(600);
+            farming.notify_reward(600, 50);
+            assert_eq!(farming.get_reward_rate(), 24);
+
+            // Emission stops at the end of the period, having paid out both budgets
+            advance_blocks(60);
+            assert_eq!(farming.pending_reward(accounts().alice), 1_600);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Insufficient staked balance")]
+        fn cannot_withdraw_more_than_staked() {
+            let mut farming = setup(100);
+            farming.stake(1_000);
+            farming.withdraw(1_001);
+        }
+
+        #[ink::test]
+        fn accepted_migration_keeps_the_position() {
+            let mut farming = setup(0);
+            farming.set_migration_source(Some(accounts().django));
+
+            set_caller(accounts().django);
+            test::set_value_transferred::<DefaultEnvironment>(70);
+            farming.accept_migration(accounts().bob, 2_000, 70, 5, None);
+
+            let stake_info = farming.get_stake_info(accounts().bob).expect("position was migrated");
+            assert_eq!((stake_info.amount, stake_info.last_staked), (2_000, 5));
+            assert_eq!(farming.pending_reward(accounts().bob), 70);
+            assert_eq!(farming.get_total_staked(), 2_000);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Pool is not the zap pool")]
+        fn zap_only_goes_through_the_zap_pool() {
+            let mut farming = setup(0);
+            farming.set_zap_pool(Some(accounts().django));
+            farming.stake(1_000);
+            advance_blocks(3);
+            farming.claim_and_zap(accounts().eve, 0);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Farm is not the migration target")]
+        fn positions_only_migrate_to_the_migration_target() {
+            let mut farming = setup(0);
+            farming.set_migration_target(Some(accounts().django));
+            farming.stake(1_000);
+            farming.migrate(accounts().eve);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Caller is not the migration source")]
+        fn only_the_migration_source_can_migrate_in() {
+            let mut farming = setup(0);
+            farming.set_migration_source(Some(accounts().django));
+            set_caller(accounts().eve);
+            farming.accept_migration(accounts().eve, 1_000_000, 0, 0, None);
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+        use ink_e2e::subxt::dynamic::Value;
+        use market_maker::MarketMakerRef;
+        use mock_psp22::MockPsp22Ref;
+        use mock_psp34::{MockPsp34Ref, PSP34};
+        use psp22_traits::PSP22;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn stake_and_withdraw_move_real_tokens(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let constructor = FarmingRef::new(0, 0, 0, alice.clone(), 10_000, Some(token.clone()));
+            let farm = client
+                .instantiate("farming", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("farming instantiate failed")
+                .account_id;
+
+            let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(farm.clone(), 500));
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+
+            let stake = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.stake(500));
+            client.call(&ink_e2e::alice(), stake, 0, None).await.expect("stake failed");
+
+            let withdraw = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.withdraw(200));
+            client.call(&ink_e2e::alice(), withdraw, 0, None).await.expect("withdraw failed");
+
+            let farm_balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(farm.clone()));
+            let alice_balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(alice.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &farm_balance, 0, None).await.return_value(), 300);
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &alice_balance, 0, None).await.return_value(), 9_700);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn migration_carries_the_position_over(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let mut farms = Vec::new();
+            for reward_rate in [10, 0] {
+                let constructor = FarmingRef::new(reward_rate, 0, 0, alice.clone(), 10_000, Some(token.clone()));
+                let farm = client
+                    .instantiate("farming", &ink_e2e::alice(), constructor, 0, None)
+                    .await
+                    .expect("farming instantiate failed")
+                    .account_id;
+                farms.push(farm);
+            }
+            let (old_farm, new_farm) = (farms[0].clone(), farms[1].clone());
+
+            // The old farm pays the carried-over rewards out of its native balance
+            let fund = vec![
+                Value::unnamed_variant("Id", [Value::from_bytes(&old_farm)]),
+                Value::u128(1_000_000_000),
+            ];
+            client
+                .runtime_call(&ink_e2e::alice(), "Balances", "transfer", fund)
+                .await
+                .expect("funding the old farm failed");
+
+            let allow = build_message::<FarmingRef>(new_farm.clone())
+                .call(|farming| farming.set_migration_source(Some(old_farm.clone())));
+            client.call(&ink_e2e::alice(), allow, 0, None).await.expect("set_migration_source failed");
+            let allow = build_message::<FarmingRef>(old_farm.clone())
+                .call(|farming| farming.set_migration_target(Some(new_farm.clone())));
+            client.call(&ink_e2e::alice(), allow, 0, None).await.expect("set_migration_target failed");
+
+            let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(old_farm.clone(), 500));
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+            let stake = build_message::<FarmingRef>(old_farm.clone()).call(|farming| farming.stake(500));
+            client.call(&ink_e2e::alice(), stake, 0, None).await.expect("stake failed");
+
+            let old_info =
+                build_message::<FarmingRef>(old_farm.clone()).call(|farming| farming.get_stake_info(alice.clone()));
+            let before = client
+                .call_dry_run(&ink_e2e::alice(), &old_info, 0, None)
+                .await
+                .return_value()
+                .expect("stake recorded");
+
+            let migrate =
+                build_message::<FarmingRef>(old_farm.clone()).call(|farming| farming.migrate(new_farm.clone()));
+            let rewards = client
+                .call(&ink_e2e::alice(), migrate, 0, None)
+                .await
+                .expect("migrate failed")
+                .return_value();
+            assert!(rewards > 0);
+
+            let new_info =
+                build_message::<FarmingRef>(new_farm.clone()).call(|farming| farming.get_stake_info(alice.clone()));
+            let after = client
+                .call_dry_run(&ink_e2e::alice(), &new_info, 0, None)
+                .await
+                .return_value()
+                .expect("stake migrated");
+            assert_eq!((after.amount, after.last_staked, after.nft), (before.amount, before.last_staked, before.nft));
+
+            let pending =
+                build_message::<FarmingRef>(new_farm.clone()).call(|farming| farming.pending_reward(alice.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &pending, 0, None).await.return_value(), rewards);
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &old_info, 0, None).await.return_value(), None);
+
+            let balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(new_farm.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value(), 500);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp34/Cargo.toml")]
+        async fn staked_nft_boosts_rewards_until_unstaked(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let collection = client
+                .instantiate("mock_psp34", &ink_e2e::alice(), MockPsp34Ref::new(), 0, None)
+                .await
+                .expect("collection instantiate failed")
+                .account_id;
+            let mint = build_message::<MockPsp34Ref>(collection.clone())
+                .call(|collection| collection.mint(alice.clone(), mock_psp34::Id::U8(1)));
+            client.call(&ink_e2e::alice(), mint, 0, None).await.expect("mint failed");
+
+            // A staked NFT doubles the weight
+            let constructor = FarmingRef::new(300, 0, 0, collection.clone(), 20_000, None);
+            let farm = client
+                .instantiate("farming", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("farming instantiate failed")
+                .account_id;
+
+            for signer in [ink_e2e::alice(), ink_e2e::bob()] {
+                let stake = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.stake(500));
+                client.call(&signer, stake, 0, None).await.expect("stake failed");
+            }
+
+            let approve = build_message::<MockPsp34Ref>(collection.clone())
+                .call(|collection| collection.approve(farm.clone(), Some(mock_psp34::Id::U8(1)), true));
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+            let stake_nft = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.stake_nft(Id::U8(1)));
+            client.call(&ink_e2e::alice(), stake_nft, 0, None).await.expect("stake_nft failed");
+
+            let owner_of = build_message::<MockPsp34Ref>(collection.clone())
+                .call(|collection| collection.owner_of(mock_psp34::Id::U8(1)));
+            let nft_owner = client.call_dry_run(&ink_e2e::alice(), &owner_of, 0, None).await.return_value();
+            assert_eq!(nft_owner, Some(farm.clone()));
+
+            let alice_pending =
+                build_message::<FarmingRef>(farm.clone()).call(|farming| farming.pending_reward(alice.clone()));
+            let bob_pending =
+                build_message::<FarmingRef>(farm.clone()).call(|farming| farming.pending_reward(bob.clone()));
+
+            // Each call seals a block; while boosted Alice earns 200 of the 300 per block
+            let alice_boosted = client.call_dry_run(&ink_e2e::alice(), &alice_pending, 0, None).await.return_value();
+            let bob_boosted = client.call_dry_run(&ink_e2e::alice(), &bob_pending, 0, None).await.return_value();
+            let unstake_nft = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.unstake_nft());
+            client.call(&ink_e2e::alice(), unstake_nft, 0, None).await.expect("unstake_nft failed");
+            let alice_unboosted = client.call_dry_run(&ink_e2e::alice(), &alice_pending, 0, None).await.return_value();
+            let bob_unboosted = client.call_dry_run(&ink_e2e::alice(), &bob_pending, 0, None).await.return_value();
+            assert_eq!(alice_unboosted - alice_boosted, 200);
+            assert_eq!(bob_unboosted - bob_boosted, 100);
+
+            let nft_owner = client.call_dry_run(&ink_e2e::alice(), &owner_of, 0, None).await.return_value();
+            assert_eq!(nft_owner, Some(alice.clone()));
+            let staked_nft =
+                build_message::<FarmingRef>(farm.clone()).call(|farming| farming.get_staked_nft(alice.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &staked_nft, 0, None).await.return_value(), None);
+
+            // Back to an even split
+            let poke = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.stake(0));
+            client.call(&ink_e2e::bob(), poke, 0, None).await.expect("stake failed");
+            let alice_after = client.call_dry_run(&ink_e2e::alice(), &alice_pending, 0, None).await.return_value();
+            let bob_after = client.call_dry_run(&ink_e2e::alice(), &bob_pending, 0, None).await.return_value();
+            assert_eq!(alice_after - alice_unboosted, 150);
+            assert_eq!(bob_after - bob_unboosted, 150);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../market_maker/Cargo.toml")]
+        async fn zap_restakes_swapped_rewards(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let pool = client
+                .instantiate("market_maker", &ink_e2e::alice(), MarketMakerRef::new(2_000_000), 0, None)
+                .await
+                .expect("market maker instantiate failed")
+                .account_id;
+            // Two tokens per unit of native currency
+            let seed = build_message::<MarketMakerRef>(pool.clone()).call(|pool| pool.add_liquidity(2_000_000, 0));
+            client.call(&ink_e2e::alice(), seed, 1_000_000, None).await.expect("add_liquidity failed");
+
+            let constructor = FarmingRef::new(100, 0, 0, alice.clone(), 10_000, None);
+            let farm = client
+                .instantiate("farming", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("farming instantiate failed")
+                .account_id;
+            let fund = vec![
+                Value::unnamed_variant("Id", [Value::from_bytes(&farm)]),
+                Value::u128(1_000_000_000),
+            ];
+            client
+                .runtime_call(&ink_e2e::alice(), "Balances", "transfer", fund)
+                .await
+                .expect("funding the farm failed");
+
+            let set_pool =
+                build_message::<FarmingRef>(farm.clone()).call(|farming| farming.set_zap_pool(Some(pool.clone())));
+            client.call(&ink_e2e::alice(), set_pool, 0, None).await.expect("set_zap_pool failed");
+            let stake = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.stake(500));
+            client.call(&ink_e2e::alice(), stake, 0, None).await.expect("stake failed");
+
+            let zap = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.claim_and_zap(pool.clone(), 1));
+            let restaked = client
+                .call(&ink_e2e::alice(), zap, 0, None)
+                .await
+                .expect("claim_and_zap failed")
+                .return_value();
+            // At least one block of rewards, bought at close to two tokens each
+            assert!(restaked >= 190);
+
+            let staked =
+                build_message::<FarmingRef>(farm.clone()).call(|farming| farming.get_staked_amount(alice.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &staked, 0, None).await.return_value(), 500 + restaked);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn zap_restakes_bought_stake_tokens(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            // The token sells itself one for one, so it doubles as the zap pool
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let stock = build_message::<MockPsp22Ref>(token.clone())
+                .call(|t| t.transfer(token.clone(), 5_000_000, Vec::new()));
+            client.call(&ink_e2e::alice(), stock, 0, None).await.expect("transfer failed");
+
+            let constructor = FarmingRef::new(100, 0, 0, alice.clone(), 10_000, Some(token.clone()));
+            let farm = client
+                .instantiate("farming", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("farming instantiate failed")
+                .account_id;
+            let fund = vec![
+                Value::unnamed_variant("Id", [Value::from_bytes(&farm)]),
+                Value::u128(1_000_000_000),
+            ];
+            client
+                .runtime_call(&ink_e2e::alice(), "Balances", "transfer", fund)
+                .await
+                .expect("funding the farm failed");
+
+            let set_pool =
+                build_message::<FarmingRef>(farm.clone()).call(|farming| farming.set_zap_pool(Some(token.clone())));
+            client.call(&ink_e2e::alice(), set_pool, 0, None).await.expect("set_zap_pool failed");
+            let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(farm.clone(), 500));
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+            let stake = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.stake(500));
+            client.call(&ink_e2e::alice(), stake, 0, None).await.expect("stake failed");
+
+            let zap = build_message::<FarmingRef>(farm.clone()).call(|farming| farming.claim_and_zap(token.clone(), 1));
+            let restaked = client
+                .call(&ink_e2e::alice(), zap, 0, None)
+                .await
+                .expect("claim_and_zap failed")
+                .return_value();
+            // At least one block of rewards
+            assert!(restaked >= 100);
+
+            // Every restaked unit is backed by a stake token held by the farm
+            let staked =
+                build_message::<FarmingRef>(farm.clone()).call(|farming| farming.get_staked_amount(alice.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &staked, 0, None).await.return_value(), 500 + restaked);
+            let balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(farm.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value(), 500 + restaked);
+
+            Ok(())
+        }
+    }
+}
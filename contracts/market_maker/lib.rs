@@ -0,0 +1,434 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::market_maker::MarketMakerRef;
+
+#[ink::contract]
+mod market_maker {
+    use ink::storage::Mapping;
+
+    /// Swap fee in basis points, left in the pool for liquidity providers
+    const FEE_BPS: Balance = 30;
+
+    #[ink(storage)]
+    pub struct MarketMaker {
+        owner: AccountId,
+        eth_reserve: Balance,
+        token_reserve: Balance,
+        token_balances: Mapping<AccountId, Balance>,
+        // Pool shares; redeemable for a pro-rata part of both reserves
+        lp_balances: Mapping<AccountId, Balance>,
+        lp_total_supply: Balance,
+        // Blocks swaps and deposits while set; withdrawals stay open
+        paused: bool,
+    }
+
+    #[ink(event)]
+    pub struct LiquidityAdded {
+        #[ink(topic)]
+        provider: AccountId,
+        eth_amount: Balance,
+        token_amount: Balance,
+        lp_minted: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LiquidityRemoved {
+        #[ink(topic)]
+        provider: AccountId,
+        eth_amount: Balance,
+        token_amount: Balance,
+        lp_burned: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TokensSwapped {
+        #[ink(topic)]
+        swapper: AccountId,
+        eth_in: Balance,
+        token_out: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ETHSwapped {
+        #[ink(topic)]
+        swapper: AccountId,
+        token_in: Balance,
+        eth_out: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    impl MarketMaker {
+        /// Credits the deployer with `initial_tokens` so they can seed the pool.
+        #[ink(constructor)]
+        pub fn new(initial_tokens: Balance) -> Self {
+            let owner = Self::env().caller();
+            let mut token_balances = Mapping::new();
+            token_balances.insert(owner, &initial_tokens);
+            Self {
+                owner,
+                eth_reserve: 0,
+                token_reserve: 0,
+                token_balances,
+                lp_balances: Mapping::new(),
+                lp_total_supply: 0,
+                paused: false,
+            }
+        }
+
+        /// Adds liquidity and mints LP tokens for the caller's share of the pool. The
+        /// first provider receives LP tokens equal to the ETH deposited; later providers
+        /// receive them in proportion to the smaller of their two contributions.
+        /// The tokens are debited from the caller's token balance. Reverts if fewer
+        /// than `min_lp_out` LP tokens would be minted.
+        #[ink(message, payable)]
+        pub fn add_liquidity(&mut self, token_amount: Balance, min_lp_out: Balance) -> Balance {
+            let caller = self.env().caller();
+            let eth_amount = self.env().transferred_value();
+
+            assert!(!self.paused, "Market maker is paused");
+            assert!(token_amount > 0 && eth_amount > 0, "Invalid amounts");
+            let token_balance = self.token_balances.get(caller).unwrap_or(0);
+            assert!(token_balance >= token_amount, "Insufficient token balance");
+
+            let lp_minted = if self.lp_total_supply == 0 {
+                eth_amount
+            } else {
+                let by_eth = eth_amount * self.lp_total_supply / self.eth_reserve;
+                let by_token = token_amount * self.lp_total_supply / self.token_reserve;
+                by_eth.min(by_token)
+            };
+            assert!(lp_minted > 0, "Liquidity too small");
+            assert!(lp_minted >= min_lp_out, "Slippage: too few LP tokens minted");
+
+            self.token_balances
+                .insert(caller, &(token_balance - token_amount));
+            self.eth_reserve += eth_amount;
+            self.token_reserve += token_amount;
+
+            let lp_balance = self.lp_balances.get(caller).unwrap_or(0);
+            self.lp_balances.insert(caller, &(lp_balance + lp_minted));
+            self.lp_total_supply += lp_minted;
+
+            self.env().emit_event(LiquidityAdded {
+                provider: caller,
+                eth_amount,
+                token_amount,
+                lp_minted,
+            });
+
+            lp_minted
+        }
+
+        #[ink(message, payable)]
+        pub fn swap_eth_for_tokens(&mut self) -> Balance {
+            let caller = self.env().caller();
+            let eth_in = self.env().transferred_value();
+            assert!(!self.paused, "Market maker is paused");
+            assert!(eth_in > 0, "Invalid ETH amount");
+
+            let token_out = self.get_token_price(eth_in);
+            assert!(token_out <= self.token_reserve, "Not enough liquidity");
+
+            self.eth_reserve += eth_in;
+            self.token_reserve -= token_out;
+
+            let caller_balance = self.token_balances.get(caller).unwrap_or(0);
+            self.token_balances
+                .insert(caller, &(caller_balance + token_out));
+
+            self.env().emit_event(TokensSwapped {
+                swapper: caller,
+                eth_in,
+                token_out,
+            });
+
+            token_out
+        }
+
+        #[ink(message)]
+        pub fn swap_tokens_for_eth(&mut self, token_in: Balance) -> Balance {
+            let caller = self.env().caller();
+            assert!(!self.paused, "Market maker is paused");
+            assert!(token_in > 0, "Invalid token amount");
+            let caller_balance = self.token_balances.get(caller).unwrap_or(0);
+            assert!(caller_balance >= token_in, "Insufficient token balance");
+
+            let eth_out = self.get_eth_price(token_in);
+            assert!(eth_out <= self.eth_reserve, "Not enough liquidity");
+
+            self.eth_reserve -= eth_out;
+            self.token_reserve += token_in;
+
+            self.token_balances
+                .insert(caller, &(caller_balance - token_in));
+
+            self.env()
+                .transfer(caller, eth_out)
+                .unwrap_or_else(|_| panic!("Transfer failed"));
+
+            self.env().emit_event(ETHSwapped {
+                swapper: caller,
+                token_in,
+                eth_out,
+            });
+
+            eth_out
+        }
+
+        // Constant-product output for `amount_in` after the swap fee; the whole input
+        // stays in the pool, so the fee accrues to liquidity providers
+        fn get_amount_out(
+            amount_in: Balance,
+            reserve_in: Balance,
+            reserve_out: Balance,
+        ) -> Balance {
+            let amount_in_with_fee = amount_in * (10_000 - FEE_BPS) / 10_000;
+            amount_in_with_fee * reserve_out / (reserve_in + amount_in_with_fee)
+        }
+
+        fn get_eth_price(&self, token_amount: Balance) -> Balance {
+            Self::get_amount_out(token_amount, self.token_reserve, self.eth_reserve)
+        }
+
+        fn get_token_price(&self, eth_amount: Balance) -> Balance {
+            Self::get_amount_out(eth_amount, self.eth_reserve, self.token_reserve)
+        }
+
+        /// Burns `lp_amount` LP tokens and pays out the matching share of both
+        /// reserves, including anything the pool accrued since the deposit. ETH is
+        /// transferred, tokens are credited to the caller's token balance. Reverts if
+        /// either amount falls below its minimum. Stays available while paused.
+        #[ink(message)]
+        pub fn remove_liquidity(
+            &mut self,
+            lp_amount: Balance,
+            min_eth_out: Balance,
+            min_token_out: Balance,
+        ) -> (Balance, Balance) {
+            let caller = self.env().caller();
+            let lp_balance = self.lp_balances.get(caller).unwrap_or(0);
+            assert!(lp_amount > 0 && lp_amount <= lp_balance, "Insufficient LP balance");
+
+            let eth_amount = lp_amount * self.eth_reserve / self.lp_total_supply;
+            let token_amount = lp_amount * self.token_reserve / self.lp_total_supply;
+            assert!(eth_amount >= min_eth_out, "Slippage: too little ETH out");
+            assert!(token_amount >= min_token_out, "Slippage: too few tokens out");
+
+            self.lp_balances.insert(caller, &(lp_balance - lp_amount));
+            self.lp_total_supply -= lp_amount;
+            self.eth_reserve -= eth_amount;
+            self.token_reserve -= token_amount;
+
+            let caller_balance = self.token_balances.get(caller).unwrap_or(0);
+            self.token_balances
+                .insert(caller, &(caller_balance + token_amount));
+
+            self.env()
+                .transfer(caller, eth_amount)
+                .unwrap_or_else(|_| panic!("Transfer failed"));
+
+            self.env().emit_event(LiquidityRemoved {
+                provider: caller,
+                eth_amount,
+                token_amount,
+                lp_burned: lp_amount,
+            });
+
+            (eth_amount, token_amount)
+        }
+
+        /// Stops swaps and deposits during an incident. Owner-only.
+        #[ink(message)]
+        pub fn pause(&mut self) {
+            let caller = self.env().caller();
+            assert!(caller == self.owner, "Only owner can pause");
+            assert!(!self.paused, "Already paused");
+            self.paused = true;
+            self.env().emit_event(Paused { by: caller });
+        }
+
+        #[ink(message)]
+        pub fn unpause(&mut self) {
+            let caller = self.env().caller();
+            assert!(caller == self.owner, "Only owner can unpause");
+            assert!(self.paused, "Not paused");
+            self.paused = false;
+            self.env().emit_event(Unpaused { by: caller });
+        }
+
+        #[ink(message)]
+        pub fn is_paused(&self) -> bool {
+            self.paused
+        }
+
+        #[ink(message)]
+        pub fn token_balance_of(&self, account: AccountId) -> Balance {
+            self.token_balances.get(account).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn lp_balance_of(&self, provider: AccountId) -> Balance {
+            self.lp_balances.get(provider).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn lp_total_supply(&self) -> Balance {
+            self.lp_total_supply
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn set_caller_and_value(caller: AccountId, value: Balance) {
+            test::set_caller::<DefaultEnvironment>(caller);
+            test::set_value_transferred::<DefaultEnvironment>(value);
+        }
+
+        /// Pool seeded by Alice with 1_000 ETH and 2_000 of her 10_000 tokens
+        fn setup() -> MarketMaker {
+            test::set_account_balance::<DefaultEnvironment>(test::callee::<DefaultEnvironment>(), 10_000_000);
+            set_caller_and_value(accounts().alice, 1_000);
+            let mut market_maker = MarketMaker::new(10_000);
+            market_maker.add_liquidity(2_000, 0);
+            market_maker
+        }
+
+        #[ink::test]
+        fn first_provider_gets_lp_tokens_for_the_eth_deposited() {
+            let market_maker = setup();
+            assert_eq!(market_maker.lp_balance_of(accounts().alice), 1_000);
+            assert_eq!(market_maker.lp_total_supply(), 1_000);
+        }
+
+        #[ink::test]
+        fn later_deposits_get_the_smaller_share() {
+            let mut market_maker = setup();
+            set_caller_and_value(accounts().alice, 500);
+            assert_eq!(market_maker.add_liquidity(400, 0), 200);
+            assert_eq!(market_maker.lp_balance_of(accounts().alice), 1_200);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Slippage: too few LP tokens minted")]
+        fn add_liquidity_respects_the_minimum() {
+            let mut market_maker = setup();
+            set_caller_and_value(accounts().alice, 500);
+            market_maker.add_liquidity(400, 201);
+        }
+
+        #[ink::test]
+        fn add_liquidity_debits_the_providers_tokens() {
+            let market_maker = setup();
+            assert_eq!(market_maker.token_balance_of(accounts().alice), 8_000);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Insufficient token balance")]
+        fn add_liquidity_needs_the_tokens() {
+            let mut market_maker = setup();
+            set_caller_and_value(accounts().bob, 500);
+            market_maker.add_liquidity(400, 0);
+        }
+
+        #[ink::test]
+        fn remove_liquidity_pays_out_the_pro_rata_share() {
+            let mut market_maker = setup();
+            set_caller_and_value(accounts().alice, 0);
+            assert_eq!(market_maker.remove_liquidity(250, 250, 500), (250, 500));
+            assert_eq!(market_maker.lp_balance_of(accounts().alice), 750);
+            assert_eq!(market_maker.lp_total_supply(), 750);
+            assert_eq!(market_maker.token_balance_of(accounts().alice), 8_500);
+        }
+
+        #[ink::test]
+        fn providers_redeem_their_share_plus_fees() {
+            test::set_account_balance::<DefaultEnvironment>(test::callee::<DefaultEnvironment>(), 10_000_000);
+            set_caller_and_value(accounts().alice, 1_000_000);
+            let mut market_maker = MarketMaker::new(2_000_000);
+            assert_eq!(market_maker.add_liquidity(2_000_000, 0), 1_000_000);
+
+            // Bob buys tokens, then joins at the moved price
+            set_caller_and_value(accounts().bob, 100_000);
+            assert_eq!(market_maker.swap_eth_for_tokens(), 181_322);
+            set_caller_and_value(accounts().bob, 100_000);
+            assert_eq!(market_maker.add_liquidity(165_335, 0), 90_909);
+
+            // Charlie round-trips, leaving the fees in the pool
+            set_caller_and_value(accounts().charlie, 200_000);
+            assert_eq!(market_maker.swap_eth_for_tokens(), 282_701);
+            set_caller_and_value(accounts().charlie, 0);
+            assert_eq!(market_maker.swap_tokens_for_eth(282_701), 198_971);
+
+            // Bob put in 100_000 ETH and 165_335 tokens; 85 ETH is his cut of the fees
+            set_caller_and_value(accounts().bob, 0);
+            assert_eq!(market_maker.remove_liquidity(90_909, 0, 0), (100_085, 165_334));
+            set_caller_and_value(accounts().alice, 0);
+            assert_eq!(market_maker.remove_liquidity(1_000_000, 0, 0), (1_100_944, 1_818_679));
+            assert_eq!(market_maker.lp_total_supply(), 0);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Slippage: too little ETH out")]
+        fn remove_liquidity_respects_the_eth_minimum() {
+            let mut market_maker = setup();
+            set_caller_and_value(accounts().alice, 0);
+            market_maker.remove_liquidity(250, 251, 0);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Slippage: too few tokens out")]
+        fn remove_liquidity_respects_the_token_minimum() {
+            let mut market_maker = setup();
+            set_caller_and_value(accounts().alice, 0);
+            market_maker.remove_liquidity(250, 0, 501);
+        }
+
+        #[ink::test]
+        fn removals_still_work_while_paused() {
+            let mut market_maker = setup();
+            market_maker.pause();
+            set_caller_and_value(accounts().alice, 0);
+            assert_eq!(market_maker.remove_liquidity(1_000, 1_000, 2_000), (1_000, 2_000));
+            assert_eq!(market_maker.lp_total_supply(), 0);
+            assert_eq!(market_maker.token_balance_of(accounts().alice), 10_000);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Market maker is paused")]
+        fn deposits_stop_while_paused() {
+            let mut market_maker = setup();
+            market_maker.pause();
+            set_caller_and_value(accounts().alice, 500);
+            market_maker.add_liquidity(400, 0);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Market maker is paused")]
+        fn swaps_stop_while_paused() {
+            let mut market_maker = setup();
+            market_maker.pause();
+            assert!(market_maker.is_paused());
+            set_caller_and_value(accounts().bob, 100);
+            market_maker.swap_eth_for_tokens();
+        }
+    }
+}
@@ -0,0 +1,124 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::mock_psp22::MockPsp22Ref;
+
+/// Minimal PSP22 token used by the end-to-end tests of the token-handling
+/// contracts. The whole supply is minted to the deployer.
+#[ink::contract]
+mod mock_psp22 {
+    use ink::prelude::vec::Vec;
+    use ink::storage::Mapping;
+    use psp22_traits::{PSP22Error, PSP22};
+
+    #[ink(storage)]
+    pub struct MockPsp22 {
+        total_supply: Balance,
+        balances: Mapping<AccountId, Balance>,
+        allowances: Mapping<(AccountId, AccountId), Balance>,
+    }
+
+    impl MockPsp22 {
+        #[ink(constructor)]
+        pub fn new(total_supply: Balance) -> Self {
+            let mut balances = Mapping::new();
+            balances.insert(&Self::env().caller(), &total_supply);
+            Self {
+                total_supply,
+                balances,
+                allowances: Mapping::new(),
+            }
+        }
+
+        /// Sells tokens held by the mock itself for the transferred value, one for one,
+        /// and returns the amount sold, so tests can use the token as a pool paying out
+        /// real tokens
+        #[ink(message, payable)]
+        pub fn swap_eth_for_tokens(&mut self) -> Balance {
+            let value = self.env().transferred_value();
+            self.move_tokens(self.env().account_id(), self.env().caller(), value)
+                .expect("Not enough tokens to sell");
+            value
+        }
+
+        fn move_tokens(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<(), PSP22Error> {
+            let from_balance = self.balances.get(&from).unwrap_or(0);
+            if from_balance < value {
+                return Err(PSP22Error::InsufficientBalance);
+            }
+            self.balances.insert(&from, &(from_balance - value));
+            let to_balance = self.balances.get(&to).unwrap_or(0);
+            self.balances.insert(&to, &(to_balance + value));
+            Ok(())
+        }
+    }
+
+    impl PSP22 for MockPsp22 {
+        #[ink(message)]
+        fn total_supply(&self) -> Balance {
+            self.total_supply
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(&owner).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get(&(owner, spender)).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, value: Balance, _data: Vec<u8>) -> Result<(), PSP22Error> {
+            self.move_tokens(self.env().caller(), to, value)
+        }
+
+        #[ink(message)]
+        fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance, _data: Vec<u8>) -> Result<(), PSP22Error> {
+            let caller = self.env().caller();
+            let allowance = self.allowances.get(&(from, caller)).unwrap_or(0);
+            if allowance < value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+            self.move_tokens(from, to, value)?;
+            self.allowances.insert(&(from, caller), &(allowance - value));
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn approve(&mut self, spender: AccountId, value: Balance) -> Result<(), PSP22Error> {
+            self.allowances.insert(&(self.env().caller(), spender), &value);
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        #[ink::test]
+        fn transfer_from_spends_the_allowance() {
+            let accounts = accounts();
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut token = MockPsp22::new(1_000);
+            token.approve(accounts.bob, 300).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(token.transfer_from(accounts.alice, accounts.charlie, 200, Vec::new()), Ok(()));
+            assert_eq!(
+                token.transfer_from(accounts.alice, accounts.charlie, 200, Vec::new()),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+            assert_eq!(token.balance_of(accounts.alice), 800);
+            assert_eq!(token.balance_of(accounts.charlie), 200);
+            assert_eq!(token.allowance(accounts.alice, accounts.bob), 100);
+            assert_eq!(token.total_supply(), 1_000);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::mock_psp34::{Id, MockPsp34Ref, PSP34Error, PSP34};
+
+/// Minimal PSP34 collection used by the end-to-end tests of the NFT-handling
+/// contracts. Only the deployer can mint.
+#[ink::contract]
+mod mock_psp34 {
+    use ink::prelude::{string::String, vec::Vec};
+    use ink::storage::Mapping;
+
+    /// Token id of a PSP34 collection
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum Id {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        U128(u128),
+        Bytes(Vec<u8>),
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP34Error {
+        Custom(String),
+        SelfApprove,
+        NotApproved,
+        TokenExists,
+        TokenNotExists,
+        SafeTransferCheckFailed(String),
+    }
+
+    /// The part of PSP34 the farm relies on
+    #[ink::trait_definition]
+    pub trait PSP34 {
+        #[ink(message)]
+        fn owner_of(&self, id: Id) -> Option<AccountId>;
+
+        /// Approves `operator` for `id`; collection-wide approvals are not supported
+        #[ink(message)]
+        fn approve(&mut self, operator: AccountId, id: Option<Id>, approved: bool) -> Result<(), PSP34Error>;
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, id: Id, data: Vec<u8>) -> Result<(), PSP34Error>;
+    }
+
+    #[ink(storage)]
+    pub struct MockPsp34 {
+        minter: AccountId,
+        owners: Mapping<Id, AccountId>,
+        approvals: Mapping<Id, AccountId>,
+    }
+
+    impl MockPsp34 {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                minter: Self::env().caller(),
+                owners: Mapping::new(),
+                approvals: Mapping::new(),
+            }
+        }
+
+        #[ink(message)]
+        pub fn mint(&mut self, to: AccountId, id: Id) -> Result<(), PSP34Error> {
+            if self.env().caller() != self.minter {
+                return Err(PSP34Error::Custom(String::from("Only the deployer can mint")));
+            }
+            if self.owners.contains(&id) {
+                return Err(PSP34Error::TokenExists);
+            }
+            self.owners.insert(&id, &to);
+            Ok(())
+        }
+    }
+
+    impl PSP34 for MockPsp34 {
+        #[ink(message)]
+        fn owner_of(&self, id: Id) -> Option<AccountId> {
+            self.owners.get(&id)
+        }
+
+        #[ink(message)]
+        fn approve(&mut self, operator: AccountId, id: Option<Id>, approved: bool) -> Result<(), PSP34Error> {
+            let id = id.ok_or(PSP34Error::Custom(String::from("Collection approvals are not supported")))?;
+            if self.owners.get(&id) != Some(self.env().caller()) {
+                return Err(PSP34Error::NotApproved);
+            }
+            if approved {
+                self.approvals.insert(&id, &operator);
+            } else {
+                self.approvals.remove(&id);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, id: Id, _data: Vec<u8>) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let owner = self.owners.get(&id).ok_or(PSP34Error::TokenNotExists)?;
+            if caller != owner && self.approvals.get(&id) != Some(caller) {
+                return Err(PSP34Error::NotApproved);
+            }
+            self.owners.insert(&id, &to);
+            self.approvals.remove(&id);
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        #[ink::test]
+        fn transfer_needs_the_owner_or_an_approval() {
+            let accounts = accounts();
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            let mut collection = MockPsp34::new();
+            collection.mint(accounts.alice, Id::U8(1)).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                collection.transfer(accounts.bob, Id::U8(1), Vec::new()),
+                Err(PSP34Error::NotApproved)
+            );
+
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            collection.approve(accounts.bob, Some(Id::U8(1)), true).unwrap();
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(collection.transfer(accounts.bob, Id::U8(1), Vec::new()), Ok(()));
+            assert_eq!(collection.owner_of(Id::U8(1)), Some(accounts.bob));
+        }
+    }
+}
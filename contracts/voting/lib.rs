@@ -0,0 +1,3737 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::prelude::string::String;
+use ink::prelude::vec;
+use ink::prelude::vec::Vec;
+use ink::storage::Mapping;
+
+#[ink::contract]
+mod voting {
+    use super::*;
+    use ink::codegen::TraitCallBuilder;
+    use ink::contract_ref;
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::hash::{Blake2x256, HashOutput};
+    use ink::env::DefaultEnvironment;
+    use psp22_traits::PSP22;
+    use shared::errors::{flatten_call, flatten_query};
+
+    /// Maximum number of accounts that may delegate to a single delegate
+    pub const MAX_DELEGATORS: u32 = 32;
+
+    /// Maximum number of entries returned by a single `get_results` call
+    pub const MAX_RESULTS_PAGE: u32 = 50;
+
+    /// Minimum and maximum number of candidates per proposal
+    pub const MIN_CANDIDATES: u32 = 2;
+    pub const MAX_CANDIDATES: u32 = 256;
+
+    /// Maximum number of candidates a ranked ballot may list
+    pub const MAX_RANKINGS: u32 = 16;
+
+    /// Maximum number of distinct rankings stored per ranked proposal; identical
+    /// ballots share one entry
+    pub const MAX_DISTINCT_RANKINGS: u32 = 256;
+
+    /// Maximum number of tally checkpoints kept per candidate; once reached, later
+    /// changes are folded into the last checkpoint
+    pub const MAX_TIMELINE_CHECKPOINTS: u32 = 128;
+
+    /// Maximum number of running proposals a single voting stake may back at once
+    pub const MAX_STAKE_LOCKS: u32 = 16;
+
+    /// Maximum number of accounts accepted by a single `register_voters` call
+    pub const MAX_REGISTRATION_BATCH: u32 = 100;
+
+    /// Denominator for values expressed in basis points
+    pub const BPS_DENOMINATOR: u128 = 10_000;
+
+    pub type ProposalId = u32;
+
+    /// Position of a candidate in its proposal, from 0 to `candidate_count - 1`
+    pub type CandidateId = u32;
+
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum VotingState {
+        Active,
+        Ended,
+        /// Ended without reaching quorum or the minimum winning margin
+        Failed,
+        /// Suspended by the admin; no votes are accepted until it is resumed
+        Paused,
+        /// Called off by the admin; terminal
+        Cancelled,
+    }
+
+    /// How ballots are cast on a proposal
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum VotingMode {
+        /// Votes are cast and tallied in the open with `vote`
+        Public,
+        /// Voters `commit_vote` a hash until `commit_ends_at`, then `reveal_vote` until
+        /// the proposal ends; unrevealed commitments are not counted
+        CommitReveal { commit_ends_at: Timestamp },
+        /// Voters rank candidates with `vote_ranked`; the winner is decided by
+        /// instant-runoff elimination. Single-seat only.
+        Ranked,
+        /// Voters lock `n^2` voting tokens with `vote_quadratic` to cast `n` votes for
+        /// one candidate, up to `max_votes` each. Requires a voting token.
+        Quadratic { max_votes: u128 },
+    }
+
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum VotingError {
+        AlreadyVoted,
+        CandidateNotFound,
+        VotingEnded,
+        VotingStillActive,
+        AlreadyFinalized,
+        ZeroVotingPower,
+        TokenCallFailed,
+        SelfDelegation,
+        AlreadyDelegated,
+        NotDelegated,
+        ChainedDelegation,
+        DelegateAlreadyVoted,
+        TooManyDelegators,
+        VoteDelegated,
+        NotAdmin,
+        VotingStarted,
+        DuplicateCandidate,
+        InvalidCandidate,
+        InvalidProposal,
+        NotVoted,
+        InvalidSeats,
+        QuorumNotReached,
+        MarginNotReached,
+        WrongVotingMode,
+        CommitPhaseEnded,
+        RevealPhaseNotStarted,
+        NoCommitment,
+        InvalidReveal,
+        NotEligible,
+        BatchTooLarge,
+        EmptyBatch,
+        VotingNotStarted,
+        InvalidSchedule,
+        InvalidRanking,
+        TooManyRankings,
+        NothingLocked,
+        TooFewCandidates,
+        TooManyCandidates,
+        NoAction,
+        NotPassed,
+        AlreadyExecuted,
+        TimelockNotElapsed,
+        TooManyVotes,
+        InvalidSignature,
+        InvalidNonce,
+        NotStaked,
+        StakeInUse,
+        TooManyStakeLocks,
+        Paused,
+        NotPaused,
+        ProposalCancelled,
+    }
+
+    impl VotingError {
+        /// Human-readable description for frontends
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                VotingError::AlreadyVoted => "Caller has already voted on this proposal",
+                VotingError::CandidateNotFound => "Candidate does not exist",
+                VotingError::VotingEnded => "Voting has ended",
+                VotingError::VotingStillActive => "Voting is still active",
+                VotingError::AlreadyFinalized => "Proposal is already finalized",
+                VotingError::ZeroVotingPower => "Caller has no voting power",
+                VotingError::TokenCallFailed => "Voting token call failed",
+                VotingError::SelfDelegation => "Cannot delegate to yourself",
+                VotingError::AlreadyDelegated => "Caller has already delegated",
+                VotingError::NotDelegated => "Caller has not delegated",
+                VotingError::ChainedDelegation => "Delegation chains are not allowed",
+                VotingError::DelegateAlreadyVoted => "Delegate has already voted",
+                VotingError::TooManyDelegators => "Delegate has too many delegators",
+                VotingError::VoteDelegated => "Caller's vote is delegated",
+                VotingError::NotAdmin => "Only the admin can do this",
+                VotingError::VotingStarted => "Voting has already started",
+                VotingError::DuplicateCandidate => "Candidate already exists",
+                VotingError::InvalidCandidate => "Invalid candidate name",
+                VotingError::InvalidProposal => "Proposal does not exist",
+                VotingError::NotVoted => "Caller has not voted",
+                VotingError::InvalidSeats => "Invalid number of seats",
+                VotingError::QuorumNotReached => "Quorum not reached",
+                VotingError::MarginNotReached => "Winning margin not reached",
+                VotingError::WrongVotingMode => "Not available in this voting mode",
+                VotingError::CommitPhaseEnded => "Commit phase has ended",
+                VotingError::RevealPhaseNotStarted => "Reveal phase has not started",
+                VotingError::NoCommitment => "No commitment found",
+                VotingError::InvalidReveal => "Reveal does not match the commitment",
+                VotingError::NotEligible => "Account is not eligible to vote",
+                VotingError::BatchTooLarge => "Too many accounts in one batch",
+                VotingError::EmptyBatch => "No accounts in the batch",
+                VotingError::VotingNotStarted => "Voting has not started yet",
+                VotingError::InvalidSchedule => "Invalid voting schedule",
+                VotingError::InvalidRanking => "Ranking is empty, too long, out of bounds or has duplicates",
+                VotingError::TooManyRankings => "Too many distinct rankings on this proposal",
+                VotingError::NothingLocked => "No tokens locked for this proposal",
+                VotingError::TooFewCandidates => "Too few candidates",
+                VotingError::TooManyCandidates => "Too many candidates",
+                VotingError::NoAction => "Proposal has no action",
+                VotingError::NotPassed => "Proposal did not pass",
+                VotingError::AlreadyExecuted => "Proposal action was already executed",
+                VotingError::TimelockNotElapsed => "Execution timelock has not elapsed",
+                VotingError::TooManyVotes => "Vote count exceeds the per-voter maximum",
+                VotingError::InvalidSignature => "Signature does not match the voter",
+                VotingError::InvalidNonce => "Nonce does not match the voter's next nonce",
+                VotingError::NotStaked => "Caller has no voting stake",
+                VotingError::StakeInUse => "Stake backs a vote on an active proposal",
+                VotingError::TooManyStakeLocks => "Stake backs too many active proposals",
+                VotingError::Paused => "Voting is paused",
+                VotingError::NotPaused => "Voting is not paused",
+                VotingError::ProposalCancelled => "Proposal was cancelled",
+            }
+        }
+    }
+
+    impl core::fmt::Display for VotingError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum WinnerResult {
+        Winner(String, u128),
+        Tie(Vec<String>, u128),
+        NoVotes,
+    }
+
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Proposal {
+        pub description: String,
+        // Names are stored per id in `candidate_names`
+        pub candidate_count: u32,
+        // Number of winners the proposal elects
+        pub seats: u32,
+        pub mode: VotingMode,
+        pub starts_at: Timestamp,
+        pub ends_at: Timestamp,
+        pub total_votes: u128,
+        // Number of accounts with a ballot for a candidate
+        pub voter_count: u32,
+        // Weight and number of abstaining ballots; not part of `total_votes`
+        pub abstain_votes: u128,
+        pub abstain_count: u32,
+        // Set by the first vote; the candidate list and whitelist are frozen from then on
+        pub voting_started: bool,
+        // Only registered accounts may vote once the first account is registered
+        pub whitelist: bool,
+        pub eligible_count: u32,
+        pub finalized: bool,
+        // Start of the current pause, if paused
+        pub paused_at: Option<Timestamp>,
+        pub cancelled: bool,
+    }
+
+    /// Cross-contract call executed once the proposal is finalized with
+    /// `candidate_index` as its winner
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct ProposalAction {
+        pub candidate_index: u32,
+        pub callee: AccountId,
+        pub selector: [u8; 4],
+        // SCALE-encoded arguments appended to the selector
+        pub input: Vec<u8>,
+        pub transferred_value: Balance,
+    }
+
+    /// Passes already encoded call arguments through unchanged
+    struct CallInput<'a>(&'a [u8]);
+
+    impl<'a> scale::Encode for CallInput<'a> {
+        fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+            dest.write(self.0);
+        }
+    }
+
+    /// What an account voted for and with how much weight
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Ballot {
+        /// `None` for an abstention
+        pub candidate: Option<CandidateId>,
+        pub weight: u128,
+    }
+
+    /// Tokens staked with `stake_for_voting`
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct VotingStake {
+        pub amount: Balance,
+        /// Block the stake's age is counted from; top-ups move it forward pro rata
+        pub since: BlockNumber,
+        /// Proposals voted on with this stake; unstaking waits until none is running
+        pub locked_by: Vec<ProposalId>,
+    }
+
+    /// Proof of how an account's ballot is currently counted, kept in step with the tallies
+    #[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Receipt {
+        /// `None` for an abstention
+        pub candidate_index: Option<CandidateId>,
+        pub weight: u128,
+        /// Block in which the ballot was last cast or changed
+        pub block: BlockNumber,
+    }
+
+    /// Candidates are referenced by their id in `get_candidates`
+    #[ink(event)]
+    pub struct ProposalCreated {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        candidate_count: u32,
+        seats: u32,
+        starts_at: Timestamp,
+        ends_at: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct VoteCast {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        voter: AccountId,
+        candidate_index: u32,
+        weight: u128,
+    }
+
+    #[ink(event)]
+    pub struct VoteChanged {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        voter: AccountId,
+        // `None` stands for an abstention
+        old_candidate_index: Option<u32>,
+        new_candidate_index: Option<u32>,
+        weight: u128,
+    }
+
+    #[ink(event)]
+    pub struct VoteToppedUp {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        voter: AccountId,
+        candidate_index: Option<u32>,
+        added_weight: u128,
+        weight: u128,
+    }
+
+    #[ink(event)]
+    pub struct VoteRevoked {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        voter: AccountId,
+        candidate_index: Option<u32>,
+        weight: u128,
+    }
+
+    #[ink(event)]
+    pub struct Abstained {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        voter: AccountId,
+        weight: u128,
+    }
+
+    #[ink(event)]
+    pub struct VotingEnded {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        winner_index: Option<u32>,
+        total_votes: u128,
+    }
+
+    #[ink(event)]
+    pub struct VotingPaused {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+    }
+
+    /// `ends_at` is the proposal's deadline after any extension for the pause
+    #[ink(event)]
+    pub struct VotingResumed {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        ends_at: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct ProposalCancelled {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+    }
+
+    #[ink(event)]
+    pub struct ProposalExecuted {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        success: bool,
+    }
+
+    #[ink(event)]
+    pub struct CandidateAdded {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        index: u32,
+    }
+
+    /// `index` is the candidate's position before removal; later candidates shift down by one
+    #[ink(event)]
+    pub struct CandidateRemoved {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        index: u32,
+    }
+
+    #[ink(event)]
+    pub struct Delegated {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        delegate: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Undelegated {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        delegate: AccountId,
+    }
+
+    #[ink(storage)]
+    pub struct Voting {
+        admin: AccountId,
+        next_proposal_id: ProposalId,
+        proposals: Mapping<ProposalId, Proposal>,
+        candidate_names: Mapping<(ProposalId, CandidateId), String>,
+        // Tally per candidate: one per voter, or the voter's token balance in weighted mode
+        votes: Mapping<(ProposalId, CandidateId), u128>,
+        // (block, tally) after each block that changed a candidate's tally
+        vote_checkpoints: Mapping<(ProposalId, CandidateId), Vec<(BlockNumber, u128)>>,
+        // Candidate and weight of every vote cast
+        ballots: Mapping<(ProposalId, AccountId), Ballot>,
+        receipts: Mapping<(ProposalId, AccountId), Receipt>,
+        // Pending commitments of commit-reveal proposals
+        commitments: Mapping<(ProposalId, AccountId), Hash>,
+        // PSP22 token whose balance is used as voting weight, if any
+        voting_token: Option<AccountId>,
+        // Voting tokens held by the contract until the proposal ends, per voter
+        locked: Mapping<(ProposalId, AccountId), Balance>,
+        // Delegator -> delegate
+        delegates: Mapping<(ProposalId, AccountId), AccountId>,
+        // Delegate -> accounts whose voting power it carries
+        delegators: Mapping<(ProposalId, AccountId), Vec<AccountId>>,
+        // Registered voters of whitelisted proposals
+        eligible: Mapping<(ProposalId, AccountId), ()>,
+        // Ranked ballots as (candidate indices in preference order, total weight)
+        ranked_ballots: Mapping<ProposalId, Vec<(Vec<u32>, u128)>>,
+        // Instant-runoff tallies per round, stored by `finalize`
+        round_results: Mapping<ProposalId, Vec<Vec<(u32, u128)>>>,
+        // Action attached to a proposal and, once executed, whether the call succeeded
+        actions: Mapping<ProposalId, ProposalAction>,
+        executions: Mapping<ProposalId, bool>,
+        // Time that must pass after a proposal ends before its action can run
+        execution_delay: Timestamp,
+        // Next nonce each account must sign for `vote_by_signature`
+        signature_nonces: Mapping<AccountId, u64>,
+        // Stake age in blocks at which staked tokens count fully; 0 disables stake weighting
+        holding_cap: BlockNumber,
+        voting_stakes: Mapping<AccountId, VotingStake>,
+        // Minimum total votes (token units in weighted mode) for a proposal to pass
+        quorum: u128,
+        // Minimum lead of the winner over the runner-up, in bps of the total votes
+        min_margin_bps: u16,
+        // Whether abstentions count towards the quorum
+        abstain_counts_for_quorum: bool,
+        // Whether resuming a paused proposal pushes its deadlines back by the pause length
+        extend_on_pause: bool,
+    }
+
+    impl Voting {
+        #[ink(constructor)]
+        pub fn new(
+            voting_token: Option<AccountId>,
+            quorum: u128,
+            min_margin_bps: u16,
+            abstain_counts_for_quorum: bool,
+            extend_on_pause: bool,
+        ) -> Self {
+            Self {
+                admin: Self::env().caller(),
+                next_proposal_id: 0,
+                proposals: Mapping::new(),
+                candidate_names: Mapping::new(),
+                votes: Mapping::new(),
+                vote_checkpoints: Mapping::new(),
+                ballots: Mapping::new(),
+                receipts: Mapping::new(),
+                commitments: Mapping::new(),
+                voting_token,
+                locked: Mapping::new(),
+                delegates: Mapping::new(),
+                delegators: Mapping::new(),
+                eligible: Mapping::new(),
+                ranked_ballots: Mapping::new(),
+                round_results: Mapping::new(),
+                actions: Mapping::new(),
+                executions: Mapping::new(),
+                execution_delay: 0,
+                signature_nonces: Mapping::new(),
+                holding_cap: 0,
+                voting_stakes: Mapping::new(),
+                quorum,
+                min_margin_bps,
+                abstain_counts_for_quorum,
+                extend_on_pause,
+            }
+        }
+
+        /// Opens a new proposal accepting votes from `starts_at` until `ends_at`. Admin-only.
+        ///
+        /// Candidate names are trimmed and must be non-empty and unique ignoring ASCII
+        /// case. Candidates get ids in the given order; messages take the id, which
+        /// `get_candidate_id` resolves from a name matched the same way, so "alice"
+        /// finds candidate "Alice".
+        #[ink(message)]
+        pub fn create_proposal(
+            &mut self,
+            description: String,
+            candidates: Vec<String>,
+            seats: u32,
+            mode: VotingMode,
+            starts_at: Timestamp,
+            ends_at: Timestamp,
+        ) -> Result<ProposalId, VotingError> {
+            if self.env().caller() != self.admin {
+                return Err(VotingError::NotAdmin);
+            }
+            if (candidates.len() as u32) < MIN_CANDIDATES {
+                return Err(VotingError::TooFewCandidates);
+            }
+            if candidates.len() as u32 > MAX_CANDIDATES {
+                return Err(VotingError::TooManyCandidates);
+            }
+            let mut names: Vec<String> = Vec::with_capacity(candidates.len());
+            for candidate in &candidates {
+                let name = candidate.trim();
+                if name.is_empty() {
+                    return Err(VotingError::InvalidCandidate);
+                }
+                if names.iter().any(|existing| existing.eq_ignore_ascii_case(name)) {
+                    return Err(VotingError::DuplicateCandidate);
+                }
+                names.push(String::from(name));
+            }
+            if seats == 0 || seats as usize > names.len() {
+                return Err(VotingError::InvalidSeats);
+            }
+            if mode == VotingMode::Ranked && seats != 1 {
+                return Err(VotingError::InvalidSeats);
+            }
+            if let VotingMode::Quadratic { max_votes } = mode {
+                if max_votes == 0 || self.voting_token.is_none() {
+                    return Err(VotingError::WrongVotingMode);
+                }
+            }
+            if starts_at >= ends_at {
+                return Err(VotingError::InvalidSchedule);
+            }
+            if let VotingMode::CommitReveal { commit_ends_at } = mode {
+                if commit_ends_at <= starts_at || commit_ends_at >= ends_at {
+                    return Err(VotingError::InvalidSchedule);
+                }
+            }
+
+            let proposal_id = self.next_proposal_id;
+            self.next_proposal_id += 1;
+            let candidate_count = names.len() as u32;
+            for (candidate_id, name) in names.iter().enumerate() {
+                self.candidate_names.insert(&(proposal_id, candidate_id as CandidateId), name);
+            }
+            self.proposals.insert(
+                &proposal_id,
+                &Proposal {
+                    description,
+                    candidate_count,
+                    seats,
+                    mode,
+                    starts_at,
+                    ends_at,
+                    total_votes: 0,
+                    voter_count: 0,
+                    abstain_votes: 0,
+                    abstain_count: 0,
+                    voting_started: false,
+                    whitelist: false,
+                    eligible_count: 0,
+                    finalized: false,
+                    paused_at: None,
+                    cancelled: false,
+                },
+            );
+            self.env().emit_event(ProposalCreated {
+                proposal_id,
+                candidate_count,
+                seats,
+                starts_at,
+                ends_at,
+            });
+
+            Ok(proposal_id)
+        }
+
+        #[ink(message)]
+        pub fn vote(&mut self, proposal_id: ProposalId, candidate_id: CandidateId) -> Result<(), VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if proposal.mode != VotingMode::Public {
+                return Err(VotingError::WrongVotingMode);
+            }
+            self.cast_vote(proposal_id, proposal, self.env().caller(), Some(candidate_id))
+        }
+
+        /// Casts `voter`'s vote on their behalf, so a relayer can pay for it. `signature`
+        /// is the voter's 65-byte ECDSA signature over `get_vote_signature_hash`, which
+        /// binds this contract, the proposal, the candidate and the voter's next nonce;
+        /// the nonce is used up by the vote. Otherwise the same rules as `vote` apply.
+        #[ink(message)]
+        pub fn vote_by_signature(
+            &mut self,
+            voter: AccountId,
+            proposal_id: ProposalId,
+            candidate_id: CandidateId,
+            nonce: u64,
+            signature: [u8; 65],
+        ) -> Result<(), VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if proposal.mode != VotingMode::Public {
+                return Err(VotingError::WrongVotingMode);
+            }
+            if nonce != self.signature_nonces.get(&voter).unwrap_or(0) {
+                return Err(VotingError::InvalidNonce);
+            }
+
+            let message = self.vote_signature_hash(proposal_id, candidate_id, nonce);
+            let public_key = self
+                .env()
+                .ecdsa_recover(&signature, &message)
+                .map_err(|_| VotingError::InvalidSignature)?;
+            let mut signer = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Blake2x256>(&public_key, &mut signer);
+            if AccountId::from(signer) != voter {
+                return Err(VotingError::InvalidSignature);
+            }
+
+            self.signature_nonces.insert(&voter, &(nonce + 1));
+            self.cast_vote(proposal_id, proposal, voter, Some(candidate_id))
+        }
+
+        /// Hash a voter signs to have `vote_by_signature` relay their vote
+        #[ink(message)]
+        pub fn get_vote_signature_hash(&self, proposal_id: ProposalId, candidate_id: CandidateId, nonce: u64) -> [u8; 32] {
+            self.vote_signature_hash(proposal_id, candidate_id, nonce)
+        }
+
+        #[ink(message)]
+        pub fn get_signature_nonce(&self, account: AccountId) -> u64 {
+            self.signature_nonces.get(&account).unwrap_or(0)
+        }
+
+        /// Records the caller as having voted without supporting any candidate.
+        /// Available in every mode but `Quadratic`, where votes are bought rather
+        /// than weighed; abstentions count towards turnout and, if so configured,
+        /// towards the quorum.
+        #[ink(message)]
+        pub fn vote_abstain(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if matches!(proposal.mode, VotingMode::Quadratic { .. }) {
+                return Err(VotingError::WrongVotingMode);
+            }
+            self.cast_vote(proposal_id, proposal, self.env().caller(), None)
+        }
+
+        /// Adds `votes` quadratic votes for `candidate_id`. The cost is charged on the
+        /// caller's cumulative total, so going from `old` to `old + votes` votes locks
+        /// `(old + votes)^2 - old^2` more voting tokens via `transfer_from`. Further
+        /// calls must name the same candidate. Tokens are returned by `withdraw_locked`
+        /// after the proposal ends.
+        #[ink(message)]
+        pub fn vote_quadratic(&mut self, proposal_id: ProposalId, candidate_id: CandidateId, votes: u128) -> Result<(), VotingError> {
+            let mut proposal = self.proposal(proposal_id)?;
+            let VotingMode::Quadratic { max_votes } = proposal.mode else {
+                return Err(VotingError::WrongVotingMode);
+            };
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Ended {
+                return Err(VotingError::VotingEnded);
+            }
+            if self.env().block_timestamp() < proposal.starts_at {
+                return Err(VotingError::VotingNotStarted);
+            }
+            if votes == 0 {
+                return Err(VotingError::ZeroVotingPower);
+            }
+
+            let caller = self.env().caller();
+            Self::check_candidate(&proposal, candidate_id)?;
+            if !self.is_eligible(proposal_id, &proposal, caller) {
+                return Err(VotingError::NotEligible);
+            }
+
+            let previous = self.ballots.get(&(proposal_id, caller));
+            let old_votes = match &previous {
+                Some(ballot) if ballot.candidate != Some(candidate_id) => {
+                    return Err(VotingError::AlreadyVoted);
+                }
+                Some(ballot) => ballot.weight,
+                None => 0,
+            };
+            let new_votes = old_votes.checked_add(votes).ok_or(VotingError::TooManyVotes)?;
+            if new_votes > max_votes {
+                return Err(VotingError::TooManyVotes);
+            }
+            let cost = new_votes
+                .checked_mul(new_votes)
+                .ok_or(VotingError::TooManyVotes)?
+                - old_votes * old_votes;
+            self.pull_tokens(proposal_id, caller, cost)?;
+
+            self.add_votes(proposal_id, candidate_id, votes);
+            proposal.total_votes += votes;
+            if previous.is_none() {
+                proposal.voter_count += 1;
+            }
+            proposal.voting_started = true;
+            self.proposals.insert(&proposal_id, &proposal);
+
+            self.env().emit_event(VoteCast {
+                proposal_id,
+                voter: caller,
+                candidate_index: candidate_id,
+                weight: votes,
+            });
+            self.store_ballot(
+                proposal_id,
+                caller,
+                &Ballot {
+                    candidate: Some(candidate_id),
+                    weight: new_votes,
+                },
+            );
+
+            Ok(())
+        }
+
+        /// Casts a ranked ballot: distinct candidate ids, most preferred first.
+        /// The first choice counts towards `get_votes`; the full ranking is used by
+        /// the instant-runoff count.
+        #[ink(message)]
+        pub fn vote_ranked(&mut self, proposal_id: ProposalId, rankings: Vec<u32>) -> Result<(), VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if proposal.mode != VotingMode::Ranked {
+                return Err(VotingError::WrongVotingMode);
+            }
+            if rankings.is_empty() || rankings.len() as u32 > MAX_RANKINGS {
+                return Err(VotingError::InvalidRanking);
+            }
+            for (position, index) in rankings.iter().enumerate() {
+                if *index >= proposal.candidate_count || rankings[..position].contains(index) {
+                    return Err(VotingError::InvalidRanking);
+                }
+            }
+
+            let mut ballots = self.ranked_ballots.get(&proposal_id).unwrap_or_default();
+            let existing = ballots.iter().position(|(ranking, _)| *ranking == rankings);
+            if existing.is_none() && ballots.len() as u32 >= MAX_DISTINCT_RANKINGS {
+                return Err(VotingError::TooManyRankings);
+            }
+
+            let caller = self.env().caller();
+            self.cast_vote(proposal_id, proposal, caller, Some(rankings[0]))?;
+
+            let weight = self
+                .ballots
+                .get(&(proposal_id, caller))
+                .map(|ballot| ballot.weight)
+                .unwrap_or_default();
+            match existing {
+                Some(position) => ballots[position].1 += weight,
+                None => ballots.push((rankings, weight)),
+            }
+            self.ranked_ballots.insert(&proposal_id, &ballots);
+
+            Ok(())
+        }
+
+        /// Stores the hash of `(candidate_id, salt, caller)`; committing again before the
+        /// commit phase ends replaces the previous commitment
+        #[ink(message)]
+        pub fn commit_vote(&mut self, proposal_id: ProposalId, commitment: Hash) -> Result<(), VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            let VotingMode::CommitReveal { commit_ends_at } = proposal.mode else {
+                return Err(VotingError::WrongVotingMode);
+            };
+            Self::ensure_open(&proposal)?;
+            if self.env().block_timestamp() < proposal.starts_at {
+                return Err(VotingError::VotingNotStarted);
+            }
+            if self.env().block_timestamp() >= commit_ends_at {
+                return Err(VotingError::CommitPhaseEnded);
+            }
+
+            self.commitments.insert(&(proposal_id, self.env().caller()), &commitment);
+            Ok(())
+        }
+
+        /// Opens the caller's commitment and tallies the vote
+        #[ink(message)]
+        pub fn reveal_vote(&mut self, proposal_id: ProposalId, candidate_id: CandidateId, salt: [u8; 32]) -> Result<(), VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            let VotingMode::CommitReveal { commit_ends_at } = proposal.mode else {
+                return Err(VotingError::WrongVotingMode);
+            };
+            if self.env().block_timestamp() < commit_ends_at {
+                return Err(VotingError::RevealPhaseNotStarted);
+            }
+
+            let caller = self.env().caller();
+            let commitment = self
+                .commitments
+                .get(&(proposal_id, caller))
+                .ok_or(VotingError::NoCommitment)?;
+            if Self::commitment_hash(candidate_id, &salt, &caller) != commitment {
+                return Err(VotingError::InvalidReveal);
+            }
+
+            self.cast_vote(proposal_id, proposal, caller, Some(candidate_id))?;
+            self.commitments.remove(&(proposal_id, caller));
+            Ok(())
+        }
+
+        /// Hash a voter commits to in commit-reveal proposals
+        #[ink(message)]
+        pub fn get_commitment_hash(&self, candidate_id: CandidateId, salt: [u8; 32], voter: AccountId) -> Hash {
+            Self::commitment_hash(candidate_id, &salt, &voter)
+        }
+
+        fn cast_vote(
+            &mut self,
+            proposal_id: ProposalId,
+            mut proposal: Proposal,
+            caller: AccountId,
+            candidate: Option<CandidateId>,
+        ) -> Result<(), VotingError> {
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Ended {
+                return Err(VotingError::VotingEnded);
+            }
+            if self.env().block_timestamp() < proposal.starts_at {
+                return Err(VotingError::VotingNotStarted);
+            }
+
+            if self.ballots.contains(&(proposal_id, caller)) {
+                return Err(VotingError::AlreadyVoted);
+            }
+
+            if let Some(candidate_id) = candidate {
+                Self::check_candidate(&proposal, candidate_id)?;
+            }
+
+            if self.delegates.contains(&(proposal_id, caller)) {
+                return Err(VotingError::VoteDelegated);
+            }
+
+            if !self.is_eligible(proposal_id, &proposal, caller) {
+                return Err(VotingError::NotEligible);
+            }
+
+            let mut weight = self.lock_voting_power(proposal_id, caller)?;
+            for delegator in self.delegators.get(&(proposal_id, caller)).unwrap_or_default() {
+                // Delegators deregistered after delegating lose their voting power, and so
+                // do delegators whose power cannot be locked, e.g. for lack of an approval:
+                // delegating needs no consent, so they must not be able to block the vote
+                if self.is_eligible(proposal_id, &proposal, delegator) {
+                    weight += self.lock_voting_power(proposal_id, delegator).unwrap_or(0);
+                }
+            }
+            if weight == 0 {
+                return Err(VotingError::ZeroVotingPower);
+            }
+
+            let ballot = Ballot { candidate, weight };
+            self.count_ballot(proposal_id, &mut proposal, &ballot);
+            proposal.voting_started = true;
+            self.proposals.insert(&proposal_id, &proposal);
+
+            match ballot.candidate {
+                Some(candidate_id) => self.env().emit_event(VoteCast {
+                    proposal_id,
+                    voter: caller,
+                    candidate_index: candidate_id,
+                    weight,
+                }),
+                None => self.env().emit_event(Abstained {
+                    proposal_id,
+                    voter: caller,
+                    weight,
+                }),
+            }
+            self.store_ballot(proposal_id, caller, &ballot);
+
+            Ok(())
+        }
+
+        /// Moves the caller's vote, with its full weight, to `new_candidate_id`
+        #[ink(message)]
+        pub fn change_vote(&mut self, proposal_id: ProposalId, new_candidate_id: CandidateId) -> Result<(), VotingError> {
+            self.move_vote(proposal_id, Some(new_candidate_id))
+        }
+
+        /// Turns the caller's vote into an abstention, under the same rules as `change_vote`
+        #[ink(message)]
+        pub fn change_vote_to_abstain(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            self.move_vote(proposal_id, None)
+        }
+
+        fn move_vote(&mut self, proposal_id: ProposalId, new_candidate: Option<CandidateId>) -> Result<(), VotingError> {
+            let mut proposal = self.proposal(proposal_id)?;
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Ended {
+                return Err(VotingError::VotingEnded);
+            }
+            if proposal.mode != VotingMode::Public {
+                return Err(VotingError::WrongVotingMode);
+            }
+
+            let caller = self.env().caller();
+            let old_ballot = self
+                .ballots
+                .get(&(proposal_id, caller))
+                .ok_or(VotingError::NotVoted)?;
+            if let Some(candidate_id) = new_candidate {
+                Self::check_candidate(&proposal, candidate_id)?;
+            }
+
+            let new_ballot = Ballot {
+                candidate: new_candidate,
+                weight: old_ballot.weight,
+            };
+            self.uncount_ballot(proposal_id, &mut proposal, &old_ballot);
+            self.count_ballot(proposal_id, &mut proposal, &new_ballot);
+            self.proposals.insert(&proposal_id, &proposal);
+
+            self.env().emit_event(VoteChanged {
+                proposal_id,
+                voter: caller,
+                old_candidate_index: old_ballot.candidate,
+                new_candidate_index: new_ballot.candidate,
+                weight: old_ballot.weight,
+            });
+            self.store_ballot(proposal_id, caller, &new_ballot);
+
+            Ok(())
+        }
+
+        /// Locks the voting tokens the caller acquired since voting and adds them to
+        /// the weight of their existing ballot, for the same candidate. Only the newly
+        /// locked tokens are counted, so topping up twice without new tokens fails.
+        /// Token-weighted public proposals only; stake-weighted votes cannot be topped up.
+        #[ink(message)]
+        pub fn top_up_vote(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            let mut proposal = self.proposal(proposal_id)?;
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Ended {
+                return Err(VotingError::VotingEnded);
+            }
+            let Some(token) = self.voting_token else {
+                return Err(VotingError::WrongVotingMode);
+            };
+            if proposal.mode != VotingMode::Public || self.holding_cap > 0 {
+                return Err(VotingError::WrongVotingMode);
+            }
+
+            let caller = self.env().caller();
+            let mut ballot = self
+                .ballots
+                .get(&(proposal_id, caller))
+                .ok_or(VotingError::NotVoted)?;
+            let added_weight = self.token_balance(token, caller)?;
+            if added_weight == 0 {
+                return Err(VotingError::ZeroVotingPower);
+            }
+            self.pull_tokens(proposal_id, caller, added_weight)?;
+
+            match ballot.candidate {
+                Some(candidate_id) => {
+                    self.add_votes(proposal_id, candidate_id, added_weight);
+                    proposal.total_votes += added_weight;
+                }
+                None => proposal.abstain_votes += added_weight,
+            }
+            self.proposals.insert(&proposal_id, &proposal);
+            ballot.weight += added_weight;
+
+            self.env().emit_event(VoteToppedUp {
+                proposal_id,
+                voter: caller,
+                candidate_index: ballot.candidate,
+                added_weight,
+                weight: ballot.weight,
+            });
+            self.store_ballot(proposal_id, caller, &ballot);
+
+            Ok(())
+        }
+
+        /// Withdraws the caller's vote so they may vote again while the proposal is active
+        #[ink(message)]
+        pub fn revoke_vote(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            let mut proposal = self.proposal(proposal_id)?;
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Ended {
+                return Err(VotingError::VotingEnded);
+            }
+            if proposal.mode != VotingMode::Public {
+                return Err(VotingError::WrongVotingMode);
+            }
+
+            let caller = self.env().caller();
+            let ballot = self
+                .ballots
+                .take(&(proposal_id, caller))
+                .ok_or(VotingError::NotVoted)?;
+            self.receipts.remove(&(proposal_id, caller));
+
+            self.uncount_ballot(proposal_id, &mut proposal, &ballot);
+            self.proposals.insert(&proposal_id, &proposal);
+
+            self.env().emit_event(VoteRevoked {
+                proposal_id,
+                voter: caller,
+                candidate_index: ballot.candidate,
+                weight: ballot.weight,
+            });
+
+            Ok(())
+        }
+
+        /// Adds a candidate. Admin-only and only before the first vote is cast.
+        #[ink(message)]
+        pub fn add_candidate(&mut self, proposal_id: ProposalId, name: String) -> Result<(), VotingError> {
+            let mut proposal = self.editable_proposal(proposal_id)?;
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(VotingError::InvalidCandidate);
+            }
+            if self.find_candidate(proposal_id, &proposal, name).is_some() {
+                return Err(VotingError::DuplicateCandidate);
+            }
+            if proposal.candidate_count >= MAX_CANDIDATES {
+                return Err(VotingError::TooManyCandidates);
+            }
+
+            let candidate_id = proposal.candidate_count;
+            self.candidate_names.insert(&(proposal_id, candidate_id), &String::from(name));
+            proposal.candidate_count += 1;
+            self.proposals.insert(&proposal_id, &proposal);
+            self.env().emit_event(CandidateAdded {
+                proposal_id,
+                index: candidate_id,
+            });
+
+            Ok(())
+        }
+
+        /// Removes a candidate; the ids of later candidates shift down by one. Admin-only
+        /// and only before the first vote is cast, so no tally needs moving.
+        #[ink(message)]
+        pub fn remove_candidate(&mut self, proposal_id: ProposalId, candidate_id: CandidateId) -> Result<(), VotingError> {
+            let mut proposal = self.editable_proposal(proposal_id)?;
+            Self::check_candidate(&proposal, candidate_id)?;
+            if proposal.candidate_count <= MIN_CANDIDATES {
+                return Err(VotingError::TooFewCandidates);
+            }
+            if proposal.candidate_count <= proposal.seats {
+                return Err(VotingError::InvalidSeats);
+            }
+
+            for next_id in candidate_id + 1..proposal.candidate_count {
+                let name = self.candidate_name(proposal_id, next_id);
+                self.candidate_names.insert(&(proposal_id, next_id - 1), &name);
+            }
+            proposal.candidate_count -= 1;
+            self.candidate_names.remove(&(proposal_id, proposal.candidate_count));
+            self.proposals.insert(&proposal_id, &proposal);
+            self.env().emit_event(CandidateRemoved {
+                proposal_id,
+                index: candidate_id,
+            });
+
+            Ok(())
+        }
+
+        /// Registers `accounts` as eligible voters and switches the proposal to
+        /// whitelist mode. Admin-only, with 1 to `MAX_REGISTRATION_BATCH` accounts per
+        /// call, so an empty batch cannot close the proposal to everyone.
+        ///
+        /// The whitelist is frozen together with the candidate list once the first vote
+        /// is cast: neither additions nor removals are accepted afterwards.
+        #[ink(message)]
+        pub fn register_voters(&mut self, proposal_id: ProposalId, accounts: Vec<AccountId>) -> Result<(), VotingError> {
+            let mut proposal = self.editable_proposal(proposal_id)?;
+            if accounts.is_empty() {
+                return Err(VotingError::EmptyBatch);
+            }
+            if accounts.len() as u32 > MAX_REGISTRATION_BATCH {
+                return Err(VotingError::BatchTooLarge);
+            }
+
+            for account in accounts {
+                if self.eligible.insert(&(proposal_id, account), &()).is_none() {
+                    proposal.eligible_count += 1;
+                }
+            }
+            proposal.whitelist = true;
+            self.proposals.insert(&proposal_id, &proposal);
+
+            Ok(())
+        }
+
+        /// Removes `account` from the whitelist. Admin-only and only before the first vote is cast.
+        #[ink(message)]
+        pub fn deregister_voter(&mut self, proposal_id: ProposalId, account: AccountId) -> Result<(), VotingError> {
+            let mut proposal = self.editable_proposal(proposal_id)?;
+            if self.eligible.take(&(proposal_id, account)).is_none() {
+                return Err(VotingError::NotEligible);
+            }
+
+            proposal.eligible_count -= 1;
+            self.proposals.insert(&proposal_id, &proposal);
+
+            Ok(())
+        }
+
+        /// Hands the caller's voting power on `proposal_id` to `to`. Rejected once
+        /// either side has voted on that proposal.
+        ///
+        /// Delegation is single-level: an account that delegated cannot receive
+        /// delegations and an account holding delegations cannot delegate onward, so
+        /// chains (A→B→C) and cycles are rejected with `ChainedDelegation`.
+        #[ink(message)]
+        pub fn delegate(&mut self, proposal_id: ProposalId, to: AccountId) -> Result<(), VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Ended {
+                return Err(VotingError::VotingEnded);
+            }
+            if matches!(proposal.mode, VotingMode::Quadratic { .. }) {
+                return Err(VotingError::WrongVotingMode);
+            }
+
+            let caller = self.env().caller();
+            if caller == to {
+                return Err(VotingError::SelfDelegation);
+            }
+            if !self.is_eligible(proposal_id, &proposal, caller) || !self.is_eligible(proposal_id, &proposal, to) {
+                return Err(VotingError::NotEligible);
+            }
+            if self.delegates.contains(&(proposal_id, caller)) {
+                return Err(VotingError::AlreadyDelegated);
+            }
+            if self.delegates.contains(&(proposal_id, to)) || self.delegators.contains(&(proposal_id, caller)) {
+                return Err(VotingError::ChainedDelegation);
+            }
+            if self.ballots.contains(&(proposal_id, caller)) {
+                return Err(VotingError::AlreadyVoted);
+            }
+            if self.ballots.contains(&(proposal_id, to)) {
+                return Err(VotingError::DelegateAlreadyVoted);
+            }
+
+            let mut delegators = self.delegators.get(&(proposal_id, to)).unwrap_or_default();
+            if delegators.len() as u32 >= MAX_DELEGATORS {
+                return Err(VotingError::TooManyDelegators);
+            }
+            delegators.push(caller);
+            self.delegators.insert(&(proposal_id, to), &delegators);
+            self.delegates.insert(&(proposal_id, caller), &to);
+
+            self.env().emit_event(Delegated {
+                proposal_id,
+                delegator: caller,
+                delegate: to,
+            });
+
+            Ok(())
+        }
+
+        /// Takes back the caller's voting power, as long as the delegate has not used it
+        #[ink(message)]
+        pub fn undelegate(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            let caller = self.env().caller();
+            let delegate = self
+                .delegates
+                .get(&(proposal_id, caller))
+                .ok_or(VotingError::NotDelegated)?;
+            if self.ballots.contains(&(proposal_id, delegate)) {
+                return Err(VotingError::DelegateAlreadyVoted);
+            }
+
+            let mut delegators = self.delegators.get(&(proposal_id, delegate)).unwrap_or_default();
+            delegators.retain(|account| *account != caller);
+            if delegators.is_empty() {
+                self.delegators.remove(&(proposal_id, delegate));
+            } else {
+                self.delegators.insert(&(proposal_id, delegate), &delegators);
+            }
+            self.delegates.remove(&(proposal_id, caller));
+
+            self.env().emit_event(Undelegated {
+                proposal_id,
+                delegator: caller,
+                delegate,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_delegate(&self, proposal_id: ProposalId, account: AccountId) -> Option<AccountId> {
+            self.delegates.get(&(proposal_id, account))
+        }
+
+        #[ink(message)]
+        pub fn get_delegators(&self, proposal_id: ProposalId, account: AccountId) -> Vec<AccountId> {
+            self.delegators.get(&(proposal_id, account)).unwrap_or_default()
+        }
+
+        /// Freezes the results once the deadline has passed. Callable by anyone, once.
+        /// A proposal that misses quorum or the minimum margin ends as `Failed`
+        /// without a winner.
+        #[ink(message)]
+        pub fn finalize(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            let mut proposal = self.proposal(proposal_id)?;
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Active {
+                return Err(VotingError::VotingStillActive);
+            }
+            if proposal.finalized {
+                return Err(VotingError::AlreadyFinalized);
+            }
+
+            proposal.finalized = true;
+            self.proposals.insert(&proposal_id, &proposal);
+
+            if proposal.mode == VotingMode::Ranked {
+                let (rounds, _, _) = self.instant_runoff(proposal_id, &proposal);
+                self.round_results.insert(&proposal_id, &rounds);
+            }
+
+            self.env().emit_event(VotingEnded {
+                proposal_id,
+                winner_index: self.winner_id(proposal_id, &proposal),
+                total_votes: proposal.total_votes,
+            });
+
+            Ok(())
+        }
+
+        /// Suspends voting on a running proposal. Admin-only. With `extend_on_pause` the
+        /// deadlines are pushed back by the pause length on `resume_voting`.
+        #[ink(message)]
+        pub fn pause_voting(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            if self.env().caller() != self.admin {
+                return Err(VotingError::NotAdmin);
+            }
+            let mut proposal = self.proposal(proposal_id)?;
+            Self::ensure_open(&proposal)?;
+            if self.state_of(&proposal) == VotingState::Ended {
+                return Err(VotingError::VotingEnded);
+            }
+
+            proposal.paused_at = Some(self.env().block_timestamp());
+            self.proposals.insert(&proposal_id, &proposal);
+            self.env().emit_event(VotingPaused { proposal_id });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn resume_voting(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            if self.env().caller() != self.admin {
+                return Err(VotingError::NotAdmin);
+            }
+            let mut proposal = self.proposal(proposal_id)?;
+            if proposal.cancelled {
+                return Err(VotingError::ProposalCancelled);
+            }
+            let paused_at = proposal.paused_at.take().ok_or(VotingError::NotPaused)?;
+
+            if self.extend_on_pause {
+                let pause = self.env().block_timestamp() - paused_at;
+                proposal.ends_at += pause;
+                if let VotingMode::CommitReveal { commit_ends_at } = &mut proposal.mode {
+                    if *commit_ends_at > paused_at {
+                        *commit_ends_at += pause;
+                    }
+                }
+            }
+            self.proposals.insert(&proposal_id, &proposal);
+            self.env().emit_event(VotingResumed {
+                proposal_id,
+                ends_at: proposal.ends_at,
+            });
+
+            Ok(())
+        }
+
+        /// Calls off a proposal that is not finalized yet. No votes, finalization or
+        /// execution are possible afterwards, and tokens locked for it can be withdrawn
+        /// right away. Admin-only.
+        #[ink(message)]
+        pub fn cancel_proposal(&mut self, proposal_id: ProposalId) -> Result<(), VotingError> {
+            if self.env().caller() != self.admin {
+                return Err(VotingError::NotAdmin);
+            }
+            let mut proposal = self.proposal(proposal_id)?;
+            if proposal.cancelled {
+                return Err(VotingError::ProposalCancelled);
+            }
+            if proposal.finalized {
+                return Err(VotingError::AlreadyFinalized);
+            }
+
+            proposal.cancelled = true;
+            proposal.paused_at = None;
+            self.proposals.insert(&proposal_id, &proposal);
+            self.env().emit_event(ProposalCancelled { proposal_id });
+
+            Ok(())
+        }
+
+        /// Attaches the action to execute if the proposal passes. Admin-only and only
+        /// before the first vote is cast.
+        #[ink(message)]
+        pub fn set_action(&mut self, proposal_id: ProposalId, action: ProposalAction) -> Result<(), VotingError> {
+            let proposal = self.editable_proposal(proposal_id)?;
+            Self::check_candidate(&proposal, action.candidate_index)?;
+            self.actions.insert(&proposal_id, &action);
+            Ok(())
+        }
+
+        /// Runs the proposal's action once it is finalized with the action's candidate
+        /// as winner and `execution_delay` has passed since it ended. Callable by
+        /// anyone, exactly once: a call that fails is recorded as failed and cannot be
+        /// retried.
+        #[ink(message)]
+        pub fn execute(&mut self, proposal_id: ProposalId) -> Result<bool, VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            let action = self.actions.get(&proposal_id).ok_or(VotingError::NoAction)?;
+            if !proposal.finalized {
+                return Err(VotingError::NotPassed);
+            }
+            if self.executions.contains(&proposal_id) {
+                return Err(VotingError::AlreadyExecuted);
+            }
+            if self.env().block_timestamp() < proposal.ends_at.saturating_add(self.execution_delay) {
+                return Err(VotingError::TimelockNotElapsed);
+            }
+            if self.winner_id(proposal_id, &proposal) != Some(action.candidate_index) {
+                return Err(VotingError::NotPassed);
+            }
+
+            let success = matches!(
+                build_call::<DefaultEnvironment>()
+                    .call(action.callee)
+                    .transferred_value(action.transferred_value)
+                    .exec_input(ExecutionInput::new(Selector::new(action.selector)).push_arg(CallInput(&action.input)))
+                    .returns::<()>()
+                    .try_invoke(),
+                Ok(Ok(()))
+            );
+            self.executions.insert(&proposal_id, &success);
+
+            self.env().emit_event(ProposalExecuted { proposal_id, success });
+
+            Ok(success)
+        }
+
+        /// Stakes `amount` voting tokens, pulled in with `transfer_from`. While
+        /// `holding_cap` is set, votes are weighted by stake and its age instead of the
+        /// voter's balance; adding to a stake makes its age the amount-weighted average.
+        #[ink(message)]
+        pub fn stake_for_voting(&mut self, amount: Balance) -> Result<(), VotingError> {
+            let Some(token) = self.voting_token else {
+                return Err(VotingError::WrongVotingMode);
+            };
+            if amount == 0 {
+                return Err(VotingError::ZeroVotingPower);
+            }
+
+            let caller = self.env().caller();
+            self.token_transfer_from(token, caller, amount)?;
+
+            let now = self.env().block_number();
+            let stake = match self.voting_stakes.get(&caller) {
+                Some(stake) => {
+                    let total = stake.amount + amount;
+                    let since = (stake.amount * stake.since as u128 + amount * now as u128) / total;
+                    VotingStake {
+                        amount: total,
+                        since: since as BlockNumber,
+                        locked_by: stake.locked_by,
+                    }
+                }
+                None => VotingStake {
+                    amount,
+                    since: now,
+                    locked_by: Vec::new(),
+                },
+            };
+            self.voting_stakes.insert(&caller, &stake);
+
+            Ok(())
+        }
+
+        /// Returns the caller's whole stake once every proposal it voted on has ended or
+        /// was cancelled
+        #[ink(message)]
+        pub fn unstake(&mut self) -> Result<Balance, VotingError> {
+            let caller = self.env().caller();
+            let stake = self.voting_stakes.get(&caller).ok_or(VotingError::NotStaked)?;
+            if stake.locked_by.iter().any(|proposal_id| self.holds_stake(*proposal_id)) {
+                return Err(VotingError::StakeInUse);
+            }
+
+            self.voting_stakes.remove(&caller);
+            self.token_transfer(caller, stake.amount)?;
+
+            Ok(stake.amount)
+        }
+
+        #[ink(message)]
+        pub fn get_voting_stake(&self, account: AccountId) -> Option<VotingStake> {
+            self.voting_stakes.get(&account)
+        }
+
+        /// Weight `account`'s stake would vote with now
+        #[ink(message)]
+        pub fn get_stake_weight(&self, account: AccountId) -> u128 {
+            self.voting_stakes
+                .get(&account)
+                .map(|stake| self.stake_weight(&stake))
+                .unwrap_or(0)
+        }
+
+        /// Enables stake-age weighting with full weight after `holding_cap` blocks, or
+        /// disables it with 0. Admin-only; requires a voting token.
+        #[ink(message)]
+        pub fn set_holding_cap(&mut self, holding_cap: BlockNumber) -> Result<(), VotingError> {
+            if self.env().caller() != self.admin {
+                return Err(VotingError::NotAdmin);
+            }
+            if self.voting_token.is_none() {
+                return Err(VotingError::WrongVotingMode);
+            }
+            self.holding_cap = holding_cap;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_holding_cap(&self) -> BlockNumber {
+            self.holding_cap
+        }
+
+        /// Sets the timelock between a proposal's end and the execution of its action.
+        /// Admin-only.
+        #[ink(message)]
+        pub fn set_execution_delay(&mut self, delay: Timestamp) -> Result<(), VotingError> {
+            if self.env().caller() != self.admin {
+                return Err(VotingError::NotAdmin);
+            }
+            self.execution_delay = delay;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_execution_delay(&self) -> Timestamp {
+            self.execution_delay
+        }
+
+        #[ink(message)]
+        pub fn get_action(&self, proposal_id: ProposalId) -> Option<ProposalAction> {
+            self.actions.get(&proposal_id)
+        }
+
+        /// `None` until executed, then whether the action's call succeeded
+        #[ink(message)]
+        pub fn get_execution(&self, proposal_id: ProposalId) -> Option<bool> {
+            self.executions.get(&proposal_id)
+        }
+
+        /// Number of instant-runoff rounds stored for a finalized ranked proposal
+        #[ink(message)]
+        pub fn get_round_count(&self, proposal_id: ProposalId) -> u32 {
+            self.round_results
+                .get(&proposal_id)
+                .map(|rounds| rounds.len() as u32)
+                .unwrap_or(0)
+        }
+
+        /// Tallies of the candidates still in the count at `round` (0-based), as
+        /// `(candidate id, votes)`. Empty until the ranked proposal is finalized.
+        #[ink(message)]
+        pub fn get_round_results(&self, proposal_id: ProposalId, round: u32) -> Vec<(u32, u128)> {
+            self.round_results
+                .get(&proposal_id)
+                .and_then(|mut rounds| {
+                    if (round as usize) < rounds.len() {
+                        Some(rounds.swap_remove(round as usize))
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or_default()
+        }
+
+        /// Result of the proposal, available once its deadline has passed
+        #[ink(message)]
+        pub fn get_winner(&self, proposal_id: ProposalId) -> Result<WinnerResult, VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if proposal.cancelled {
+                return Err(VotingError::ProposalCancelled);
+            }
+            if self.state_of(&proposal) == VotingState::Active {
+                return Err(VotingError::VotingStillActive);
+            }
+            self.check_quorum(&proposal)?;
+            self.check_margin(proposal_id, &proposal)?;
+            let (leaders, votes) = self.compute_winner(proposal_id, &proposal);
+            Ok(match leaders.len() {
+                0 => WinnerResult::NoVotes,
+                1 => WinnerResult::Winner(self.candidate_name(proposal_id, leaders[0]), votes),
+                _ => WinnerResult::Tie(
+                    leaders
+                        .into_iter()
+                        .map(|candidate_id| self.candidate_name(proposal_id, candidate_id))
+                        .collect(),
+                    votes,
+                ),
+            })
+        }
+
+        /// Candidates sorted by votes (descending, ties alphabetical), paginated with
+        /// `limit` capped at `MAX_RESULTS_PAGE`
+        #[ink(message)]
+        pub fn get_results(&self, proposal_id: ProposalId, offset: u32, limit: u32) -> Vec<(String, u128)> {
+            let Some(proposal) = self.proposals.get(&proposal_id) else {
+                return Vec::new();
+            };
+            if !self.tally_visible(proposal_id) {
+                return Vec::new();
+            }
+            self.sorted_results(proposal_id, &proposal)
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit.min(MAX_RESULTS_PAGE) as usize)
+                .collect()
+        }
+
+        /// The `seats` candidates with the most votes once the proposal has ended. Ties
+        /// at the cutoff are broken alphabetically; candidates without votes never win.
+        #[ink(message)]
+        pub fn winners(&self, proposal_id: ProposalId) -> Result<Vec<String>, VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if proposal.cancelled {
+                return Err(VotingError::ProposalCancelled);
+            }
+            if self.state_of(&proposal) == VotingState::Active {
+                return Err(VotingError::VotingStillActive);
+            }
+            self.check_quorum(&proposal)?;
+            if proposal.mode == VotingMode::Ranked {
+                return Ok(match self.instant_runoff(proposal_id, &proposal).1[..] {
+                    [winner] => vec![self.candidate_name(proposal_id, winner)],
+                    _ => Vec::new(),
+                });
+            }
+            Ok(self
+                .sorted_results(proposal_id, &proposal)
+                .into_iter()
+                .filter(|(_, votes)| *votes > 0)
+                .take(proposal.seats as usize)
+                .map(|(candidate, _)| candidate)
+                .collect())
+        }
+
+        /// Number of accounts that voted for a candidate, number that abstained and
+        /// number of registered voters (0 while no whitelist exists)
+        #[ink(message)]
+        pub fn get_turnout(&self, proposal_id: ProposalId) -> (u32, u32, u32) {
+            self.proposals
+                .get(&proposal_id)
+                .map(|proposal| (proposal.voter_count, proposal.abstain_count, proposal.eligible_count))
+                .unwrap_or((0, 0, 0))
+        }
+
+        #[ink(message)]
+        pub fn get_abstain_count(&self, proposal_id: ProposalId) -> u32 {
+            self.proposals
+                .get(&proposal_id)
+                .map(|proposal| proposal.abstain_count)
+                .unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn get_eligible_count(&self, proposal_id: ProposalId) -> u32 {
+            self.proposals
+                .get(&proposal_id)
+                .map(|proposal| proposal.eligible_count)
+                .unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn is_registered(&self, proposal_id: ProposalId, account: AccountId) -> bool {
+            self.eligible.contains(&(proposal_id, account))
+        }
+
+        #[ink(message)]
+        pub fn get_state(&self, proposal_id: ProposalId) -> Result<VotingState, VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if proposal.cancelled {
+                return Ok(VotingState::Cancelled);
+            }
+            let state = self.state_of(&proposal);
+            if state == VotingState::Active && proposal.paused_at.is_some() {
+                return Ok(VotingState::Paused);
+            }
+            if state == VotingState::Ended
+                && (self.check_quorum(&proposal).is_err() || self.check_margin(proposal_id, &proposal).is_err())
+            {
+                return Ok(VotingState::Failed);
+            }
+            Ok(state)
+        }
+
+        #[ink(message)]
+        pub fn get_proposal(&self, proposal_id: ProposalId) -> Option<Proposal> {
+            self.proposals.get(&proposal_id)
+        }
+
+        #[ink(message)]
+        pub fn get_proposal_count(&self) -> ProposalId {
+            self.next_proposal_id
+        }
+
+        /// Votes of `candidate_id`. Commit-reveal tallies read as 0 until the reveal phase ends.
+        #[ink(message)]
+        pub fn get_votes(&self, proposal_id: ProposalId, candidate_id: CandidateId) -> u128 {
+            if !self.tally_visible(proposal_id) {
+                return 0;
+            }
+            self.votes.get(&(proposal_id, candidate_id)).unwrap_or(0)
+        }
+
+        /// `(block, votes)` checkpoints of `candidate_id`, oldest first, one per block in
+        /// which its tally changed. Empty for commit-reveal proposals until the reveal
+        /// phase ends.
+        #[ink(message)]
+        pub fn vote_timeline(
+            &self,
+            proposal_id: ProposalId,
+            candidate_id: CandidateId,
+        ) -> Vec<(BlockNumber, u128)> {
+            if !self.tally_visible(proposal_id) {
+                return Vec::new();
+            }
+            self.vote_checkpoints.get(&(proposal_id, candidate_id)).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn has_voted(&self, proposal_id: ProposalId, account: AccountId) -> bool {
+            self.ballots.contains(&(proposal_id, account))
+        }
+
+        /// Returns the caller's voting tokens locked for `proposal_id` once it has ended
+        /// or was cancelled
+        #[ink(message)]
+        pub fn withdraw_locked(&mut self, proposal_id: ProposalId) -> Result<Balance, VotingError> {
+            let proposal = self.proposal(proposal_id)?;
+            if !proposal.cancelled && self.state_of(&proposal) == VotingState::Active {
+                return Err(VotingError::VotingStillActive);
+            }
+
+            let caller = self.env().caller();
+            let amount = self
+                .locked
+                .take(&(proposal_id, caller))
+                .ok_or(VotingError::NothingLocked)?;
+            self.token_transfer(caller, amount)?;
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        pub fn get_locked(&self, proposal_id: ProposalId, account: AccountId) -> Balance {
+            self.locked.get(&(proposal_id, account)).unwrap_or(0)
+        }
+
+        /// Candidate and weight of the caller's vote, if they voted
+        #[ink(message)]
+        pub fn get_my_vote(&self, proposal_id: ProposalId) -> Option<Ballot> {
+            self.ballots.get(&(proposal_id, self.env().caller()))
+        }
+
+        /// Receipt of `account`'s counted ballot. Commit-reveal ballots get one when revealed.
+        #[ink(message)]
+        pub fn get_receipt(&self, proposal_id: ProposalId, account: AccountId) -> Option<Receipt> {
+            self.receipts.get(&(proposal_id, account))
+        }
+
+        #[ink(message)]
+        pub fn get_quorum(&self) -> (u128, u16, bool) {
+            (self.quorum, self.min_margin_bps, self.abstain_counts_for_quorum)
+        }
+
+        #[ink(message)]
+        pub fn get_admin(&self) -> AccountId {
+            self.admin
+        }
+
+        #[ink(message)]
+        pub fn get_voting_token(&self) -> Option<AccountId> {
+            self.voting_token
+        }
+
+        /// Candidates as `(id, name)` in id order, as referenced by the vote and candidate events
+        #[ink(message)]
+        pub fn get_candidates(&self, proposal_id: ProposalId) -> Vec<(CandidateId, String)> {
+            let candidate_count = self
+                .proposals
+                .get(&proposal_id)
+                .map(|proposal| proposal.candidate_count)
+                .unwrap_or(0);
+            (0..candidate_count)
+                .map(|candidate_id| (candidate_id, self.candidate_name(proposal_id, candidate_id)))
+                .collect()
+        }
+
+        /// Id of the candidate named `name`, matched after trimming and ignoring ASCII case.
+        /// Meant for frontends resolving names before calling the id-based messages.
+        #[ink(message)]
+        pub fn get_candidate_id(&self, proposal_id: ProposalId, name: String) -> Option<CandidateId> {
+            let proposal = self.proposals.get(&proposal_id)?;
+            self.find_candidate(proposal_id, &proposal, &name)
+        }
+
+        fn proposal(&self, proposal_id: ProposalId) -> Result<Proposal, VotingError> {
+            self.proposals.get(&proposal_id).ok_or(VotingError::InvalidProposal)
+        }
+
+        fn state_of(&self, proposal: &Proposal) -> VotingState {
+            if self.env().block_timestamp() < proposal.ends_at {
+                VotingState::Active
+            } else {
+                VotingState::Ended
+            }
+        }
+
+        /// Rejects anything but reads on paused or cancelled proposals
+        fn ensure_open(proposal: &Proposal) -> Result<(), VotingError> {
+            if proposal.cancelled {
+                return Err(VotingError::ProposalCancelled);
+            }
+            if proposal.paused_at.is_some() {
+                return Err(VotingError::Paused);
+            }
+            Ok(())
+        }
+
+        fn editable_proposal(&self, proposal_id: ProposalId) -> Result<Proposal, VotingError> {
+            if self.env().caller() != self.admin {
+                return Err(VotingError::NotAdmin);
+            }
+            let proposal = self.proposal(proposal_id)?;
+            if proposal.cancelled {
+                return Err(VotingError::ProposalCancelled);
+            }
+            if proposal.voting_started {
+                return Err(VotingError::VotingStarted);
+            }
+            Ok(proposal)
+        }
+
+        fn add_votes(&mut self, proposal_id: ProposalId, candidate_id: CandidateId, weight: u128) {
+            let key = (proposal_id, candidate_id);
+            let current_votes = self.votes.get(&key).unwrap_or(0);
+            self.votes.insert(&key, &(current_votes + weight));
+            self.record_checkpoint(proposal_id, candidate_id, current_votes + weight);
+        }
+
+        fn remove_votes(&mut self, proposal_id: ProposalId, candidate_id: CandidateId, weight: u128) {
+            let key = (proposal_id, candidate_id);
+            let current_votes = self.votes.get(&key).unwrap_or(0);
+            self.votes.insert(&key, &(current_votes - weight));
+            self.record_checkpoint(proposal_id, candidate_id, current_votes - weight);
+        }
+
+        /// Appends `(current block, votes)` to the candidate's timeline. Changes within
+        /// one block, and every change once the timeline is full, overwrite the last
+        /// checkpoint.
+        fn record_checkpoint(&mut self, proposal_id: ProposalId, candidate_id: CandidateId, votes: u128) {
+            let key = (proposal_id, candidate_id);
+            let block = self.env().block_number();
+            let mut checkpoints = self.vote_checkpoints.get(&key).unwrap_or_default();
+            let full = checkpoints.len() as u32 >= MAX_TIMELINE_CHECKPOINTS;
+            match checkpoints.last_mut() {
+                Some(last) if last.0 == block || full => {
+                    *last = (block, votes);
+                }
+                _ => checkpoints.push((block, votes)),
+            }
+            self.vote_checkpoints.insert(&key, &checkpoints);
+        }
+
+        /// Weight `account` brings to `proposal_id`. In token-weighted mode the account's
+        /// whole token balance is pulled in with `transfer_from` (the voting contract
+        /// must be approved) and stays locked until the proposal ends, so tokens bought
+        /// or moved after voting cannot be counted twice. The weight is everything the
+        /// account has locked for the proposal.
+        ///
+        /// With stake weighting enabled the account's voting stake is used instead and
+        /// kept staked until the proposal ends.
+        fn lock_voting_power(&mut self, proposal_id: ProposalId, account: AccountId) -> Result<u128, VotingError> {
+            let Some(token) = self.voting_token else {
+                return Ok(1);
+            };
+
+            if self.holding_cap > 0 {
+                let Some(mut stake) = self.voting_stakes.get(&account) else {
+                    return Ok(0);
+                };
+                stake.locked_by.retain(|locked_id| self.holds_stake(*locked_id));
+                // A revoked vote leaves its lock in place, so voting again reuses it
+                if !stake.locked_by.contains(&proposal_id) {
+                    if stake.locked_by.len() as u32 >= MAX_STAKE_LOCKS {
+                        return Err(VotingError::TooManyStakeLocks);
+                    }
+                    stake.locked_by.push(proposal_id);
+                }
+                self.voting_stakes.insert(&account, &stake);
+                return Ok(self.stake_weight(&stake));
+            }
+
+            let balance = self.token_balance(token, account)?;
+            self.pull_tokens(proposal_id, account, balance)
+        }
+
+        /// Whether a stake that voted on `proposal_id` is still held by it
+        fn holds_stake(&self, proposal_id: ProposalId) -> bool {
+            self.proposals
+                .get(&proposal_id)
+                .map(|proposal| !proposal.cancelled && self.state_of(&proposal) == VotingState::Active)
+                .unwrap_or(false)
+        }
+
+        /// `stake.amount * min(age, holding_cap) / holding_cap`, with the age in blocks
+        fn stake_weight(&self, stake: &VotingStake) -> u128 {
+            if self.holding_cap == 0 {
+                return 0;
+            }
+            let age = self.env().block_number().saturating_sub(stake.since).min(self.holding_cap);
+            stake.amount * age as u128 / self.holding_cap as u128
+        }
+
+        /// Moves `amount` voting tokens from `account` into the contract, locked for
+        /// `proposal_id`, and returns everything the account has locked for it
+        fn pull_tokens(&mut self, proposal_id: ProposalId, account: AccountId, amount: Balance) -> Result<Balance, VotingError> {
+            let Some(token) = self.voting_token else {
+                return Err(VotingError::WrongVotingMode);
+            };
+
+            if amount > 0 {
+                self.token_transfer_from(token, account, amount)?;
+            }
+
+            let locked = self.locked.get(&(proposal_id, account)).unwrap_or(0) + amount;
+            if locked > 0 {
+                self.locked.insert(&(proposal_id, account), &locked);
+            }
+            Ok(locked)
+        }
+
+        /// Pulls `amount` voting tokens from `from` into the contract
+        fn token_transfer_from(&self, token: AccountId, from: AccountId, amount: Balance) -> Result<(), VotingError> {
+            let mut token: contract_ref!(PSP22) = token.into();
+            let result = token
+                .call_mut()
+                .transfer_from(from, self.env().account_id(), amount, Vec::new())
+                .try_invoke();
+            flatten_call(result, VotingError::TokenCallFailed, VotingError::TokenCallFailed)
+        }
+
+        fn token_transfer(&self, to: AccountId, amount: Balance) -> Result<(), VotingError> {
+            let Some(token) = self.voting_token else {
+                return Err(VotingError::NothingLocked);
+            };
+            let mut token: contract_ref!(PSP22) = token.into();
+            let result = token.call_mut().transfer(to, amount, Vec::new()).try_invoke();
+            flatten_call(result, VotingError::TokenCallFailed, VotingError::TokenCallFailed)
+        }
+
+        fn token_balance(&self, token: AccountId, account: AccountId) -> Result<Balance, VotingError> {
+            let token: contract_ref!(PSP22) = token.into();
+            let result = token.call().balance_of(account).try_invoke();
+            flatten_query(result, VotingError::TokenCallFailed)
+        }
+
+        fn tally_visible(&self, proposal_id: ProposalId) -> bool {
+            match self.proposals.get(&proposal_id) {
+                Some(proposal) if matches!(proposal.mode, VotingMode::CommitReveal { .. }) => {
+                    self.state_of(&proposal) == VotingState::Ended
+                }
+                _ => true,
+            }
+        }
+
+        /// Saves `account`'s ballot together with a receipt for the current block
+        fn store_ballot(&mut self, proposal_id: ProposalId, account: AccountId, ballot: &Ballot) {
+            self.ballots.insert(&(proposal_id, account), ballot);
+            self.receipts.insert(
+                &(proposal_id, account),
+                &Receipt {
+                    candidate_index: ballot.candidate,
+                    weight: ballot.weight,
+                    block: self.env().block_number(),
+                },
+            );
+        }
+
+        /// Adds a ballot's weight to its candidate's tally, or to the abstentions
+        fn count_ballot(&mut self, proposal_id: ProposalId, proposal: &mut Proposal, ballot: &Ballot) {
+            match ballot.candidate {
+                Some(candidate_id) => {
+                    self.add_votes(proposal_id, candidate_id, ballot.weight);
+                    proposal.total_votes += ballot.weight;
+                    proposal.voter_count += 1;
+                }
+                None => {
+                    proposal.abstain_votes += ballot.weight;
+                    proposal.abstain_count += 1;
+                }
+            }
+        }
+
+        fn uncount_ballot(&mut self, proposal_id: ProposalId, proposal: &mut Proposal, ballot: &Ballot) {
+            match ballot.candidate {
+                Some(candidate_id) => {
+                    self.remove_votes(proposal_id, candidate_id, ballot.weight);
+                    proposal.total_votes -= ballot.weight;
+                    proposal.voter_count -= 1;
+                }
+                None => {
+                    proposal.abstain_votes -= ballot.weight;
+                    proposal.abstain_count -= 1;
+                }
+            }
+        }
+
+        fn check_candidate(proposal: &Proposal, candidate_id: CandidateId) -> Result<(), VotingError> {
+            if candidate_id >= proposal.candidate_count {
+                return Err(VotingError::CandidateNotFound);
+            }
+            Ok(())
+        }
+
+        fn candidate_name(&self, proposal_id: ProposalId, candidate_id: CandidateId) -> String {
+            self.candidate_names.get(&(proposal_id, candidate_id)).unwrap_or_default()
+        }
+
+        /// Id of the candidate matching `name` after trimming, ignoring ASCII case. Reads
+        /// every name, so it is only used by admin messages and off-chain lookups.
+        fn find_candidate(&self, proposal_id: ProposalId, proposal: &Proposal, name: &str) -> Option<CandidateId> {
+            let name = name.trim();
+            (0..proposal.candidate_count)
+                .find(|candidate_id| self.candidate_name(proposal_id, *candidate_id).eq_ignore_ascii_case(name))
+        }
+
+        fn vote_signature_hash(&self, proposal_id: ProposalId, candidate_id: CandidateId, nonce: u64) -> [u8; 32] {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(
+                &(self.env().account_id(), proposal_id, candidate_id, nonce),
+                &mut output,
+            );
+            output
+        }
+
+        fn commitment_hash(candidate_id: CandidateId, salt: &[u8; 32], voter: &AccountId) -> Hash {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(&(candidate_id, salt, voter), &mut output);
+            Hash::from(output)
+        }
+
+        fn is_eligible(&self, proposal_id: ProposalId, proposal: &Proposal, account: AccountId) -> bool {
+            !proposal.whitelist || self.eligible.contains(&(proposal_id, account))
+        }
+
+        /// In unweighted whitelisted proposals the quorum is capped at the number of
+        /// registered voters, so a small electorate can still reach it
+        fn effective_quorum(&self, proposal: &Proposal) -> u128 {
+            if proposal.whitelist && self.voting_token.is_none() {
+                self.quorum.min(proposal.eligible_count as u128)
+            } else {
+                self.quorum
+            }
+        }
+
+        fn check_quorum(&self, proposal: &Proposal) -> Result<(), VotingError> {
+            let mut counted = proposal.total_votes;
+            if self.abstain_counts_for_quorum {
+                counted += proposal.abstain_votes;
+            }
+            if counted < self.effective_quorum(proposal) {
+                return Err(VotingError::QuorumNotReached);
+            }
+            Ok(())
+        }
+
+        /// The margin applies to plurality counts; ranked proposals are decided by majority
+        fn check_margin(&self, proposal_id: ProposalId, proposal: &Proposal) -> Result<(), VotingError> {
+            if self.min_margin_bps == 0 || proposal.mode == VotingMode::Ranked {
+                return Ok(());
+            }
+            let results = self.sorted_results(proposal_id, proposal);
+            let first = results.first().map(|(_, votes)| *votes).unwrap_or(0);
+            let second = results.get(1).map(|(_, votes)| *votes).unwrap_or(0);
+            let required = proposal.total_votes * self.min_margin_bps as u128 / BPS_DENOMINATOR;
+            if first - second < required {
+                return Err(VotingError::MarginNotReached);
+            }
+            Ok(())
+        }
+
+        /// All candidates with their votes, sorted descending by votes then by name
+        fn sorted_results(&self, proposal_id: ProposalId, proposal: &Proposal) -> Vec<(String, u128)> {
+            let mut results: Vec<(String, u128)> = (0..proposal.candidate_count)
+                .map(|candidate_id| {
+                    let votes = self.votes.get(&(proposal_id, candidate_id)).unwrap_or(0);
+                    (self.candidate_name(proposal_id, candidate_id), votes)
+                })
+                .collect();
+            results.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            results
+        }
+
+        /// Single winner of a proposal that ended, passing quorum and margin, without a tie
+        fn winner_id(&self, proposal_id: ProposalId, proposal: &Proposal) -> Option<CandidateId> {
+            if self.state_of(proposal) == VotingState::Active
+                || self.check_quorum(proposal).is_err()
+                || self.check_margin(proposal_id, proposal).is_err()
+            {
+                return None;
+            }
+            match self.compute_winner(proposal_id, proposal).0[..] {
+                [winner] => Some(winner),
+                _ => None,
+            }
+        }
+
+        /// Candidates sharing the highest vote count, with that count; empty if nobody
+        /// received a vote
+        fn compute_winner(&self, proposal_id: ProposalId, proposal: &Proposal) -> (Vec<CandidateId>, u128) {
+            if proposal.mode == VotingMode::Ranked {
+                let (_, leaders, votes) = self.instant_runoff(proposal_id, proposal);
+                return (leaders, votes);
+            }
+
+            let mut leaders: Vec<CandidateId> = Vec::new();
+            let mut best = 0;
+            for candidate_id in 0..proposal.candidate_count {
+                let votes = self.votes.get(&(proposal_id, candidate_id)).unwrap_or(0);
+                if votes > best {
+                    best = votes;
+                    leaders.clear();
+                    leaders.push(candidate_id);
+                } else if votes == best && votes > 0 {
+                    leaders.push(candidate_id);
+                }
+            }
+            (leaders, best)
+        }
+
+        /// Runs instant-runoff rounds until a candidate holds a strict majority of the
+        /// ballots still in the count. Each round eliminates the candidate with the
+        /// fewest votes (the highest index among equals); ballots whose ranked
+        /// candidates are all eliminated are exhausted. If every remaining candidate
+        /// is level the count ends in a tie. Returns the rounds and, as `compute_winner`,
+        /// the leading candidates with their votes.
+        fn instant_runoff(
+            &self,
+            proposal_id: ProposalId,
+            proposal: &Proposal,
+        ) -> (Vec<Vec<(CandidateId, u128)>>, Vec<CandidateId>, u128) {
+            let ballots = self.ranked_ballots.get(&proposal_id).unwrap_or_default();
+            let candidate_count = proposal.candidate_count as usize;
+            let mut active = vec![true; candidate_count];
+            let mut rounds: Vec<Vec<(CandidateId, u128)>> = Vec::new();
+
+            loop {
+                let mut tallies = vec![0u128; candidate_count];
+                for (ranking, weight) in &ballots {
+                    if let Some(choice) = ranking.iter().find(|index| active[**index as usize]) {
+                        tallies[*choice as usize] += weight;
+                    }
+                }
+
+                let round: Vec<(CandidateId, u128)> = (0..candidate_count)
+                    .filter(|index| active[*index])
+                    .map(|index| (index as CandidateId, tallies[index]))
+                    .collect();
+                let continuing: u128 = round.iter().map(|(_, votes)| votes).sum();
+                let best = round.iter().map(|(_, votes)| *votes).max().unwrap_or(0);
+                let worst = round.iter().map(|(_, votes)| *votes).min().unwrap_or(0);
+                rounds.push(round.clone());
+
+                if continuing == 0 {
+                    return (rounds, Vec::new(), 0);
+                }
+                if let Some((index, votes)) = round.iter().find(|(_, votes)| *votes * 2 > continuing) {
+                    return (rounds, vec![*index], *votes);
+                }
+                if best == worst {
+                    let tied = round.iter().map(|(index, _)| *index).collect();
+                    return (rounds, tied, best);
+                }
+
+                if let Some((eliminated, _)) = round.iter().rev().find(|(_, votes)| *votes == worst) {
+                    active[*eliminated as usize] = false;
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test::{self, DefaultAccounts};
+        use ink::env::DefaultEnvironment;
+
+        type Event = <Voting as ::ink::reflect::ContractEventBase>::Type;
+
+        const ENDS_AT: Timestamp = 1_000;
+
+        fn accounts() -> DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn set_caller(account: AccountId) {
+            test::set_caller::<DefaultEnvironment>(account);
+        }
+
+        fn set_time(timestamp: Timestamp) {
+            test::set_block_timestamp::<DefaultEnvironment>(timestamp);
+        }
+
+        fn names(candidates: &[&str]) -> Vec<String> {
+            candidates.iter().map(|name| String::from(*name)).collect()
+        }
+
+        /// Unweighted contract without quorum or margin, with one public proposal
+        /// on Alice, Bob and Carol running from 0 until `ENDS_AT`
+        fn setup() -> Voting {
+            set_caller(accounts().alice);
+            set_time(0);
+            let mut voting = Voting::new(None, 0, 0, false, false);
+            voting
+                .create_proposal(
+                    String::from("Board"),
+                    names(&["Alice", "Bob", "Carol"]),
+                    1,
+                    VotingMode::Public,
+                    0,
+                    ENDS_AT,
+                )
+                .expect("proposal is valid");
+            voting
+        }
+
+        fn vote_as(voting: &mut Voting, voter: AccountId, candidate_id: CandidateId) -> Result<(), VotingError> {
+            set_caller(voter);
+            voting.vote(0, candidate_id)
+        }
+
+        const VOTER_KEY: [u8; 32] = [9; 32];
+
+        /// Account whose public key is that of `VOTER_KEY`
+        fn signing_voter() -> AccountId {
+            let secret = secp256k1::SecretKey::from_slice(&VOTER_KEY).unwrap();
+            let public_key = secp256k1::PublicKey::from_secret_key(secp256k1::SECP256K1, &secret).serialize();
+            let mut account = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Blake2x256>(&public_key, &mut account);
+            AccountId::from(account)
+        }
+
+        /// 65-byte recoverable signature of `message` by `VOTER_KEY`
+        fn voter_sign(message: [u8; 32]) -> [u8; 65] {
+            let secret = secp256k1::SecretKey::from_slice(&VOTER_KEY).unwrap();
+            let message = secp256k1::Message::from_slice(&message).unwrap();
+            let (recovery_id, compact) = secp256k1::SECP256K1
+                .sign_ecdsa_recoverable(&message, &secret)
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            signature
+        }
+
+        fn decoded_events() -> Vec<Event> {
+            test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).expect("event decodes"))
+                .collect()
+        }
+
+        #[ink::test]
+        fn constructor_sets_up_an_empty_contract() {
+            set_caller(accounts().alice);
+            let voting = Voting::new(None, 5, 1_000, true, false);
+
+            assert_eq!(voting.get_admin(), accounts().alice);
+            assert_eq!(voting.get_proposal_count(), 0);
+            assert_eq!(voting.get_quorum(), (5, 1_000, true));
+            assert_eq!(voting.get_voting_token(), None);
+            assert_eq!(voting.get_proposal(0), None);
+        }
+
+        #[ink::test]
+        fn create_proposal_validates_its_input() {
+            set_caller(accounts().alice);
+            let mut voting = Voting::new(None, 0, 0, false, false);
+            let mut create = |candidates: &[&str], seats: u32, starts_at: Timestamp, ends_at: Timestamp| {
+                voting.create_proposal(String::new(), names(candidates), seats, VotingMode::Public, starts_at, ends_at)
+            };
+
+            assert_eq!(create(&["Alice"], 1, 0, 10), Err(VotingError::TooFewCandidates));
+            assert_eq!(create(&["Alice", " "], 1, 0, 10), Err(VotingError::InvalidCandidate));
+            assert_eq!(create(&["Alice", "alice "], 1, 0, 10), Err(VotingError::DuplicateCandidate));
+            assert_eq!(create(&["Alice", "Bob"], 3, 0, 10), Err(VotingError::InvalidSeats));
+            assert_eq!(create(&["Alice", "Bob"], 1, 10, 10), Err(VotingError::InvalidSchedule));
+            assert_eq!(create(&["Alice", "Bob"], 1, 0, 10), Ok(0));
+
+            set_caller(accounts().bob);
+            assert_eq!(
+                voting.create_proposal(String::new(), names(&["Alice", "Bob"]), 1, VotingMode::Public, 0, 10),
+                Err(VotingError::NotAdmin)
+            );
+        }
+
+        #[ink::test]
+        fn second_vote_is_rejected() {
+            let mut voting = setup();
+            let bob = accounts().bob;
+
+            assert_eq!(vote_as(&mut voting, bob, 0), Ok(()));
+            assert_eq!(vote_as(&mut voting, bob, 1), Err(VotingError::AlreadyVoted));
+            assert_eq!(voting.get_votes(0, 0), 1);
+            assert_eq!(voting.get_votes(0, 1), 0);
+        }
+
+        #[ink::test]
+        fn unknown_candidate_is_rejected() {
+            let mut voting = setup();
+
+            assert_eq!(vote_as(&mut voting, accounts().bob, 3), Err(VotingError::CandidateNotFound));
+            assert!(!voting.has_voted(0, accounts().bob));
+            assert_eq!(voting.get_candidate_id(0, String::from(" carol ")), Some(2));
+            assert_eq!(voting.get_candidate_id(0, String::from("Dave")), None);
+        }
+
+        #[ink::test]
+        fn tallies_are_kept_per_candidate() {
+            let mut voting = setup();
+            let accounts = accounts();
+
+            vote_as(&mut voting, accounts.bob, 0).unwrap();
+            vote_as(&mut voting, accounts.charlie, 2).unwrap();
+            vote_as(&mut voting, accounts.django, 2).unwrap();
+
+            assert_eq!(voting.get_votes(0, 0), 1);
+            assert_eq!(voting.get_votes(0, 1), 0);
+            assert_eq!(voting.get_votes(0, 2), 2);
+            assert_eq!(voting.get_turnout(0), (3, 0, 0));
+            assert_eq!(
+                voting.get_receipt(0, accounts.charlie).map(|receipt| (receipt.candidate_index, receipt.weight)),
+                Some((Some(2), 1))
+            );
+        }
+
+        #[ink::test]
+        fn voting_closes_at_the_deadline() {
+            let mut voting = setup();
+
+            set_time(ENDS_AT - 1);
+            assert_eq!(vote_as(&mut voting, accounts().bob, 0), Ok(()));
+            assert_eq!(voting.get_state(0), Ok(VotingState::Active));
+            assert_eq!(voting.get_winner(0), Err(VotingError::VotingStillActive));
+
+            set_time(ENDS_AT);
+            assert_eq!(vote_as(&mut voting, accounts().charlie, 0), Err(VotingError::VotingEnded));
+            assert_eq!(voting.get_state(0), Ok(VotingState::Ended));
+        }
+
+        #[ink::test]
+        fn winner_is_determined() {
+            let mut voting = setup();
+            let accounts = accounts();
+
+            vote_as(&mut voting, accounts.bob, 1).unwrap();
+            vote_as(&mut voting, accounts.charlie, 1).unwrap();
+            vote_as(&mut voting, accounts.django, 2).unwrap();
+            set_time(ENDS_AT);
+            assert_eq!(voting.get_winner(0), Ok(WinnerResult::Winner(String::from("Bob"), 2)));
+            assert_eq!(voting.winners(0), Ok(names(&["Bob"])));
+        }
+
+        #[ink::test]
+        fn tie_is_reported() {
+            let mut voting = setup();
+            let accounts = accounts();
+
+            vote_as(&mut voting, accounts.bob, 1).unwrap();
+            vote_as(&mut voting, accounts.charlie, 2).unwrap();
+            set_time(ENDS_AT);
+            assert_eq!(voting.get_winner(0), Ok(WinnerResult::Tie(names(&["Bob", "Carol"]), 1)));
+        }
+
+        #[ink::test]
+        fn proposal_without_votes_has_no_winner() {
+            let voting = setup();
+            set_time(ENDS_AT);
+            assert_eq!(voting.get_winner(0), Ok(WinnerResult::NoVotes));
+            assert_eq!(voting.winners(0), Ok(Vec::new()));
+        }
+
+        #[ink::test]
+        fn events_are_emitted() {
+            let mut voting = setup();
+            vote_as(&mut voting, accounts().bob, 2).unwrap();
+            set_time(ENDS_AT);
+            voting.finalize(0).unwrap();
+
+            let events = decoded_events();
+            assert_eq!(events.len(), 3);
+            match &events[0] {
+                Event::ProposalCreated(event) => {
+                    assert_eq!((event.proposal_id, event.candidate_count, event.seats), (0, 3, 1));
+                }
+                _ => panic!("expected ProposalCreated"),
+            }
+            match &events[1] {
+                Event::VoteCast(event) => {
+                    assert_eq!(event.voter, accounts().bob);
+                    assert_eq!((event.candidate_index, event.weight), (2, 1));
+                }
+                _ => panic!("expected VoteCast"),
+            }
+            match &events[2] {
+                Event::VotingEnded(event) => {
+                    assert_eq!((event.winner_index, event.total_votes), (Some(2), 1));
+                }
+                _ => panic!("expected VotingEnded"),
+            }
+        }
+
+        #[ink::test]
+        fn timeline_tracks_cumulative_votes_per_block() {
+            let mut voting = setup();
+            let accounts = accounts();
+            let start = ink::env::block_number::<DefaultEnvironment>();
+
+            // Two votes in the first block share one checkpoint
+            vote_as(&mut voting, accounts.bob, 1).unwrap();
+            vote_as(&mut voting, accounts.charlie, 1).unwrap();
+            test::advance_block::<DefaultEnvironment>();
+            vote_as(&mut voting, accounts.django, 1).unwrap();
+            vote_as(&mut voting, accounts.eve, 0).unwrap();
+            test::advance_block::<DefaultEnvironment>();
+            test::advance_block::<DefaultEnvironment>();
+            set_caller(accounts.charlie);
+            voting.change_vote(0, 0).unwrap();
+
+            assert_eq!(voting.vote_timeline(0, 1), vec![(start, 2), (start + 1, 3), (start + 3, 2)]);
+            assert_eq!(voting.vote_timeline(0, 0), vec![(start + 1, 1), (start + 3, 2)]);
+            assert_eq!(voting.vote_timeline(0, 2), Vec::new());
+        }
+
+        #[ink::test]
+        fn top_up_needs_a_token_weighted_vote() {
+            let mut voting = setup();
+            assert_eq!(voting.top_up_vote(0), Err(VotingError::WrongVotingMode));
+            assert_eq!(voting.top_up_vote(1), Err(VotingError::InvalidProposal));
+        }
+
+        #[ink::test]
+        fn results_are_sorted_with_ties_by_name_and_paginated() {
+            let mut voting = setup();
+            let proposal_id = voting
+                .create_proposal(String::from("Chair"), names(&["Zed", "Amy", "Max", "Bea"]), 1, VotingMode::Public, 0, ENDS_AT)
+                .unwrap();
+            // Accounts clear of the default test accounts
+            for (i, candidate_id) in [0, 0, 1, 2, 2].into_iter().enumerate() {
+                set_caller(AccountId::from([100 + i as u8; 32]));
+                voting.vote(proposal_id, candidate_id).unwrap();
+            }
+            set_caller(AccountId::from([110; 32]));
+            voting.vote_abstain(proposal_id).unwrap();
+
+            let sorted = vec![
+                (String::from("Max"), 2),
+                (String::from("Zed"), 2),
+                (String::from("Amy"), 1),
+                (String::from("Bea"), 0),
+            ];
+            assert_eq!(voting.get_results(proposal_id, 0, 10), sorted);
+            assert_eq!(voting.get_results(proposal_id, 0, 2), sorted[..2].to_vec());
+            assert_eq!(voting.get_results(proposal_id, 1, 2), sorted[1..3].to_vec());
+            assert_eq!(voting.get_results(proposal_id, 3, 10), sorted[3..].to_vec());
+            assert_eq!(voting.get_results(proposal_id, 4, 10), Vec::new());
+            assert_eq!(voting.get_results(proposal_id, 0, 0), Vec::new());
+            assert_eq!(voting.get_results(7, 0, 10), Vec::new());
+            assert_eq!(voting.get_turnout(proposal_id), (5, 1, 0));
+            assert_eq!(voting.get_turnout(0), (0, 0, 0));
+
+            set_caller(accounts().alice);
+            let crowded: Vec<String> = (0..MAX_RESULTS_PAGE + 10).map(|i| format!("Candidate {i}")).collect();
+            let crowded_id = voting
+                .create_proposal(String::from("Crowded"), crowded, 1, VotingMode::Public, 0, ENDS_AT)
+                .unwrap();
+            assert_eq!(voting.get_results(crowded_id, 0, u32::MAX).len(), MAX_RESULTS_PAGE as usize);
+            assert_eq!(voting.get_results(crowded_id, MAX_RESULTS_PAGE, u32::MAX).len(), 10);
+        }
+
+        #[ink::test]
+        fn instant_runoff_transfers_overturn_the_plurality_leader() {
+            let mut voting = setup();
+            let candidates = names(&["Alice", "Bob", "Carol", "Dave"]);
+            assert_eq!(
+                voting.create_proposal(String::from("Chair"), candidates.clone(), 2, VotingMode::Ranked, 0, ENDS_AT),
+                Err(VotingError::InvalidSeats)
+            );
+            let proposal_id = voting
+                .create_proposal(String::from("Chair"), candidates, 1, VotingMode::Ranked, 0, ENDS_AT)
+                .unwrap();
+
+            set_caller(accounts().bob);
+            assert_eq!(voting.vote(proposal_id, 0), Err(VotingError::WrongVotingMode));
+            for invalid in [vec![], vec![1, 1], vec![0, 4]] {
+                assert_eq!(voting.vote_ranked(proposal_id, invalid), Err(VotingError::InvalidRanking));
+            }
+
+            // Alice leads on first preferences, but Carol's voters prefer Bob to her and
+            // the Dave-only ballot exhausts once Dave is out
+            let mut ballots = vec![vec![0]; 4];
+            ballots.extend(vec![vec![1, 2]; 3]);
+            ballots.extend(vec![vec![2, 1]; 2]);
+            ballots.push(vec![3]);
+            // Accounts clear of the default test accounts
+            for (i, ranking) in ballots.into_iter().enumerate() {
+                set_caller(AccountId::from([100 + i as u8; 32]));
+                voting.vote_ranked(proposal_id, ranking).unwrap();
+            }
+            assert_eq!(voting.get_round_count(proposal_id), 0);
+
+            set_time(ENDS_AT);
+            voting.finalize(proposal_id).unwrap();
+            assert_eq!(voting.get_round_count(proposal_id), 3);
+            assert_eq!(voting.get_round_results(proposal_id, 0), vec![(0, 4), (1, 3), (2, 2), (3, 1)]);
+            assert_eq!(voting.get_round_results(proposal_id, 1), vec![(0, 4), (1, 3), (2, 2)]);
+            assert_eq!(voting.get_round_results(proposal_id, 2), vec![(0, 4), (1, 5)]);
+            assert_eq!(voting.get_round_results(proposal_id, 3), Vec::new());
+            assert_eq!(voting.get_winner(proposal_id), Ok(WinnerResult::Winner(String::from("Bob"), 5)));
+            assert_eq!(voting.winners(proposal_id), Ok(vec![String::from("Bob")]));
+        }
+
+        #[ink::test]
+        fn abstentions_meet_quorum_only_when_configured_to() {
+            let accounts = accounts();
+            // Contracts in one test share storage, so each round uses its own voters
+            for (abstain_counts_for_quorum, first, second) in
+                [(true, accounts.bob, accounts.charlie), (false, accounts.django, accounts.eve)]
+            {
+                set_caller(accounts.alice);
+                set_time(0);
+                let mut voting = Voting::new(None, 2, 0, abstain_counts_for_quorum, false);
+                voting
+                    .create_proposal(String::from("Board"), names(&["Alice", "Bob"]), 1, VotingMode::Public, 0, ENDS_AT)
+                    .unwrap();
+
+                set_caller(first);
+                voting.vote_abstain(0).unwrap();
+                assert_eq!(voting.vote_abstain(0), Err(VotingError::AlreadyVoted));
+                set_caller(second);
+                voting.vote(0, 1).unwrap();
+                assert_eq!(voting.get_turnout(0), (1, 1, 0));
+                // Switching to an abstention follows the change-vote rules
+                voting.change_vote_to_abstain(0).unwrap();
+                assert_eq!(voting.get_turnout(0), (0, 2, 0));
+                assert_eq!(voting.get_abstain_count(0), 2);
+                assert_eq!(voting.get_votes(0, 1), 0);
+
+                set_time(ENDS_AT);
+                if abstain_counts_for_quorum {
+                    assert_eq!(voting.get_winner(0), Ok(WinnerResult::NoVotes));
+                    assert_eq!(voting.get_state(0), Ok(VotingState::Ended));
+                } else {
+                    assert_eq!(voting.get_winner(0), Err(VotingError::QuorumNotReached));
+                    assert_eq!(voting.get_state(0), Ok(VotingState::Failed));
+                }
+            }
+        }
+
+        #[ink::test]
+        fn quadratic_proposals_cannot_be_abstained_on() {
+            let accounts = accounts();
+            set_caller(accounts.alice);
+            set_time(0);
+            let mut voting = Voting::new(Some(AccountId::from([9; 32])), 0, 0, true, false);
+            voting
+                .create_proposal(
+                    String::from("Budget"),
+                    names(&["Parks", "Roads"]),
+                    1,
+                    VotingMode::Quadratic { max_votes: 10 },
+                    0,
+                    ENDS_AT,
+                )
+                .unwrap();
+
+            // An abstention would weigh the stake linearly instead of charging votes^2
+            set_caller(accounts.bob);
+            assert_eq!(voting.vote_abstain(0), Err(VotingError::WrongVotingMode));
+            assert_eq!(voting.get_abstain_count(0), 0);
+            assert_eq!(voting.get_turnout(0), (0, 0, 0));
+        }
+
+        #[ink::test]
+        fn quorum_and_margin_decide_whether_a_proposal_passes() {
+            fn cast(voting: &mut Voting, proposal_id: ProposalId, ballots: &[CandidateId]) {
+                // Accounts clear of the default test accounts
+                for (i, candidate_id) in ballots.iter().enumerate() {
+                    set_caller(AccountId::from([100 + i as u8; 32]));
+                    voting.vote(proposal_id, *candidate_id).unwrap();
+                }
+            }
+
+            // Quorum of 3 votes and a winning margin of 20% of the votes cast
+            set_caller(accounts().alice);
+            set_time(0);
+            let mut voting = Voting::new(None, 3, 2_000, false, false);
+            for _ in 0..4 {
+                voting
+                    .create_proposal(String::from("Board"), names(&["Alice", "Bob"]), 1, VotingMode::Public, 0, ENDS_AT)
+                    .unwrap();
+            }
+            cast(&mut voting, 0, &[0, 0]);
+            cast(&mut voting, 1, &[0, 0, 0]);
+            cast(&mut voting, 2, &[0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1]);
+            cast(&mut voting, 3, &[0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
+
+            set_time(ENDS_AT);
+            assert_eq!(voting.get_winner(0), Err(VotingError::QuorumNotReached));
+            assert_eq!(voting.get_state(0), Ok(VotingState::Failed));
+            assert_eq!(voting.get_winner(1), Ok(WinnerResult::Winner(String::from("Alice"), 3)));
+            assert_eq!(voting.get_state(1), Ok(VotingState::Ended));
+            // A lead of 1 out of 11 is short of the 2 required; 2 out of 10 is just enough
+            assert_eq!(voting.get_winner(2), Err(VotingError::MarginNotReached));
+            assert_eq!(voting.get_state(2), Ok(VotingState::Failed));
+            assert_eq!(voting.get_winner(3), Ok(WinnerResult::Winner(String::from("Alice"), 6)));
+
+            for proposal_id in 0..4 {
+                voting.finalize(proposal_id).unwrap();
+            }
+            let winners: Vec<Option<u32>> = decoded_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::VotingEnded(event) => Some(event.winner_index),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(winners, vec![None, Some(0), None, Some(0)]);
+        }
+
+        #[ink::test]
+        fn two_seats_break_the_cutoff_tie_by_name() {
+            let mut voting = setup();
+            let council = voting
+                .create_proposal(String::from("Council"), names(&["Dan", "Eve", "Cat", "Ann"]), 2, VotingMode::Public, 0, ENDS_AT)
+                .unwrap();
+            let runoff = voting
+                .create_proposal(String::from("Runoff"), names(&["Dan", "Eve", "Cat", "Ann"]), 2, VotingMode::Public, 0, ENDS_AT)
+                .unwrap();
+            // Accounts clear of the default test accounts; Eve and Cat tie for the second seat
+            for (i, candidate_id) in [0, 0, 0, 1, 2].into_iter().enumerate() {
+                set_caller(AccountId::from([100 + i as u8; 32]));
+                voting.vote(council, candidate_id).unwrap();
+            }
+            set_caller(AccountId::from([110; 32]));
+            voting.vote(runoff, 1).unwrap();
+            assert_eq!(voting.winners(council), Err(VotingError::VotingStillActive));
+
+            set_time(ENDS_AT);
+            assert_eq!(voting.winners(council), Ok(vec![String::from("Dan"), String::from("Cat")]));
+            // Seats stay empty rather than go to candidates without votes
+            assert_eq!(voting.winners(runoff), Ok(vec![String::from("Eve")]));
+
+            // Quorum applies to the whole turnout, not per seat
+            set_caller(accounts().alice);
+            set_time(0);
+            let mut voting = Voting::new(None, 6, 0, false, false);
+            voting
+                .create_proposal(String::from("Council"), names(&["Dan", "Eve", "Cat", "Ann"]), 2, VotingMode::Public, 0, ENDS_AT)
+                .unwrap();
+            for (i, candidate_id) in [0, 0, 0, 1, 2].into_iter().enumerate() {
+                vote_as(&mut voting, AccountId::from([100 + i as u8; 32]), candidate_id).unwrap();
+            }
+            set_time(ENDS_AT);
+            assert_eq!(voting.winners(0), Err(VotingError::QuorumNotReached));
+        }
+
+        #[ink::test]
+        fn votes_are_changed_and_revoked_until_the_deadline() {
+            let mut voting = setup();
+            let accounts = accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(voting.change_vote(0, 1), Err(VotingError::NotVoted));
+            assert_eq!(voting.revoke_vote(0), Err(VotingError::NotVoted));
+            vote_as(&mut voting, accounts.bob, 0).unwrap();
+            vote_as(&mut voting, accounts.charlie, 0).unwrap();
+
+            set_caller(accounts.bob);
+            assert_eq!(voting.change_vote(0, 9), Err(VotingError::CandidateNotFound));
+            voting.change_vote(0, 2).unwrap();
+            assert_eq!((voting.get_votes(0, 0), voting.get_votes(0, 2)), (1, 1));
+
+            set_caller(accounts.charlie);
+            voting.revoke_vote(0).unwrap();
+            assert!(!voting.has_voted(0, accounts.charlie));
+            assert_eq!(voting.get_votes(0, 0), 0);
+            assert_eq!(voting.get_turnout(0), (1, 0, 0));
+            voting.vote(0, 1).unwrap();
+            assert_eq!(
+                (voting.get_votes(0, 0), voting.get_votes(0, 1), voting.get_votes(0, 2)),
+                (0, 1, 1)
+            );
+            assert_eq!(voting.get_turnout(0), (2, 0, 0));
+
+            set_time(ENDS_AT);
+            assert_eq!(voting.change_vote(0, 2), Err(VotingError::VotingEnded));
+            assert_eq!(voting.revoke_vote(0), Err(VotingError::VotingEnded));
+            assert_eq!(voting.get_votes(0, 1), 1);
+
+            let changes: Vec<(AccountId, Option<CandidateId>, Option<CandidateId>)> = decoded_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::VoteChanged(event) => Some((event.voter, event.old_candidate_index, event.new_candidate_index)),
+                    Event::VoteRevoked(event) => Some((event.voter, event.candidate_index, None)),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(
+                changes,
+                vec![(accounts.bob, Some(0), Some(2)), (accounts.charlie, Some(0), None)]
+            );
+        }
+
+        #[ink::test]
+        fn relayed_votes_are_bound_to_voter_nonce_candidate_and_contract() {
+            let mut voting = setup();
+            let voter = signing_voter();
+            let second = voting
+                .create_proposal(String::from("Treasurer"), names(&["Alice", "Bob", "Carol"]), 1, VotingMode::Public, 0, ENDS_AT)
+                .unwrap();
+
+            set_caller(accounts().charlie);
+            let signature = voter_sign(voting.get_vote_signature_hash(0, 1, 0));
+            voting.vote_by_signature(voter, 0, 1, 0, signature).unwrap();
+            assert_eq!(voting.get_votes(0, 1), 1);
+            assert!(voting.has_voted(0, voter));
+            assert!(!voting.has_voted(0, accounts().charlie));
+            assert_eq!(voting.get_signature_nonce(voter), 1);
+            assert_eq!(voting.vote_by_signature(voter, 0, 1, 0, signature), Err(VotingError::InvalidNonce));
+
+            // A relayer cannot redirect the vote
+            let signature = voter_sign(voting.get_vote_signature_hash(second, 1, 1));
+            assert_eq!(voting.vote_by_signature(voter, second, 2, 1, signature), Err(VotingError::InvalidSignature));
+
+            // Nor replay a signature made for another instance of the contract
+            let contract = ink::env::account_id::<DefaultEnvironment>();
+            test::set_callee::<DefaultEnvironment>(AccountId::from([0xEE; 32]));
+            let foreign = voter_sign(voting.get_vote_signature_hash(second, 1, 1));
+            test::set_callee::<DefaultEnvironment>(contract);
+            assert_eq!(voting.vote_by_signature(voter, second, 1, 1, foreign), Err(VotingError::InvalidSignature));
+
+            voting.vote_by_signature(voter, second, 1, 1, signature).unwrap();
+            assert_eq!(voting.get_votes(second, 1), 1);
+            assert_eq!(voting.get_signature_nonce(voter), 2);
+        }
+
+        #[ink::test]
+        fn receipts_follow_votes_changes_and_revocations() {
+            let mut voting = setup();
+            let accounts = accounts();
+            let voters = [accounts.bob, accounts.charlie, accounts.django, accounts.eve];
+
+            set_caller(accounts.django);
+            voting.delegate(0, accounts.eve).unwrap();
+            vote_as(&mut voting, accounts.bob, 0).unwrap();
+            vote_as(&mut voting, accounts.charlie, 1).unwrap();
+            vote_as(&mut voting, accounts.eve, 2).unwrap();
+            test::advance_block::<DefaultEnvironment>();
+            set_caller(accounts.bob);
+            voting.change_vote(0, 2).unwrap();
+            set_caller(accounts.charlie);
+            voting.revoke_vote(0).unwrap();
+
+            assert_eq!(
+                voting.get_receipt(0, accounts.bob),
+                Some(Receipt { candidate_index: Some(2), weight: 1, block: 1 })
+            );
+            assert_eq!(voting.get_receipt(0, accounts.charlie), None);
+            assert_eq!(voting.get_receipt(0, accounts.django), None);
+            assert_eq!(
+                voting.get_receipt(0, accounts.eve),
+                Some(Receipt { candidate_index: Some(2), weight: 2, block: 0 })
+            );
+            for candidate_id in 0..3 {
+                let receipted: u128 = voters
+                    .iter()
+                    .filter_map(|voter| voting.get_receipt(0, *voter))
+                    .filter(|receipt| receipt.candidate_index == Some(candidate_id))
+                    .map(|receipt| receipt.weight)
+                    .sum();
+                assert_eq!(receipted, voting.get_votes(0, candidate_id));
+            }
+
+            // Commit-reveal ballots get their receipt when revealed
+            set_caller(accounts.alice);
+            let secret = voting
+                .create_proposal(
+                    String::from("Secret"),
+                    names(&["Alice", "Bob"]),
+                    1,
+                    VotingMode::CommitReveal { commit_ends_at: 400 },
+                    0,
+                    ENDS_AT,
+                )
+                .unwrap();
+            set_caller(accounts.bob);
+            voting
+                .commit_vote(secret, voting.get_commitment_hash(1, [7; 32], accounts.bob))
+                .unwrap();
+            assert_eq!(voting.get_receipt(secret, accounts.bob), None);
+            set_time(400);
+            voting.reveal_vote(secret, 1, [7; 32]).unwrap();
+            assert_eq!(
+                voting.get_receipt(secret, accounts.bob),
+                Some(Receipt { candidate_index: Some(1), weight: 1, block: 1 })
+            );
+        }
+
+        #[ink::test]
+        fn overlapping_proposals_keep_separate_tallies_and_vote_locks() {
+            let mut voting = setup();
+            let accounts = accounts();
+            let referendum = voting
+                .create_proposal(String::from("Referendum"), names(&["Yes", "No"]), 1, VotingMode::Public, 500, 1_500)
+                .unwrap();
+            assert_eq!(voting.get_proposal_count(), 2);
+
+            vote_as(&mut voting, accounts.bob, 1).unwrap();
+            set_caller(accounts.bob);
+            assert_eq!(voting.vote(referendum, 0), Err(VotingError::VotingNotStarted));
+
+            set_time(500);
+            voting.vote(referendum, 0).unwrap();
+            assert_eq!(voting.vote(0, 2), Err(VotingError::AlreadyVoted));
+            assert_eq!(voting.vote(referendum, 1), Err(VotingError::AlreadyVoted));
+            assert_eq!(voting.vote(7, 0), Err(VotingError::InvalidProposal));
+            set_caller(accounts.charlie);
+            voting.vote(referendum, 0).unwrap();
+
+            assert_eq!(voting.get_votes(0, 1), 1);
+            assert_eq!(voting.get_votes(referendum, 0), 2);
+            assert_eq!(voting.get_votes(referendum, 1), 0);
+            assert!(voting.has_voted(0, accounts.bob));
+            assert!(!voting.has_voted(0, accounts.charlie));
+            assert!(voting.has_voted(referendum, accounts.charlie));
+
+            // The board vote closes while the referendum keeps running
+            set_time(ENDS_AT);
+            assert_eq!(voting.vote(0, 1), Err(VotingError::VotingEnded));
+            assert_eq!(voting.get_winner(0), Ok(WinnerResult::Winner(String::from("Bob"), 1)));
+            assert_eq!(voting.get_winner(referendum), Err(VotingError::VotingStillActive));
+            set_caller(accounts.django);
+            voting.vote(referendum, 1).unwrap();
+            assert_eq!(voting.get_state(0), Ok(VotingState::Ended));
+            assert_eq!(voting.get_state(referendum), Ok(VotingState::Active));
+        }
+
+        #[ink::test]
+        fn candidates_are_edited_until_the_first_vote() {
+            let mut voting = setup();
+            let accounts = accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(voting.add_candidate(0, String::from("Dave")), Err(VotingError::NotAdmin));
+            set_caller(accounts.alice);
+            assert_eq!(voting.add_candidate(0, String::from("  bob ")), Err(VotingError::DuplicateCandidate));
+            assert_eq!(voting.add_candidate(0, String::from("  ")), Err(VotingError::InvalidCandidate));
+            voting.add_candidate(0, String::from(" Dave ")).unwrap();
+            assert_eq!(voting.remove_candidate(0, 7), Err(VotingError::CandidateNotFound));
+            voting.remove_candidate(0, 0).unwrap();
+            assert_eq!(
+                voting.get_candidates(0),
+                vec![(0, String::from("Bob")), (1, String::from("Carol")), (2, String::from("Dave"))]
+            );
+
+            vote_as(&mut voting, accounts.bob, 2).unwrap();
+            set_caller(accounts.alice);
+            assert_eq!(voting.add_candidate(0, String::from("Erin")), Err(VotingError::VotingStarted));
+            assert_eq!(voting.remove_candidate(0, 1), Err(VotingError::VotingStarted));
+            assert_eq!(voting.get_candidates(0).len(), 3);
+            assert_eq!(voting.get_votes(0, 2), 1);
+
+            let edits: Vec<(bool, u32)> = decoded_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::CandidateAdded(event) => Some((true, event.index)),
+                    Event::CandidateRemoved(event) => Some((false, event.index)),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(edits, vec![(true, 3), (false, 0)]);
+        }
+
+        #[ink::test]
+        fn delegation_is_single_level_and_closes_with_the_vote() {
+            let mut voting = setup();
+            let accounts = accounts();
+
+            set_caller(accounts.charlie);
+            assert_eq!(voting.delegate(0, accounts.charlie), Err(VotingError::SelfDelegation));
+            voting.delegate(0, accounts.bob).unwrap();
+            assert_eq!(voting.delegate(0, accounts.eve), Err(VotingError::AlreadyDelegated));
+
+            // Chains are rejected from either end, and so is closing a cycle
+            set_caller(accounts.django);
+            assert_eq!(voting.delegate(0, accounts.charlie), Err(VotingError::ChainedDelegation));
+            set_caller(accounts.bob);
+            assert_eq!(voting.delegate(0, accounts.eve), Err(VotingError::ChainedDelegation));
+            assert_eq!(voting.delegate(0, accounts.charlie), Err(VotingError::ChainedDelegation));
+
+            set_caller(accounts.django);
+            voting.delegate(0, accounts.frank).unwrap();
+            voting.undelegate(0).unwrap();
+            assert_eq!(voting.undelegate(0), Err(VotingError::NotDelegated));
+            assert_eq!(voting.get_delegators(0, accounts.frank), Vec::new());
+            assert_eq!(voting.get_delegators(0, accounts.bob), vec![accounts.charlie]);
+
+            assert_eq!(vote_as(&mut voting, accounts.charlie, 0), Err(VotingError::VoteDelegated));
+            vote_as(&mut voting, accounts.bob, 1).unwrap();
+            assert_eq!(voting.get_votes(0, 1), 2);
+            assert_eq!(voting.get_receipt(0, accounts.bob).map(|receipt| receipt.weight), Some(2));
+
+            // Once the delegate voted nobody can delegate to it or take power back,
+            // and voters cannot hand their vote on
+            set_caller(accounts.django);
+            assert_eq!(voting.delegate(0, accounts.bob), Err(VotingError::DelegateAlreadyVoted));
+            set_caller(accounts.charlie);
+            assert_eq!(voting.undelegate(0), Err(VotingError::DelegateAlreadyVoted));
+            vote_as(&mut voting, accounts.eve, 2).unwrap();
+            assert_eq!(voting.delegate(0, accounts.frank), Err(VotingError::AlreadyVoted));
+
+            let delegations: Vec<(bool, AccountId, AccountId)> = decoded_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::Delegated(event) => Some((true, event.delegator, event.delegate)),
+                    Event::Undelegated(event) => Some((false, event.delegator, event.delegate)),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(
+                delegations,
+                vec![
+                    (true, accounts.charlie, accounts.bob),
+                    (true, accounts.django, accounts.frank),
+                    (false, accounts.django, accounts.frank),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn only_registered_voters_vote_on_whitelisted_proposals() {
+            let mut voting = setup();
+            let accounts = accounts();
+
+            assert_eq!(voting.register_voters(0, Vec::new()), Err(VotingError::EmptyBatch));
+            // Accounts clear of the default test accounts
+            let too_many: Vec<AccountId> =
+                (0..=MAX_REGISTRATION_BATCH).map(|i| AccountId::from([100 + i as u8; 32])).collect();
+            assert_eq!(voting.register_voters(0, too_many.clone()), Err(VotingError::BatchTooLarge));
+            assert_eq!(voting.get_proposal(0).map(|proposal| proposal.whitelist), Some(false));
+            voting.register_voters(0, too_many[..MAX_REGISTRATION_BATCH as usize].to_vec()).unwrap();
+            assert_eq!(voting.get_eligible_count(0), MAX_REGISTRATION_BATCH);
+
+            // Registering again only counts the new accounts
+            voting.register_voters(0, vec![accounts.bob, accounts.charlie, accounts.bob]).unwrap();
+            assert_eq!(voting.get_eligible_count(0), MAX_REGISTRATION_BATCH + 2);
+            set_caller(accounts.bob);
+            assert_eq!(voting.register_voters(0, vec![accounts.eve]), Err(VotingError::NotAdmin));
+            set_caller(accounts.alice);
+            voting.deregister_voter(0, accounts.charlie).unwrap();
+            assert_eq!(voting.deregister_voter(0, accounts.charlie), Err(VotingError::NotEligible));
+            assert!(!voting.is_registered(0, accounts.charlie));
+
+            assert_eq!(vote_as(&mut voting, accounts.django, 0), Err(VotingError::NotEligible));
+            assert_eq!(vote_as(&mut voting, accounts.charlie, 0), Err(VotingError::NotEligible));
+            assert_eq!(vote_as(&mut voting, accounts.bob, 0), Ok(()));
+            assert_eq!(voting.get_turnout(0), (1, 0, MAX_REGISTRATION_BATCH + 1));
+
+            // The whitelist is frozen by the first vote
+            set_caller(accounts.alice);
+            assert_eq!(voting.register_voters(0, vec![accounts.django]), Err(VotingError::VotingStarted));
+            assert_eq!(voting.deregister_voter(0, accounts.bob), Err(VotingError::VotingStarted));
+        }
+
+        #[ink::test]
+        fn pauses_extend_the_deadline_only_when_configured_to() {
+            let accounts = accounts();
+            // Contracts in one test share storage, so each round uses its own voter
+            for (extend_on_pause, voter) in [(true, accounts.bob), (false, accounts.charlie)] {
+                set_caller(accounts.alice);
+                set_time(0);
+                let mut voting = Voting::new(None, 0, 0, false, extend_on_pause);
+                voting
+                    .create_proposal(String::from("Board"), names(&["Alice", "Bob"]), 1, VotingMode::Public, 0, ENDS_AT)
+                    .unwrap();
+
+                set_time(100);
+                set_caller(voter);
+                assert_eq!(voting.pause_voting(0), Err(VotingError::NotAdmin));
+                set_caller(accounts.alice);
+                voting.pause_voting(0).unwrap();
+                assert_eq!(voting.get_state(0), Ok(VotingState::Paused));
+                assert_eq!(vote_as(&mut voting, voter, 0), Err(VotingError::Paused));
+
+                set_time(300);
+                set_caller(accounts.alice);
+                voting.resume_voting(0).unwrap();
+                assert_eq!(voting.resume_voting(0), Err(VotingError::NotPaused));
+                let ends_at = if extend_on_pause { ENDS_AT + 200 } else { ENDS_AT };
+                assert_eq!(voting.get_proposal(0).map(|proposal| proposal.ends_at), Some(ends_at));
+
+                set_time(ENDS_AT + 100);
+                if extend_on_pause {
+                    vote_as(&mut voting, voter, 0).unwrap();
+                } else {
+                    assert_eq!(vote_as(&mut voting, voter, 0), Err(VotingError::VotingEnded));
+                }
+            }
+
+            let resumed: Vec<Timestamp> = decoded_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::VotingResumed(event) => Some(event.ends_at),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(resumed, vec![ENDS_AT + 200, ENDS_AT]);
+        }
+
+        #[ink::test]
+        fn cancelled_proposals_accept_nothing_but_withdrawals() {
+            let mut voting = setup();
+            let accounts = accounts();
+            vote_as(&mut voting, accounts.bob, 0).unwrap();
+
+            set_caller(accounts.alice);
+            voting.pause_voting(0).unwrap();
+            voting.cancel_proposal(0).unwrap();
+            assert_eq!(voting.cancel_proposal(0), Err(VotingError::ProposalCancelled));
+            assert_eq!(voting.resume_voting(0), Err(VotingError::ProposalCancelled));
+            assert_eq!(voting.get_state(0), Ok(VotingState::Cancelled));
+            assert_eq!(vote_as(&mut voting, accounts.charlie, 0), Err(VotingError::ProposalCancelled));
+            // Withdrawals open before the deadline; an unweighted vote has nothing locked
+            assert_eq!(voting.withdraw_locked(0), Err(VotingError::NothingLocked));
+
+            set_time(ENDS_AT);
+            set_caller(accounts.alice);
+            assert_eq!(voting.finalize(0), Err(VotingError::ProposalCancelled));
+            assert_eq!(voting.get_winner(0), Err(VotingError::ProposalCancelled));
+            assert!(decoded_events()
+                .into_iter()
+                .any(|event| matches!(event, Event::ProposalCancelled(ProposalCancelled { proposal_id: 0 }))));
+        }
+
+        #[ink::test]
+        fn commit_reveal_counts_only_revealed_ballots_after_the_deadline() {
+            let mut voting = setup();
+            let accounts = accounts();
+            let proposal_id = voting
+                .create_proposal(
+                    String::from("Secret"),
+                    names(&["Alice", "Bob"]),
+                    1,
+                    VotingMode::CommitReveal { commit_ends_at: 400 },
+                    0,
+                    ENDS_AT,
+                )
+                .unwrap();
+            let (first_salt, second_salt) = ([7; 32], [8; 32]);
+
+            // Bob re-commits before the commit deadline; Django never reveals
+            set_caller(accounts.bob);
+            assert_eq!(voting.vote(proposal_id, 0), Err(VotingError::WrongVotingMode));
+            voting
+                .commit_vote(proposal_id, voting.get_commitment_hash(0, first_salt, accounts.bob))
+                .unwrap();
+            voting
+                .commit_vote(proposal_id, voting.get_commitment_hash(1, second_salt, accounts.bob))
+                .unwrap();
+            assert_eq!(voting.reveal_vote(proposal_id, 1, second_salt), Err(VotingError::RevealPhaseNotStarted));
+            set_caller(accounts.charlie);
+            voting
+                .commit_vote(proposal_id, voting.get_commitment_hash(1, first_salt, accounts.charlie))
+                .unwrap();
+            set_caller(accounts.django);
+            voting
+                .commit_vote(proposal_id, voting.get_commitment_hash(0, first_salt, accounts.django))
+                .unwrap();
+
+            set_time(400);
+            assert_eq!(
+                voting.commit_vote(proposal_id, voting.get_commitment_hash(0, second_salt, accounts.django)),
+                Err(VotingError::CommitPhaseEnded)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(voting.reveal_vote(proposal_id, 0, first_salt), Err(VotingError::InvalidReveal));
+            assert_eq!(voting.reveal_vote(proposal_id, 1, first_salt), Err(VotingError::InvalidReveal));
+            voting.reveal_vote(proposal_id, 1, second_salt).unwrap();
+            assert_eq!(voting.reveal_vote(proposal_id, 1, second_salt), Err(VotingError::NoCommitment));
+            set_caller(accounts.charlie);
+            voting.reveal_vote(proposal_id, 1, first_salt).unwrap();
+            set_caller(accounts.eve);
+            assert_eq!(voting.reveal_vote(proposal_id, 0, first_salt), Err(VotingError::NoCommitment));
+
+            // Revealed votes stay hidden while the reveal phase runs
+            assert_eq!(voting.get_votes(proposal_id, 1), 0);
+            assert_eq!(voting.get_results(proposal_id, 0, 10), Vec::new());
+            assert_eq!(voting.vote_timeline(proposal_id, 1), Vec::new());
+            assert_eq!(voting.get_winner(proposal_id), Err(VotingError::VotingStillActive));
+
+            set_time(ENDS_AT);
+            set_caller(accounts.django);
+            assert_eq!(voting.reveal_vote(proposal_id, 0, first_salt), Err(VotingError::VotingEnded));
+            assert_eq!(voting.get_votes(proposal_id, 0), 0);
+            assert_eq!(voting.get_votes(proposal_id, 1), 2);
+            assert_eq!(voting.get_turnout(proposal_id), (2, 0, 0));
+            assert!(!voting.has_voted(proposal_id, accounts.django));
+            assert_eq!(voting.get_winner(proposal_id), Ok(WinnerResult::Winner(String::from("Bob"), 2)));
+        }
+
+        #[ink::test]
+        fn randomized_tally_matches_accepted_votes() {
+            const VOTERS: u8 = 200;
+            let mut voting = setup();
+            // Linear congruential generator, so the sequence is the same on every run
+            let mut seed: u32 = 0x5eed;
+            let mut accepted = 0u128;
+
+            for voter in 0..VOTERS {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                // One in five choices is out of range and must be rejected
+                let candidate_id = (seed >> 16) % 5 * 3 / 4;
+                let account = AccountId::from([voter; 32]);
+                match vote_as(&mut voting, account, candidate_id) {
+                    Ok(()) => {
+                        accepted += 1;
+                        // Repeat voters are always turned away
+                        if voter % 7 == 0 {
+                            assert_eq!(vote_as(&mut voting, account, 0), Err(VotingError::AlreadyVoted));
+                        }
+                    }
+                    Err(error) => assert_eq!(error, VotingError::CandidateNotFound),
+                }
+            }
+
+            let tally: u128 = (0..3).map(|candidate_id| voting.get_votes(0, candidate_id)).sum();
+            assert_eq!(tally, accepted);
+            assert_eq!(voting.get_proposal(0).map(|proposal| proposal.total_votes), Some(accepted));
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+        use mock_psp22::MockPsp22Ref;
+        use psp22_traits::PSP22;
+        use scale::Encode;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        /// Timestamp `millis` ahead of the wall clock, which the node's blocks follow
+        fn deadline_in(millis: Timestamp) -> Timestamp {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("clock is past the epoch");
+            now.as_millis() as Timestamp + millis
+        }
+
+        /// Waits until `proposal_id` is no longer active. The node only moves its clock
+        /// when it seals a block, so every round seals one with a balance transfer.
+        async fn wait_for_end(
+            client: &mut ink_e2e::Client<ink_e2e::PolkadotConfig, ink::env::DefaultEnvironment>,
+            contract: &AccountId,
+            proposal_id: ProposalId,
+        ) {
+            loop {
+                let get_state = build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_state(proposal_id));
+                if client.call_dry_run(&ink_e2e::alice(), &get_state, 0, None).await.return_value() != Ok(VotingState::Active) {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                client.create_and_fund_account(&ink_e2e::alice(), 1_000_000_000_000).await;
+            }
+        }
+
+        #[ink_e2e::test]
+        async fn three_signers_vote_and_results_are_queried(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let constructor = VotingRef::new(None, 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Board"),
+                    vec![String::from("Alice"), String::from("Bob")],
+                    1,
+                    VotingMode::Public,
+                    0,
+                    Timestamp::MAX,
+                )
+            });
+            client
+                .call(&ink_e2e::alice(), create, 0, None)
+                .await
+                .expect("create_proposal failed");
+
+            for (signer, candidate_id) in [(ink_e2e::alice(), 0), (ink_e2e::bob(), 1), (ink_e2e::charlie(), 1)] {
+                let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, candidate_id));
+                client.call(&signer, vote, 0, None).await.expect("vote failed");
+            }
+
+            let mut tally = Vec::new();
+            for candidate_id in 0..2 {
+                let get_votes =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_votes(0, candidate_id));
+                tally.push(client.call_dry_run(&ink_e2e::alice(), &get_votes, 0, None).await.return_value());
+            }
+            assert_eq!(tally, vec![1, 2]);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn token_balances_weigh_the_outcome(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(Some(token.clone()), 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+            let dave = ink_e2e::account_id(ink_e2e::AccountKeyring::Dave);
+
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Board"),
+                    vec![String::from("Alice"), String::from("Bob")],
+                    1,
+                    VotingMode::Public,
+                    0,
+                    Timestamp::MAX,
+                )
+            });
+            client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+
+            for signer in [ink_e2e::bob(), ink_e2e::charlie(), ink_e2e::dave(), ink_e2e::eve()] {
+                let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(contract.clone(), 10_000));
+                client.call(&signer, approve, 0, None).await.expect("approve failed");
+            }
+            for (account, amount) in [(bob.clone(), 100), (charlie.clone(), 250), (dave.clone(), 400)] {
+                let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(account.clone(), amount, Vec::new()));
+                client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+            }
+
+            // Two small holders lose to one large holder, and Eve holds nothing at all
+            for (signer, candidate_id) in [(ink_e2e::bob(), 0), (ink_e2e::charlie(), 0), (ink_e2e::dave(), 1)] {
+                let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, candidate_id));
+                client.call(&signer, vote, 0, None).await.expect("vote failed");
+            }
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, 0));
+            let empty = client.call_dry_run(&ink_e2e::eve(), &vote, 0, None).await.return_value();
+            assert_eq!(empty, Err(VotingError::ZeroVotingPower));
+
+            let mut tally = Vec::new();
+            for candidate_id in 0..2 {
+                let get_votes =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_votes(0, candidate_id));
+                tally.push(client.call_dry_run(&ink_e2e::alice(), &get_votes, 0, None).await.return_value());
+            }
+            assert_eq!(tally, vec![350, 400]);
+            for (signer, candidate_id, weight) in [(ink_e2e::bob(), 0, 100), (ink_e2e::charlie(), 0, 250), (ink_e2e::dave(), 1, 400)] {
+                let get_my_vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_my_vote(0));
+                assert_eq!(
+                    client.call_dry_run(&signer, &get_my_vote, 0, None).await.return_value(),
+                    Some(Ballot { candidate: Some(candidate_id), weight })
+                );
+            }
+            let results = build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_results(0, 0, 2));
+            assert_eq!(
+                client.call_dry_run(&ink_e2e::alice(), &results, 0, None).await.return_value(),
+                vec![(String::from("Bob"), 400), (String::from("Alice"), 350)]
+            );
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn passing_proposal_executes_its_action_once(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(None, 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+
+            // If "Yes" wins, the voting contract lets Charlie spend 500 of its tokens
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Grant"),
+                    vec![String::from("No"), String::from("Yes")],
+                    1,
+                    VotingMode::Public,
+                    0,
+                    deadline_in(10_000),
+                )
+            });
+            client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+            let action = ProposalAction {
+                candidate_index: 1,
+                callee: token.clone(),
+                selector: ink::selector_bytes!("PSP22::approve"),
+                input: (charlie.clone(), 500u128).encode(),
+                transferred_value: 0,
+            };
+            let set_action = build_message::<VotingRef>(contract.clone()).call(|voting| voting.set_action(0, action.clone()));
+            client.call(&ink_e2e::alice(), set_action, 0, None).await.expect("set_action failed");
+
+            for (signer, candidate_id) in [(ink_e2e::alice(), 1), (ink_e2e::bob(), 1), (ink_e2e::dave(), 0)] {
+                let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, candidate_id));
+                client.call(&signer, vote, 0, None).await.expect("vote failed");
+            }
+            wait_for_end(&mut client, &contract, 0).await;
+
+            let execute = build_message::<VotingRef>(contract.clone()).call(|voting| voting.execute(0));
+            let unfinalized = client.call_dry_run(&ink_e2e::bob(), &execute, 0, None).await.return_value();
+            assert_eq!(unfinalized, Err(VotingError::NotPassed));
+            let finalize = build_message::<VotingRef>(contract.clone()).call(|voting| voting.finalize(0));
+            client.call(&ink_e2e::bob(), finalize, 0, None).await.expect("finalize failed");
+
+            let allowance =
+                build_message::<MockPsp22Ref>(token.clone()).call(|t| t.allowance(contract.clone(), charlie.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &allowance, 0, None).await.return_value(), 0);
+            let execute = build_message::<VotingRef>(contract.clone()).call(|voting| voting.execute(0));
+            let executed = client.call(&ink_e2e::bob(), execute, 0, None).await.expect("execute failed");
+            assert_eq!(executed.return_value(), Ok(true));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &allowance, 0, None).await.return_value(), 500);
+
+            let execute = build_message::<VotingRef>(contract.clone()).call(|voting| voting.execute(0));
+            let again = client.call_dry_run(&ink_e2e::bob(), &execute, 0, None).await.return_value();
+            assert_eq!(again, Err(VotingError::AlreadyExecuted));
+            let get_execution = build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_execution(0));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &get_execution, 0, None).await.return_value(), Some(true));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn actions_wait_for_the_timelock_and_a_passing_vote(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(None, 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+
+            // Both proposals would approve Charlie; only the second one passes
+            let ends_at = deadline_in(10_000);
+            for (proposal_id, amount) in [(0, 300u128), (1, 700u128)] {
+                let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                    voting.create_proposal(
+                        String::from("Grant"),
+                        vec![String::from("No"), String::from("Yes")],
+                        1,
+                        VotingMode::Public,
+                        0,
+                        ends_at,
+                    )
+                });
+                client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+                let action = ProposalAction {
+                    candidate_index: 1,
+                    callee: token.clone(),
+                    selector: ink::selector_bytes!("PSP22::approve"),
+                    input: (charlie.clone(), amount).encode(),
+                    transferred_value: 0,
+                };
+                let set_action =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.set_action(proposal_id, action.clone()));
+                client.call(&ink_e2e::alice(), set_action, 0, None).await.expect("set_action failed");
+            }
+            let set_delay = build_message::<VotingRef>(contract.clone()).call(|voting| voting.set_execution_delay(3_600_000));
+            client.call(&ink_e2e::alice(), set_delay, 0, None).await.expect("set_execution_delay failed");
+            for (proposal_id, candidate_id) in [(0, 0), (1, 1)] {
+                let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(proposal_id, candidate_id));
+                client.call(&ink_e2e::bob(), vote, 0, None).await.expect("vote failed");
+            }
+            wait_for_end(&mut client, &contract, 1).await;
+            for proposal_id in 0..2 {
+                let finalize = build_message::<VotingRef>(contract.clone()).call(|voting| voting.finalize(proposal_id));
+                client.call(&ink_e2e::bob(), finalize, 0, None).await.expect("finalize failed");
+            }
+
+            let execute = build_message::<VotingRef>(contract.clone()).call(|voting| voting.execute(1));
+            let early = client.call_dry_run(&ink_e2e::bob(), &execute, 0, None).await.return_value();
+            assert_eq!(early, Err(VotingError::TimelockNotElapsed));
+            let set_delay = build_message::<VotingRef>(contract.clone()).call(|voting| voting.set_execution_delay(0));
+            client.call(&ink_e2e::alice(), set_delay, 0, None).await.expect("set_execution_delay failed");
+
+            let allowance =
+                build_message::<MockPsp22Ref>(token.clone()).call(|t| t.allowance(contract.clone(), charlie.clone()));
+            let execute = build_message::<VotingRef>(contract.clone()).call(|voting| voting.execute(0));
+            let rejected = client.call_dry_run(&ink_e2e::bob(), &execute, 0, None).await.return_value();
+            assert_eq!(rejected, Err(VotingError::NotPassed));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &allowance, 0, None).await.return_value(), 0);
+
+            let execute = build_message::<VotingRef>(contract.clone()).call(|voting| voting.execute(1));
+            let executed = client.call(&ink_e2e::bob(), execute, 0, None).await.expect("execute failed");
+            assert_eq!(executed.return_value(), Ok(true));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &allowance, 0, None).await.return_value(), 700);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn quadratic_votes_cost_squares_and_are_refunded_after_the_end(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(Some(token.clone()), 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Budget"),
+                    vec![String::from("Parks"), String::from("Roads")],
+                    1,
+                    VotingMode::Quadratic { max_votes: 10 },
+                    0,
+                    deadline_in(10_000),
+                )
+            });
+            client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+            for (signer, account, amount) in [(ink_e2e::bob(), bob.clone(), 200), (ink_e2e::charlie(), charlie.clone(), 100)] {
+                let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(contract.clone(), 1_000));
+                client.call(&signer, approve, 0, None).await.expect("approve failed");
+                let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(account.clone(), amount, Vec::new()));
+                client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+            }
+
+            // 3 votes cost 9 tokens; 2 more cost 5^2 - 3^2 = 16
+            for (votes, locked) in [(3, 9), (2, 25)] {
+                let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote_quadratic(0, 1, votes));
+                client.call(&ink_e2e::bob(), vote, 0, None).await.expect("vote_quadratic failed");
+                let get_locked =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_locked(0, bob.clone()));
+                assert_eq!(client.call_dry_run(&ink_e2e::alice(), &get_locked, 0, None).await.return_value(), locked);
+            }
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote_quadratic(0, 1, 6));
+            let capped = client.call_dry_run(&ink_e2e::bob(), &vote, 0, None).await.return_value();
+            assert_eq!(capped, Err(VotingError::TooManyVotes));
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote_quadratic(0, 0, 1));
+            let switched = client.call_dry_run(&ink_e2e::bob(), &vote, 0, None).await.return_value();
+            assert_eq!(switched, Err(VotingError::AlreadyVoted));
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote_quadratic(0, 0, 4));
+            client.call(&ink_e2e::charlie(), vote, 0, None).await.expect("vote_quadratic failed");
+
+            let mut tally = Vec::new();
+            for candidate_id in 0..2 {
+                let get_votes =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_votes(0, candidate_id));
+                tally.push(client.call_dry_run(&ink_e2e::alice(), &get_votes, 0, None).await.return_value());
+            }
+            assert_eq!(tally, vec![4, 5]);
+            let withdraw = build_message::<VotingRef>(contract.clone()).call(|voting| voting.withdraw_locked(0));
+            let early = client.call_dry_run(&ink_e2e::bob(), &withdraw, 0, None).await.return_value();
+            assert_eq!(early, Err(VotingError::VotingStillActive));
+
+            wait_for_end(&mut client, &contract, 0).await;
+            for (signer, account, refund, balance) in
+                [(ink_e2e::bob(), bob.clone(), 25, 200), (ink_e2e::charlie(), charlie.clone(), 16, 100)]
+            {
+                let withdraw = build_message::<VotingRef>(contract.clone()).call(|voting| voting.withdraw_locked(0));
+                let withdrawn = client.call(&signer, withdraw, 0, None).await.expect("withdraw_locked failed");
+                assert_eq!(withdrawn.return_value(), Ok(refund));
+                let balance_of = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(account.clone()));
+                assert_eq!(client.call_dry_run(&ink_e2e::alice(), &balance_of, 0, None).await.return_value(), balance);
+            }
+            let withdraw = build_message::<VotingRef>(contract.clone()).call(|voting| voting.withdraw_locked(0));
+            let again = client.call_dry_run(&ink_e2e::bob(), &withdraw, 0, None).await.return_value();
+            assert_eq!(again, Err(VotingError::NothingLocked));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn old_stakes_outweigh_fresh_ones(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(Some(token.clone()), 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+
+            let set_cap = build_message::<VotingRef>(contract.clone()).call(|voting| voting.set_holding_cap(10));
+            client.call(&ink_e2e::alice(), set_cap, 0, None).await.expect("set_holding_cap failed");
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Board"),
+                    vec![String::from("Alice"), String::from("Bob")],
+                    1,
+                    VotingMode::Public,
+                    0,
+                    deadline_in(30_000),
+                )
+            });
+            client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+            for (signer, account) in [(ink_e2e::bob(), bob.clone()), (ink_e2e::charlie(), charlie.clone())] {
+                let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(contract.clone(), 1_000));
+                client.call(&signer, approve, 0, None).await.expect("approve failed");
+                let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(account.clone(), 100, Vec::new()));
+                client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+            }
+
+            // Bob's stake ages past the cap before Charlie stakes the same amount
+            let stake = build_message::<VotingRef>(contract.clone()).call(|voting| voting.stake_for_voting(100));
+            client.call(&ink_e2e::bob(), stake, 0, None).await.expect("stake_for_voting failed");
+            for _ in 0..10 {
+                client.create_and_fund_account(&ink_e2e::alice(), 1_000_000_000_000).await;
+            }
+            let stake = build_message::<VotingRef>(contract.clone()).call(|voting| voting.stake_for_voting(100));
+            client.call(&ink_e2e::charlie(), stake, 0, None).await.expect("stake_for_voting failed");
+
+            for (signer, candidate_id) in [(ink_e2e::bob(), 0), (ink_e2e::charlie(), 1)] {
+                let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, candidate_id));
+                client.call(&signer, vote, 0, None).await.expect("vote failed");
+            }
+            let mut weights = Vec::new();
+            for (account, candidate_id) in [(bob.clone(), 0), (charlie.clone(), 1)] {
+                let get_receipt =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_receipt(0, account.clone()));
+                let receipt = client
+                    .call_dry_run(&ink_e2e::alice(), &get_receipt, 0, None)
+                    .await
+                    .return_value()
+                    .expect("vote has a receipt");
+                let get_votes =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_votes(0, candidate_id));
+                assert_eq!(client.call_dry_run(&ink_e2e::alice(), &get_votes, 0, None).await.return_value(), receipt.weight);
+                weights.push(receipt.weight);
+            }
+            assert_eq!(weights[0], 100);
+            assert!(weights[1] > 0 && weights[1] < weights[0], "fresh stake weighs {}", weights[1]);
+
+            // Revoking and voting again keeps a single lock for the proposal
+            for _ in 0..2 {
+                let revoke = build_message::<VotingRef>(contract.clone()).call(|voting| voting.revoke_vote(0));
+                client.call(&ink_e2e::bob(), revoke, 0, None).await.expect("revoke_vote failed");
+                let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, 0));
+                client.call(&ink_e2e::bob(), vote, 0, None).await.expect("vote failed");
+            }
+            let get_stake =
+                build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_voting_stake(bob.clone()));
+            let stake = client
+                .call_dry_run(&ink_e2e::alice(), &get_stake, 0, None)
+                .await
+                .return_value()
+                .expect("Bob has a stake");
+            assert_eq!(stake.locked_by, vec![0]);
+
+            // Stakes stay put while the proposal they voted on runs
+            let unstake = build_message::<VotingRef>(contract.clone()).call(|voting| voting.unstake());
+            let early = client.call_dry_run(&ink_e2e::bob(), &unstake, 0, None).await.return_value();
+            assert_eq!(early, Err(VotingError::StakeInUse));
+            wait_for_end(&mut client, &contract, 0).await;
+            let unstake = build_message::<VotingRef>(contract.clone()).call(|voting| voting.unstake());
+            let unstaked = client.call(&ink_e2e::bob(), unstake, 0, None).await.expect("unstake failed");
+            assert_eq!(unstaked.return_value(), Ok(100));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn cancelled_proposals_release_locked_tokens(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(Some(token.clone()), 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Board"),
+                    vec![String::from("Alice"), String::from("Bob")],
+                    1,
+                    VotingMode::Public,
+                    0,
+                    Timestamp::MAX,
+                )
+            });
+            client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+
+            let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(contract.clone(), 1_000));
+            client.call(&ink_e2e::bob(), approve, 0, None).await.expect("approve failed");
+            let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(bob.clone(), 100, Vec::new()));
+            client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, 1));
+            client.call(&ink_e2e::bob(), vote, 0, None).await.expect("vote failed");
+
+            let withdraw = build_message::<VotingRef>(contract.clone()).call(|voting| voting.withdraw_locked(0));
+            let early = client.call_dry_run(&ink_e2e::bob(), &withdraw, 0, None).await.return_value();
+            assert_eq!(early, Err(VotingError::VotingStillActive));
+
+            // The deadline is far away, but cancelling releases the tokens right away
+            let cancel = build_message::<VotingRef>(contract.clone()).call(|voting| voting.cancel_proposal(0));
+            client.call(&ink_e2e::alice(), cancel, 0, None).await.expect("cancel_proposal failed");
+            let withdraw = build_message::<VotingRef>(contract.clone()).call(|voting| voting.withdraw_locked(0));
+            let withdrawn = client.call(&ink_e2e::bob(), withdraw, 0, None).await.expect("withdraw_locked failed");
+            assert_eq!(withdrawn.return_value(), Ok(100));
+
+            let withdraw = build_message::<VotingRef>(contract.clone()).call(|voting| voting.withdraw_locked(0));
+            let again = client.call_dry_run(&ink_e2e::bob(), &withdraw, 0, None).await.return_value();
+            assert_eq!(again, Err(VotingError::NothingLocked));
+            let balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(bob.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value(), 100);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn tokens_bought_mid_vote_add_no_weight(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(Some(token.clone()), 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+            let dave = ink_e2e::account_id(ink_e2e::AccountKeyring::Dave);
+
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Board"),
+                    vec![String::from("Alice"), String::from("Bob")],
+                    1,
+                    VotingMode::Public,
+                    0,
+                    Timestamp::MAX,
+                )
+            });
+            client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+
+            // Charlie never approves the voting contract
+            for signer in [ink_e2e::bob(), ink_e2e::dave()] {
+                let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(contract.clone(), 10_000));
+                client.call(&signer, approve, 0, None).await.expect("approve failed");
+            }
+            for (account, amount) in [(bob.clone(), 100), (charlie.clone(), 200), (dave.clone(), 300)] {
+                let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(account.clone(), amount, Vec::new()));
+                client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+            }
+
+            // Delegating without an approval cannot block the delegate's vote; it only
+            // leaves the delegator's tokens out
+            let delegate = build_message::<VotingRef>(contract.clone()).call(|voting| voting.delegate(0, dave.clone()));
+            client.call(&ink_e2e::charlie(), delegate, 0, None).await.expect("delegate failed");
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, 0));
+            client.call(&ink_e2e::dave(), vote, 0, None).await.expect("delegate vote failed");
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, 1));
+            client.call(&ink_e2e::bob(), vote, 0, None).await.expect("vote failed");
+
+            // Tokens Bob buys after voting stay out of the tally
+            let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(bob.clone(), 1_000, Vec::new()));
+            client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, 1));
+            let again = client.call_dry_run(&ink_e2e::bob(), &vote, 0, None).await.return_value();
+            assert_eq!(again, Err(VotingError::AlreadyVoted));
+
+            let mut tally = Vec::new();
+            for candidate_id in 0..2 {
+                let get_votes =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_votes(0, candidate_id));
+                tally.push(client.call_dry_run(&ink_e2e::alice(), &get_votes, 0, None).await.return_value());
+            }
+            assert_eq!(tally, vec![300, 100]);
+            for (account, locked) in [(bob.clone(), 100), (charlie.clone(), 0), (dave.clone(), 300)] {
+                let get_locked =
+                    build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_locked(0, account.clone()));
+                assert_eq!(client.call_dry_run(&ink_e2e::alice(), &get_locked, 0, None).await.return_value(), locked);
+            }
+            let balance = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.balance_of(bob.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value(), 1_000);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../mock_psp22/Cargo.toml")]
+        async fn top_up_counts_new_tokens_once(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate("mock_psp22", &ink_e2e::alice(), MockPsp22Ref::new(10_000), 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let constructor = VotingRef::new(Some(token.clone()), 0, 0, false, false);
+            let contract = client
+                .instantiate("voting", &ink_e2e::alice(), constructor, 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+            let create = build_message::<VotingRef>(contract.clone()).call(|voting| {
+                voting.create_proposal(
+                    String::from("Board"),
+                    vec![String::from("Alice"), String::from("Bob")],
+                    1,
+                    VotingMode::Public,
+                    0,
+                    Timestamp::MAX,
+                )
+            });
+            client.call(&ink_e2e::alice(), create, 0, None).await.expect("create_proposal failed");
+
+            let approve = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.approve(contract.clone(), 1_000));
+            client.call(&ink_e2e::bob(), approve, 0, None).await.expect("approve failed");
+            let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(bob.clone(), 100, Vec::new()));
+            client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+
+            let vote = build_message::<VotingRef>(contract.clone()).call(|voting| voting.vote(0, 1));
+            client.call(&ink_e2e::bob(), vote, 0, None).await.expect("vote failed");
+
+            let fund = build_message::<MockPsp22Ref>(token.clone()).call(|t| t.transfer(bob.clone(), 50, Vec::new()));
+            client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+            let top_up = build_message::<VotingRef>(contract.clone()).call(|voting| voting.top_up_vote(0));
+            client.call(&ink_e2e::bob(), top_up, 0, None).await.expect("top_up_vote failed");
+
+            let top_up = build_message::<VotingRef>(contract.clone()).call(|voting| voting.top_up_vote(0));
+            let again = client.call_dry_run(&ink_e2e::bob(), &top_up, 0, None).await.return_value();
+            assert_eq!(again, Err(VotingError::ZeroVotingPower));
+
+            let get_votes = build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_votes(0, 1));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &get_votes, 0, None).await.return_value(), 150);
+            let get_locked =
+                build_message::<VotingRef>(contract.clone()).call(|voting| voting.get_locked(0, bob.clone()));
+            assert_eq!(client.call_dry_run(&ink_e2e::alice(), &get_locked, 0, None).await.return_value(), 150);
+
+            Ok(())
+        }
+    }
+}
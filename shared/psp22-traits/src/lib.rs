@@ -0,0 +1,44 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! The PSP22 fungible token interface shared by the token-handling contracts,
+//! which hold a token as `contract_ref!(PSP22)` and call it through this trait.
+
+use ink::prelude::{string::String, vec::Vec};
+use ink::primitives::AccountId;
+
+pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+
+/// Error returned by a PSP22 token
+#[derive(scale::Encode, scale::Decode, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP22Error {
+    Custom(String),
+    InsufficientBalance,
+    InsufficientAllowance,
+    ZeroRecipientAddress,
+    ZeroSenderAddress,
+    SafeTransferCheckFailed(String),
+}
+
+/// The PSP22 fungible token interface. Selectors are `PSP22::<message>`, so any
+/// PSP22 token can be called through it.
+#[ink::trait_definition]
+pub trait PSP22 {
+    #[ink(message)]
+    fn total_supply(&self) -> Balance;
+
+    #[ink(message)]
+    fn balance_of(&self, owner: AccountId) -> Balance;
+
+    #[ink(message)]
+    fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance;
+
+    #[ink(message)]
+    fn transfer(&mut self, to: AccountId, value: Balance, data: Vec<u8>) -> Result<(), PSP22Error>;
+
+    #[ink(message)]
+    fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance, data: Vec<u8>) -> Result<(), PSP22Error>;
+
+    #[ink(message)]
+    fn approve(&mut self, spender: AccountId, value: Balance) -> Result<(), PSP22Error>;
+}
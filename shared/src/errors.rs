@@ -0,0 +1,49 @@
+/// Result of a cross-contract call to a message returning `Result<T, E>`: the
+/// environment error, the ink! dispatch error and the callee's own error
+pub type CallResult<T, E> = ink::env::Result<ink::MessageResult<Result<T, E>>>;
+
+/// Result of a cross-contract call to a message returning a plain `T`
+pub type QueryResult<T> = ink::env::Result<ink::MessageResult<T>>;
+
+/// Collapses a `CallResult` into the caller's error type: `call_failed` if the call
+/// could not be made or dispatched, `rejected` if the callee returned an error
+pub fn flatten_call<T, E, F>(result: CallResult<T, E>, call_failed: F, rejected: F) -> Result<T, F> {
+    match result {
+        Ok(Ok(Ok(value))) => Ok(value),
+        Ok(Ok(Err(_))) => Err(rejected),
+        Ok(Err(_)) | Err(_) => Err(call_failed),
+    }
+}
+
+/// Collapses a `QueryResult` into the caller's error type
+pub fn flatten_query<T, F>(result: QueryResult<T>, call_failed: F) -> Result<T, F> {
+    match result {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(_)) | Err(_) => Err(call_failed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_layers_map_to_the_caller_error() {
+        assert_eq!(flatten_call::<u8, (), _>(Ok(Ok(Ok(7))), "failed", "rejected"), Ok(7));
+        assert_eq!(flatten_call::<u8, (), _>(Ok(Ok(Err(()))), "failed", "rejected"), Err("rejected"));
+        assert_eq!(
+            flatten_call::<u8, (), _>(Ok(Err(ink::LangError::CouldNotReadInput)), "failed", "rejected"),
+            Err("failed")
+        );
+        assert_eq!(
+            flatten_call::<u8, (), _>(Err(ink::env::Error::CalleeTrapped), "failed", "rejected"),
+            Err("failed")
+        );
+    }
+
+    #[test]
+    fn query_layers_map_to_the_caller_error() {
+        assert_eq!(flatten_query(Ok(Ok(7u8)), "failed"), Ok(7));
+        assert_eq!(flatten_query::<u8, _>(Err(ink::env::Error::CalleeReverted), "failed"), Err("failed"));
+    }
+}
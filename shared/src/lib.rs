@@ -0,0 +1,8 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Items shared by the contracts of the workspace: cross-contract call error
+//! handling and fixed-width math helpers. The PSP22 token interface lives in the
+//! `psp22-traits` crate next to this one.
+
+pub mod errors;
+pub mod math;
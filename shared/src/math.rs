@@ -0,0 +1,91 @@
+/// Integer square root, rounded down
+pub fn sqrt(y: u128) -> u128 {
+    if y < 4 {
+        return if y == 0 { 0 } else { 1 };
+    }
+    // Newton's method; the estimate decreases monotonically to the floor
+    let mut z = y;
+    let mut x = y / 2 + 1;
+    while x < z {
+        z = x;
+        x = (y / x + x) / 2;
+    }
+    z
+}
+
+/// `a * b / denominator` rounded down, with a 256-bit intermediate product so it
+/// does not overflow when only the product exceeds `u128`. `None` if `denominator`
+/// is zero or the result does not fit in `u128`.
+pub fn full_mul_div(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    if denominator == 0 {
+        return None;
+    }
+    if let Some(product) = a.checked_mul(b) {
+        return Some(product / denominator);
+    }
+
+    let (hi, lo) = full_mul(a, b);
+    if hi >= denominator {
+        return None;
+    }
+    // Long division of (hi, lo) by the denominator, one bit at a time; the
+    // remainder always stays below the denominator
+    let mut remainder = hi;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((lo >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+/// 256-bit product of `a` and `b` as (high, low) halves
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+    let lo_lo = a_lo * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_lo = a_hi * b_lo;
+    let hi_hi = a_hi * b_hi;
+
+    let middle = (lo_lo >> 64) + (lo_hi & MASK) + (hi_lo & MASK);
+    let lo = (lo_lo & MASK) | (middle << 64);
+    let hi = hi_hi + (lo_hi >> 64) + (hi_lo >> 64) + (middle >> 64);
+    (hi, lo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqrt_rounds_down() {
+        let cases = [(0, 0), (1, 1), (2, 1), (3, 1), (4, 2), (15, 3), (16, 4), (1_000_000, 1_000)];
+        for (y, root) in cases {
+            assert_eq!(sqrt(y), root, "sqrt({y})");
+        }
+        assert_eq!(sqrt(u128::MAX), u64::MAX as u128);
+    }
+
+    #[test]
+    fn full_mul_div_matches_plain_arithmetic() {
+        assert_eq!(full_mul_div(6, 7, 4), Some(10));
+        assert_eq!(full_mul_div(6, 7, 0), None);
+        assert_eq!(full_mul_div(0, u128::MAX, 1), Some(0));
+    }
+
+    #[test]
+    fn full_mul_div_survives_overflowing_products() {
+        assert_eq!(full_mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(full_mul_div(u128::MAX, 3, 6), Some(u128::MAX / 2));
+        assert_eq!(full_mul_div(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+        assert_eq!(full_mul_div(u128::MAX, 2, 1), None);
+    }
+}
@@ -1,224 +1,3693 @@
-use std::collections::HashMap;
+// `main` only demonstrates part of the token API; the rest is exercised by the tests
+#![allow(dead_code)]
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
-#[derive(Clone)]
-struct Event {
-    event_type: String,
-    from: String,
-    to: String,
-    amount: u64,
+use sha2::{Digest, Sha256};
+
+/// Longest address, not counting the "0x" prefix of the hex form
+const MAX_ADDRESS_LEN: usize = 64;
+
+/// Account key the token accepts: 1 to 64 lowercase letters and digits, or "0x"
+/// followed by 1 to 64 hex digits. Checking the format up front keeps a typo
+/// from silently opening a new account; hex digits are lowercased for the same
+/// reason, so each hex account has a single spelling.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+struct Address(String);
+
+impl Address {
+    fn new(address: &str) -> Result<Self, AddressError> {
+        let hex = address
+            .strip_prefix("0x")
+            .filter(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit()));
+        let body = match hex {
+            Some(digits) => digits,
+            None => {
+                if let Some(c) = address
+                    .chars()
+                    .find(|c| !c.is_ascii_lowercase() && !c.is_ascii_digit())
+                {
+                    return Err(AddressError::InvalidCharacter(c));
+                }
+                address
+            }
+        };
+        if body.is_empty() {
+            return Err(AddressError::Empty);
+        }
+        if body.len() > MAX_ADDRESS_LEN {
+            return Err(AddressError::TooLong(body.len()));
+        }
+        match hex {
+            Some(digits) => Ok(Address(format!("0x{}", digits.to_ascii_lowercase()))),
+            None => Ok(Address(address.to_string())),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
-impl fmt::Debug for Event {
+/// Hashes like the `String` it wraps, so maps keyed by address can be queried
+/// with a `&str`
+impl std::borrow::Borrow<str> for Address {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        Address::new(address)
+    }
+}
+
+impl TryFrom<&str> for Address {
+    type Error = AddressError;
+
+    fn try_from(address: &str) -> Result<Self, Self::Error> {
+        Address::new(address)
+    }
+}
+
+impl TryFrom<String> for Address {
+    type Error = AddressError;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        Address::new(&address)
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> Self {
+        address.0
+    }
+}
+
+/// Reason a string is not a valid `Address`
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AddressError {
+    Empty,
+    /// Length of the address without the "0x" prefix
+    TooLong(usize),
+    InvalidCharacter(char),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::Empty => write!(f, "Address is empty"),
+            AddressError::TooLong(len) => write!(
+                f,
+                "Address has {} characters, at most {} are allowed",
+                len, MAX_ADDRESS_LEN
+            ),
+            AddressError::InvalidCharacter(c) => write!(f, "Address contains {:?}", c),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// State change recorded by the token, in the order it happened
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum TokenEvent {
+    Transfer {
+        from: String,
+        to: String,
+        amount: u64,
+    },
+    TransferFrom {
+        from: String,
+        to: String,
+        spender: String,
+        amount: u64,
+    },
+    /// Part of a transfer withheld for the treasury
+    TransferFee {
+        from: String,
+        treasury: String,
+        amount: u64,
+    },
+    /// `prev_amount` is the allowance the approval replaced, so indexers can spot
+    /// an old allowance spent in between two approvals
+    Approval {
+        owner: String,
+        spender: String,
+        amount: u64,
+        prev_amount: u64,
+    },
+    Mint {
+        to: String,
+        amount: u64,
+    },
+    /// `spender` is set when the tokens were burned out of its allowance
+    Burn {
+        from: String,
+        spender: Option<String>,
+        amount: u64,
+    },
+    MetadataChanged {
+        name: String,
+        symbol: String,
+    },
+    Paused {
+        by: String,
+    },
+    Unpaused {
+        by: String,
+    },
+    MinterAdded {
+        account: String,
+    },
+    MinterRemoved {
+        account: String,
+    },
+    /// `proposed` must accept before it replaces the owners
+    OwnershipProposed {
+        proposed: String,
+    },
+    /// `new_owner` is "0x0" once ownership is renounced
+    OwnershipTransferred {
+        new_owner: String,
+    },
+    AccountFrozen {
+        account: String,
+    },
+    AccountUnfrozen {
+        account: String,
+    },
+}
+
+impl TokenEvent {
+    fn kind(&self) -> &'static str {
+        match self {
+            TokenEvent::Transfer { .. } => "Transfer",
+            TokenEvent::TransferFrom { .. } => "TransferFrom",
+            TokenEvent::TransferFee { .. } => "TransferFee",
+            TokenEvent::Approval { .. } => "Approval",
+            TokenEvent::Mint { .. } => "Mint",
+            TokenEvent::Burn { .. } => "Burn",
+            TokenEvent::MetadataChanged { .. } => "MetadataChanged",
+            TokenEvent::Paused { .. } => "Paused",
+            TokenEvent::Unpaused { .. } => "Unpaused",
+            TokenEvent::MinterAdded { .. } => "MinterAdded",
+            TokenEvent::MinterRemoved { .. } => "MinterRemoved",
+            TokenEvent::OwnershipProposed { .. } => "OwnershipProposed",
+            TokenEvent::OwnershipTransferred { .. } => "OwnershipTransferred",
+            TokenEvent::AccountFrozen { .. } => "AccountFrozen",
+            TokenEvent::AccountUnfrozen { .. } => "AccountUnfrozen",
+        }
+    }
+
+    /// Accounts the event involves, each once
+    fn accounts(&self) -> Vec<&str> {
+        let mut accounts = match self {
+            TokenEvent::Transfer { from, to, .. } => vec![from.as_str(), to.as_str()],
+            TokenEvent::TransferFrom {
+                from, to, spender, ..
+            } => vec![from.as_str(), to.as_str(), spender.as_str()],
+            TokenEvent::TransferFee { from, treasury, .. } => {
+                vec![from.as_str(), treasury.as_str()]
+            }
+            TokenEvent::Approval { owner, spender, .. } => vec![owner.as_str(), spender.as_str()],
+            TokenEvent::Mint { to, .. } => vec![to.as_str()],
+            TokenEvent::Burn { from, spender, .. } => {
+                let mut accounts = vec![from.as_str()];
+                accounts.extend(spender.as_deref());
+                accounts
+            }
+            TokenEvent::MetadataChanged { .. } => Vec::new(),
+            TokenEvent::Paused { by } | TokenEvent::Unpaused { by } => vec![by.as_str()],
+            TokenEvent::MinterAdded { account }
+            | TokenEvent::MinterRemoved { account }
+            | TokenEvent::AccountFrozen { account }
+            | TokenEvent::AccountUnfrozen { account } => vec![account.as_str()],
+            TokenEvent::OwnershipProposed { proposed } => vec![proposed.as_str()],
+            TokenEvent::OwnershipTransferred { new_owner } => vec![new_owner.as_str()],
+        };
+        accounts.sort_unstable();
+        accounts.dedup();
+        accounts
+    }
+}
+
+/// One line per event, with "0x0" standing for the side a mint, burn or role
+/// change has no account on
+impl fmt::Display for TokenEvent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (from, to, amount, spender) = match self {
+            TokenEvent::Transfer { from, to, amount } => {
+                (from.as_str(), to.as_str(), *amount, None)
+            }
+            TokenEvent::TransferFrom {
+                from,
+                to,
+                spender,
+                amount,
+            } => (from.as_str(), to.as_str(), *amount, Some(spender)),
+            TokenEvent::TransferFee {
+                from,
+                treasury,
+                amount,
+            } => (from.as_str(), treasury.as_str(), *amount, None),
+            TokenEvent::Approval {
+                owner,
+                spender,
+                amount,
+                ..
+            } => (owner.as_str(), spender.as_str(), *amount, None),
+            TokenEvent::Mint { to, amount } => ("0x0", to.as_str(), *amount, None),
+            TokenEvent::Burn {
+                from,
+                spender,
+                amount,
+            } => (from.as_str(), "0x0", *amount, spender.as_ref()),
+            TokenEvent::MetadataChanged { name, symbol } => {
+                (name.as_str(), symbol.as_str(), 0, None)
+            }
+            TokenEvent::Paused { by } | TokenEvent::Unpaused { by } => {
+                (by.as_str(), "0x0", 0, None)
+            }
+            TokenEvent::MinterAdded { account }
+            | TokenEvent::MinterRemoved { account }
+            | TokenEvent::AccountFrozen { account }
+            | TokenEvent::AccountUnfrozen { account } => (account.as_str(), "0x0", 0, None),
+            TokenEvent::OwnershipProposed { proposed } => (proposed.as_str(), "0x0", 0, None),
+            TokenEvent::OwnershipTransferred { new_owner } => (new_owner.as_str(), "0x0", 0, None),
+        };
         write!(
             f,
-            "Event {{ type: {}, from: {}, to: {}, amount: {} }}",
-            self.event_type, self.from, self.to, self.amount
-        )
+            "Event {{ type: {}, from: {}, to: {}, amount: {}",
+            self.kind(),
+            from,
+            to,
+            amount
+        )?;
+        if let Some(spender) = spender {
+            write!(f, ", spender: {}", spender)?;
+        }
+        if let TokenEvent::Approval { prev_amount, .. } = self {
+            write!(f, ", previous: {}", prev_amount)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+/// A `TokenEvent` as recorded in the log. `sequence` numbers the events from 0
+/// in the order they were emitted; `timestamp` is read from the token's clock,
+/// if it has one.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Event {
+    sequence: u64,
+    timestamp: Option<u64>,
+    payload: TokenEvent,
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} ", self.sequence)?;
+        if let Some(timestamp) = self.timestamp {
+            write!(f, "@{} ", timestamp)?;
+        }
+        write!(f, "{}", self.payload)
+    }
+}
+
+/// Source of the timestamps put on events
+trait Clock {
+    fn now(&self) -> u64;
+}
+
+/// Tokens minted to `recipient` at `rate_per_block` for `duration` blocks from
+/// `start`, withdrawable as they accrue
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Stream {
+    recipient: Address,
+    rate_per_block: u64,
+    start: u64,
+    duration: u64,
+    withdrawn: u64,
+}
+
+impl Stream {
+    /// Total the stream mints over its whole duration
+    fn budget(&self) -> u64 {
+        self.rate_per_block * self.duration
+    }
+
+    /// Amount minted so far at block `now`, capped by the budget
+    fn accrued(&self, now: u64) -> u64 {
+        self.rate_per_block * now.saturating_sub(self.start).min(self.duration)
+    }
+}
+
+/// Reason a token operation was refused. Failed operations leave the state
+/// untouched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TokenError {
+    InsufficientBalance {
+        account: String,
+        required: u64,
+        available: u64,
+    },
+    InsufficientAllowance {
+        owner: String,
+        spender: String,
+        required: u64,
+        available: u64,
+    },
+    /// The caller is not one of the owners
+    NotOwner,
+    /// The operation must go through the owners' multisig
+    NeedsConfirmations {
+        threshold: usize,
+    },
+    Overflow,
+    Underflow,
+    Paused,
+    NotPaused,
+    /// Tokens cannot be sent to the "0x0" event sentinel
+    InvalidRecipient(String),
+    AlreadyMinter(String),
+    NotMinter(String),
+    EmptyMetadata,
+    AlreadyLocked(String),
+    NotLocked(String),
+    AlreadyBurnAddress(String),
+    NotAContract(String),
+    TransferRejected(String),
+    CannotReceive(String),
+    FeeTooHigh,
+    DuplicateOwners,
+    InvalidThreshold,
+    AlreadyProposed(u64),
+    NotProposed(u64),
+    AlreadyConfirmed {
+        owner: String,
+        hash: u64,
+    },
+    NotEnoughConfirmations {
+        hash: u64,
+        confirmed: usize,
+        threshold: usize,
+    },
+    EmptyStream,
+    NothingToWithdraw(String),
+    /// The caller is not the account proposed as the new owner
+    NotProposedOwner(String),
+    AccountFrozen(String),
+    AlreadyFrozen(String),
+    NotFrozen(String),
+    NotQueued(u64),
+    /// The timelock delay of the queued operation has not elapsed yet
+    Timelocked {
+        id: u64,
+        eta: u64,
+    },
+    /// The queued operation lacks the owner confirmations the threshold requires
+    Unconfirmed {
+        id: u64,
+        confirmed: usize,
+        threshold: usize,
+    },
+    UnknownSnapshot(u64),
+    /// No reward pool was funded for the snapshot
+    NoRewards(u64),
+    AlreadyClaimed {
+        account: String,
+        snapshot_id: u64,
+    },
+    NothingToClaim(String),
+    AirdropClaimed(String),
+    /// The merkle proof does not lead from the claimed leaf to the airdrop's root
+    InvalidProof(String),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::InsufficientBalance {
+                account,
+                required,
+                available,
+            } => write!(
+                f,
+                "Insufficient balance for {}: {} required, {} available",
+                account, required, available
+            ),
+            TokenError::InsufficientAllowance {
+                owner,
+                spender,
+                required,
+                available,
+            } => write!(
+                f,
+                "Insufficient allowance for {} from {}: {} required, {} available",
+                spender, owner, required, available
+            ),
+            TokenError::NotOwner => write!(f, "Caller is not an owner"),
+            TokenError::NeedsConfirmations { threshold } => {
+                write!(f, "Operation needs {} owner confirmations", threshold)
+            }
+            TokenError::Overflow => write!(f, "Balance overflow"),
+            TokenError::Underflow => write!(f, "Balance underflow"),
+            TokenError::Paused => write!(f, "Contract is paused"),
+            TokenError::NotPaused => write!(f, "Contract is not paused"),
+            TokenError::InvalidRecipient(to) => write!(f, "{:?} cannot receive tokens", to),
+            TokenError::AlreadyMinter(account) => write!(f, "{} is already a minter", account),
+            TokenError::NotMinter(account) => write!(f, "{} is not a minter", account),
+            TokenError::EmptyMetadata => write!(f, "Token name and symbol must not be empty"),
+            TokenError::AlreadyLocked(address) => write!(f, "{} is already locked", address),
+            TokenError::NotLocked(address) => write!(f, "{} is not locked", address),
+            TokenError::AlreadyBurnAddress(address) => {
+                write!(f, "{} is already a burn address", address)
+            }
+            TokenError::NotAContract(address) => write!(f, "{} is not a contract", address),
+            TokenError::TransferRejected(address) => write!(f, "{} rejected the transfer", address),
+            TokenError::CannotReceive(address) => write!(f, "{} cannot receive tokens", address),
+            TokenError::FeeTooHigh => write!(f, "Transfer fee exceeds 100%"),
+            TokenError::DuplicateOwners => write!(f, "Owners must be distinct"),
+            TokenError::InvalidThreshold => {
+                write!(f, "Threshold must be between 1 and the number of owners")
+            }
+            TokenError::AlreadyProposed(hash) => {
+                write!(f, "Operation {:x} is already proposed", hash)
+            }
+            TokenError::NotProposed(hash) => write!(f, "Operation {:x} is not proposed", hash),
+            TokenError::AlreadyConfirmed { owner, hash } => {
+                write!(f, "{} already confirmed operation {:x}", owner, hash)
+            }
+            TokenError::NotEnoughConfirmations {
+                hash,
+                confirmed,
+                threshold,
+            } => write!(
+                f,
+                "Operation {:x} has {} of {} confirmations",
+                hash, confirmed, threshold
+            ),
+            TokenError::EmptyStream => write!(f, "Stream must have a rate and a duration"),
+            TokenError::NothingToWithdraw(recipient) => {
+                write!(f, "Nothing to withdraw for {}", recipient)
+            }
+            TokenError::NotProposedOwner(account) => {
+                write!(f, "{} is not the proposed owner", account)
+            }
+            TokenError::AccountFrozen(account) => write!(f, "{} is frozen", account),
+            TokenError::AlreadyFrozen(account) => write!(f, "{} is already frozen", account),
+            TokenError::NotFrozen(account) => write!(f, "{} is not frozen", account),
+            TokenError::NotQueued(id) => write!(f, "Operation {} is not queued", id),
+            TokenError::Timelocked { id, eta } => {
+                write!(f, "Operation {} is timelocked until block {}", id, eta)
+            }
+            TokenError::Unconfirmed {
+                id,
+                confirmed,
+                threshold,
+            } => write!(
+                f,
+                "Queued operation {} has {} of {} confirmations",
+                id, confirmed, threshold
+            ),
+            TokenError::UnknownSnapshot(id) => write!(f, "Unknown snapshot {}", id),
+            TokenError::NoRewards(id) => write!(f, "No rewards for snapshot {}", id),
+            TokenError::AlreadyClaimed {
+                account,
+                snapshot_id,
+            } => write!(f, "{} already claimed snapshot {}", account, snapshot_id),
+            TokenError::NothingToClaim(account) => write!(f, "Nothing to claim for {}", account),
+            TokenError::AirdropClaimed(account) => {
+                write!(f, "{} already claimed the airdrop", account)
+            }
+            TokenError::InvalidProof(account) => write!(f, "Invalid airdrop proof for {}", account),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Reason a saved token state could not be restored
+#[derive(Debug)]
+enum SnapshotError {
+    Json(String),
+    /// The balances do not add up to the total supply
+    SupplyMismatch {
+        balances: u128,
+        total_supply: u64,
+    },
+    /// An allowance belongs to an account without a balance entry
+    UnknownAllowanceOwner(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Json(error) => write!(f, "Invalid snapshot: {}", error),
+            SnapshotError::SupplyMismatch {
+                balances,
+                total_supply,
+            } => write!(
+                f,
+                "Balances add up to {} but the total supply is {}",
+                balances, total_supply
+            ),
+            SnapshotError::UnknownAllowanceOwner(owner) => {
+                write!(f, "Allowance references unknown owner {}", owner)
+            }
+        }
     }
 }
 
+impl std::error::Error for SnapshotError {}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct OperationStats {
+    transfer_count: u64,
+    mint_count: u64,
+    burn_count: u64,
+    approve_count: u64,
+}
+
+/// Denominator for fees expressed in basis points
+const BPS_DENOMINATOR: u64 = 10_000;
+
+/// How the proportional part of the transfer fee is rounded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum FeeRounding {
+    #[default]
+    Down,
+    Up,
+}
+
+/// Code deployed at a contract address, as far as the token calls into it
+trait Contract {
+    /// Receiver hook consulted by `safe_transfer`: `Some(accept)` if the contract
+    /// implements it, `None` if it does not
+    fn can_receive(&self, _from: &Address, _amount: u64) -> Option<bool> {
+        None
+    }
+
+    /// Observer hook called with every event the token emits, once the state
+    /// change it describes has been applied
+    fn on_state_change(&mut self, _event: &Event) -> Result<(), String> {
+        Err("Contract does not observe state changes".to_string())
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct TokenContract {
     name: String,
     symbol: String,
+    // Number of decimal places raw amounts are displayed with
+    decimals: u8,
     total_supply: u64,
-    balances: HashMap<String, u64>,
-    allowances: HashMap<String, HashMap<String, u64>>,
+    balances: HashMap<Address, u64>,
+    allowances: HashMap<Address, HashMap<Address, u64>>,
     events: Vec<Event>,
-    owner: String,
+    // Timestamps events when set; without it events carry no timestamp
+    #[cfg_attr(feature = "serde", serde(skip))]
+    clock: Option<Box<dyn Clock>>,
+    // Positions in `events` of the events involving each account
+    events_by_account: HashMap<String, Vec<usize>>,
+    // Holder of the initial supply and recipient of the transfer fees
+    treasury: Address,
+    // Privileged operations need `threshold` distinct confirmations from `owners`
+    owners: Vec<Address>,
+    threshold: usize,
+    // Proposed operations by hash, with the owners who confirmed them
+    proposals: HashMap<u64, (OwnerOperation, HashSet<Address>)>,
+    // Accounts granted the right to mint besides the owners
+    minters: HashSet<Address>,
+    stats: OperationStats,
+    // Frozen copies of the balances and total supply, indexed by snapshot id
+    snapshots: Vec<(HashMap<Address, u64>, u64)>,
+    // Treasury, vesting and burn addresses left out of the circulating supply
+    locked_addresses: HashSet<Address>,
+    // Addresses holding contract code rather than belonging to plain accounts
+    #[cfg_attr(feature = "serde", serde(skip))]
+    contracts: HashMap<Address, Box<dyn Contract>>,
+    // Addresses nobody controls; tokens sent there are out of reach for good
+    burn_addresses: HashSet<Address>,
+    // Tokens sent to burn addresses, as opposed to burns that reduce the supply
+    total_burned: u64,
+    // Fee taken from every transfer for the treasury: `transfer_fee_bps` of the
+    // amount rounded per `fee_rounding`, but at least `min_transfer_fee`
+    transfer_fee_bps: u64,
+    min_transfer_fee: u64,
+    fee_rounding: FeeRounding,
+    // Contract notified of every event, so indexers can mirror the state
+    observer: Option<Address>,
+    // Positions in `events` the observer has yet to be notified of; notifications
+    // wait until the operation that emitted the events has finished
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unnotified: Vec<usize>,
+    // Continuous mints by id; several may pay the same recipient
+    streams: HashMap<u64, Stream>,
+    next_stream_id: u64,
+    // Current block, which only moves forward; streams accrue against it
+    block: u64,
+    // Set by an owner to stop transfers, approvals, mints and burns during an
+    // incident; views keep working
+    paused: bool,
+    // Account proposed to take over from the owners, until it accepts
+    pending_owner: Option<Address>,
+    // Accounts that can neither send nor receive tokens, nor burn them
+    frozen: HashSet<Address>,
+    // Vested grants per account as `(amount, release_block)`; the amount cannot
+    // be moved before the release block
+    vesting_locks: HashMap<Address, Vec<(u64, u64)>>,
 }
 
 impl TokenContract {
+    /// Token with the customary 18 decimals
     fn new(name: String, symbol: String, initial_supply: u64) -> Self {
+        Self::new_with_decimals(name, symbol, initial_supply, 18)
+    }
+
+    fn new_with_decimals(name: String, symbol: String, initial_supply: u64, decimals: u8) -> Self {
+        let owner = Address("owner".to_string());
         let mut balances = HashMap::new();
-        balances.insert("owner".to_string(), initial_supply);
+        balances.insert(owner.clone(), initial_supply);
 
         TokenContract {
             name,
             symbol,
+            decimals,
             total_supply: initial_supply,
             balances,
             allowances: HashMap::new(),
             events: Vec::new(),
-            owner: "owner".to_string(),
+            clock: None,
+            events_by_account: HashMap::new(),
+            treasury: owner.clone(),
+            owners: vec![owner],
+            threshold: 1,
+            proposals: HashMap::new(),
+            minters: HashSet::new(),
+            stats: OperationStats::default(),
+            snapshots: Vec::new(),
+            locked_addresses: HashSet::new(),
+            contracts: HashMap::new(),
+            burn_addresses: HashSet::new(),
+            total_burned: 0,
+            transfer_fee_bps: 0,
+            min_transfer_fee: 0,
+            fee_rounding: FeeRounding::Down,
+            observer: None,
+            unnotified: Vec::new(),
+            streams: HashMap::new(),
+            next_stream_id: 0,
+            block: 0,
+            paused: false,
+            pending_owner: None,
+            frozen: HashSet::new(),
+            vesting_locks: HashMap::new(),
         }
     }
 
-    fn transfer(&mut self, from: &str, to: &str, amount: u64) -> Result<(), String> {
+    fn transfer(&mut self, from: &Address, to: &Address, amount: u64) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        self.check_recipient(to)?;
+        self.check_not_frozen(from)?;
+        self.check_not_frozen(to)?;
         self.check_balance(from, amount)?;
         self.update_balances(from, to, amount)?;
-        self.emit_event("Transfer", from, to, amount);
+        self.emit(TokenEvent::Transfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+        });
+        self.stats.transfer_count += 1;
+        self.notify_observer();
+        Ok(())
+    }
+
+    /// Pays every recipient its amount from `from`, all or nothing: if the total
+    /// exceeds the balance or any leg fails, no balance changes
+    fn batch_transfer(
+        &mut self,
+        from: &Address,
+        recipients: &[(&Address, u64)],
+    ) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        let total = recipients
+            .iter()
+            .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+            .ok_or(TokenError::Overflow)?;
+        self.check_not_frozen(from)?;
+        self.check_balance(from, total)?;
+        let mut updated = HashMap::new();
+        for (to, amount) in recipients {
+            self.check_recipient(to)?;
+            self.check_not_frozen(to)?;
+            self.stage_transfer(&mut updated, from, to, *amount)?;
+        }
+
+        self.balances.extend(updated);
+        for (to, amount) in recipients {
+            self.settle_transfer(from, to, *amount);
+            self.emit(TokenEvent::Transfer {
+                from: from.to_string(),
+                to: to.to_string(),
+                amount: *amount,
+            });
+            self.stats.transfer_count += 1;
+        }
+        self.notify_observer();
+        Ok(())
+    }
+
+    /// Like `transfer`, but a contract recipient must accept the tokens through
+    /// its `can_receive` hook. Plain accounts always accept.
+    fn safe_transfer(
+        &mut self,
+        from: &Address,
+        to: &Address,
+        amount: u64,
+    ) -> Result<(), TokenError> {
+        if let Some(code) = self.contracts.get(to) {
+            match code.can_receive(from, amount) {
+                Some(true) => {}
+                Some(false) => return Err(TokenError::TransferRejected(to.to_string())),
+                None => return Err(TokenError::CannotReceive(to.to_string())),
+            }
+        }
+        self.transfer(from, to, amount)
+    }
+
+    /// Transfers `amount` to `to` and locks what `to` receives until block
+    /// `release_block`, e.g. for a team allocation
+    fn transfer_vested(
+        &mut self,
+        from: &Address,
+        to: &Address,
+        amount: u64,
+        release_block: u64,
+    ) -> Result<(), TokenError> {
+        let received = amount - self.transfer_fee(amount);
+        self.transfer(from, to, amount)?;
+        self.vesting_locks
+            .entry(to.clone())
+            .or_default()
+            .push((received, release_block));
         Ok(())
     }
 
     fn transfer_from(
         &mut self,
-        from: &str,
-        to: &str,
-        spender: &str,
+        from: &Address,
+        to: &Address,
+        spender: &Address,
         amount: u64,
-    ) -> Result<(), String> {
+    ) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        self.check_recipient(to)?;
+        self.check_not_frozen(from)?;
+        self.check_not_frozen(to)?;
         self.check_balance(from, amount)?;
         self.check_allowance(from, spender, amount)?;
         self.update_balances(from, to, amount)?;
         self.update_allowance(from, spender, amount)?;
-        self.emit_event("TransferFrom", from, to, amount);
+        self.emit(TokenEvent::TransferFrom {
+            from: from.to_string(),
+            to: to.to_string(),
+            spender: spender.to_string(),
+            amount,
+        });
+        self.stats.transfer_count += 1;
+        self.notify_observer();
         Ok(())
     }
 
-    fn approve(&mut self, owner: &str, spender: &str, amount: u64) -> Result<(), String> {
-        self.allowances
-            .entry(owner.to_string())
-            .or_insert_with(HashMap::new)
-            .insert(spender.to_string(), amount);
-        self.emit_event("Approval", owner, spender, amount);
+    fn approve(
+        &mut self,
+        owner: &Address,
+        spender: &Address,
+        amount: u64,
+    ) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        self.set_allowance(owner, spender, amount);
         Ok(())
     }
 
-    fn mint(&mut self, to: &str, amount: u64) -> Result<(), String> {
-        if to != self.owner {
-            return Err("Only owner can mint tokens".to_string());
-        }
-        *self.balances.entry(to.to_string()).or_insert(0) += amount;
-        self.total_supply += amount;
-        self.emit_event("Mint", "0x0", to, amount);
+    /// Raises the allowance by `delta` instead of overwriting it, so a spender
+    /// cannot front-run the change and spend both the old and the new amount
+    fn increase_allowance(
+        &mut self,
+        owner: &Address,
+        spender: &Address,
+        delta: u64,
+    ) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        let allowance = self
+            .allowance(owner, spender)
+            .checked_add(delta)
+            .ok_or(TokenError::Overflow)?;
+        self.set_allowance(owner, spender, allowance);
         Ok(())
     }
 
-    fn burn(&mut self, from: &str, amount: u64) -> Result<(), String> {
-        self.check_balance(from, amount)?;
-        *self.balances.get_mut(from).unwrap() -= amount;
-        self.total_supply -= amount;
-        self.emit_event("Burn", from, "0x0", amount);
+    fn decrease_allowance(
+        &mut self,
+        owner: &Address,
+        spender: &Address,
+        delta: u64,
+    ) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        self.check_allowance(owner, spender, delta)?;
+        let allowance = self.allowance(owner, spender) - delta;
+        self.set_allowance(owner, spender, allowance);
         Ok(())
     }
 
-    fn balance_of(&self, account: &str) -> u64 {
-        *self.balances.get(account).unwrap_or(&0)
+    /// Stores the allowance and emits an `Approval` event carrying it
+    fn set_allowance(&mut self, owner: &Address, spender: &Address, amount: u64) {
+        // Register the owner so every allowance refers to a known account
+        self.balances.entry(owner.clone()).or_insert(0);
+        let prev_amount = self
+            .allowances
+            .entry(owner.clone())
+            .or_default()
+            .insert(spender.clone(), amount)
+            .unwrap_or(0);
+        self.emit(TokenEvent::Approval {
+            owner: owner.to_string(),
+            spender: spender.to_string(),
+            amount,
+            prev_amount,
+        });
+        self.stats.approve_count += 1;
+        self.notify_observer();
     }
 
-    fn allowance(&self, owner: &str, spender: &str) -> u64 {
-        self.allowances
-            .get(owner)
-            .and_then(|spenders| spenders.get(spender))
-            .cloned()
-            .unwrap_or(0)
+    /// Mints `amount` to any `to`, on behalf of an owner or a minter
+    fn mint(&mut self, caller: &Address, to: &Address, amount: u64) -> Result<(), TokenError> {
+        if !self.is_minter(caller) {
+            self.check_privileged(caller)?;
+        }
+        self.issue(to, amount)
     }
 
-    fn get_token_info(&self) -> (String, String, u64) {
-        (self.name.clone(), self.symbol.clone(), self.total_supply)
+    fn grant_minter(&mut self, caller: &Address, account: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::GrantMinter {
+            account: account.clone(),
+        })
     }
 
-    fn check_balance(&self, account: &str, amount: u64) -> Result<(), String> {
-        let balance = self.balance_of(account);
-        if balance < amount {
-            Err(format!("Insufficient balance for {}", account))
-        } else {
-            Ok(())
-        }
+    fn revoke_minter(&mut self, caller: &Address, account: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::RevokeMinter {
+            account: account.clone(),
+        })
     }
 
-    fn check_allowance(&self, owner: &str, spender: &str, amount: u64) -> Result<(), String> {
-        let allowed = self.allowance(owner, spender);
-        if allowed < amount {
-            Err(format!(
-                "Insufficient allowance for {} from {}",
-                spender, owner
-            ))
-        } else {
-            Ok(())
+    /// First step of handing the token to `new_owner`, who takes over only once it
+    /// calls `accept_ownership`, so a mistyped address cannot lock the owners out
+    fn propose_owner(&mut self, caller: &Address, new_owner: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::ProposeOwner {
+            new_owner: new_owner.clone(),
+        })
+    }
+
+    /// Makes the proposed owner the sole owner, replacing all current owners
+    fn accept_ownership(&mut self, caller: &Address) -> Result<(), TokenError> {
+        if self.pending_owner.as_ref() != Some(caller) {
+            return Err(TokenError::NotProposedOwner(caller.to_string()));
         }
+        self.pending_owner = None;
+        self.owners = vec![caller.clone()];
+        self.threshold = 1;
+        self.emit(TokenEvent::OwnershipTransferred {
+            new_owner: caller.to_string(),
+        });
+        self.notify_observer();
+        Ok(())
+    }
+
+    /// Leaves the token without owners or minters for good: nothing can be minted
+    /// or reconfigured afterwards, though open streams keep paying out
+    fn renounce_ownership(&mut self, caller: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::RenounceOwnership)
+    }
+
+    fn is_minter(&self, account: &Address) -> bool {
+        self.minters.contains(account)
     }
 
-    fn update_balances(&mut self, from: &str, to: &str, amount: u64) -> Result<(), String> {
-        *self.balances.entry(from.to_string()).or_insert(0) -= amount;
-        *self.balances.entry(to.to_string()).or_insert(0) += amount;
+    fn issue(&mut self, to: &Address, amount: u64) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        let total_supply = self
+            .total_supply
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+        let balance = self
+            .balance_of(to)
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+        self.balances.insert(to.clone(), balance);
+        self.total_supply = total_supply;
+        self.emit(TokenEvent::Mint {
+            to: to.to_string(),
+            amount,
+        });
+        self.stats.mint_count += 1;
+        self.notify_observer();
         Ok(())
     }
 
-    fn update_allowance(&mut self, owner: &str, spender: &str, amount: u64) -> Result<(), String> {
-        let allowance = self
-            .allowances
-            .get_mut(owner)
-            .and_then(|spenders| spenders.get_mut(spender))
-            .unwrap();
-        *allowance -= amount;
+    fn burn(&mut self, from: &Address, amount: u64) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        self.check_not_frozen(from)?;
+        self.check_balance(from, amount)?;
+        self.destroy(from, amount, None)?;
+        self.notify_observer();
         Ok(())
     }
 
-    fn emit_event(&mut self, event_type: &str, from: &str, to: &str, amount: u64) {
-        self.events.push(Event {
-            event_type: event_type.to_string(),
+    /// Burns `amount` of `owner`'s tokens out of the allowance of `spender`
+    fn burn_from(
+        &mut self,
+        owner: &Address,
+        spender: &Address,
+        amount: u64,
+    ) -> Result<(), TokenError> {
+        self.check_not_paused()?;
+        self.check_not_frozen(owner)?;
+        self.check_balance(owner, amount)?;
+        self.check_allowance(owner, spender, amount)?;
+        self.destroy(owner, amount, Some(spender))?;
+        self.update_allowance(owner, spender, amount)?;
+        // Only now is the burn complete, allowance included
+        self.notify_observer();
+        Ok(())
+    }
+
+    fn destroy(
+        &mut self,
+        from: &Address,
+        amount: u64,
+        spender: Option<&Address>,
+    ) -> Result<(), TokenError> {
+        let balance = self
+            .balance_of(from)
+            .checked_sub(amount)
+            .ok_or(TokenError::Underflow)?;
+        let total_supply = self
+            .total_supply
+            .checked_sub(amount)
+            .ok_or(TokenError::Underflow)?;
+        self.balances.insert(from.clone(), balance);
+        self.total_supply = total_supply;
+        self.emit(TokenEvent::Burn {
             from: from.to_string(),
-            to: to.to_string(),
+            spender: spender.map(Address::to_string),
             amount,
         });
+        self.stats.burn_count += 1;
+        Ok(())
     }
 
-    fn get_events(&self) -> &[Event] {
-        &self.events
+    fn balance_of(&self, account: &Address) -> u64 {
+        *self.balances.get(account).unwrap_or(&0)
     }
 
-    fn print_event_summary(&self) {
-        let mut transfer_volume = 0;
-        let mut mint_volume = 0;
-        let mut burn_volume = 0;
+    /// Accounts with a positive balance, largest first. Emptied accounts keep
+    /// their entry in `balances` when they have approvals on record, so they are
+    /// filtered out here rather than removed.
+    fn holders(&self) -> Vec<(Address, u64)> {
+        let mut holders: Vec<(Address, u64)> = self
+            .balances
+            .iter()
+            .filter(|(_, &balance)| balance > 0)
+            .map(|(account, &balance)| (account.clone(), balance))
+            .collect();
+        holders.sort_by(|(a, a_balance), (b, b_balance)| b_balance.cmp(a_balance).then(a.cmp(b)));
+        holders
+    }
 
-        for event in &self.events {
-            match event.event_type.as_str() {
-                "Transfer" | "TransferFrom" => transfer_volume += event.amount,
-                "Mint" => mint_volume += event.amount,
-                "Burn" => burn_volume += event.amount,
-                _ => {}
-            }
+    fn holder_count(&self) -> usize {
+        self.balances
+            .values()
+            .filter(|&&balance| balance > 0)
+            .count()
+    }
+
+    /// The part of the balance `account` can move right now. Transfers and burns
+    /// are checked against this figure: tokens still vesting are deducted, and
+    /// burn addresses and frozen accounts can move nothing.
+    fn spendable_balance(&self, account: &Address) -> u64 {
+        if self.is_burn_address(account) || self.is_frozen(account) {
+            return 0;
         }
+        self.balance_of(account).saturating_sub(self.vesting_balance(account))
+    }
 
-        println!("Event Summary:");
-        println!("Total Transfer Volume: {}", transfer_volume);
-        println!("Total Minted: {}", mint_volume);
-        println!("Total Burned: {}", burn_volume);
+    /// Tokens of `account` whose vesting lock has not been released yet
+    fn vesting_balance(&self, account: &Address) -> u64 {
+        self.vesting_locks
+            .get(account)
+            .into_iter()
+            .flatten()
+            .filter(|(_, release_block)| *release_block > self.block)
+            .map(|(amount, _)| amount)
+            .sum()
     }
-}
 
-impl fmt::Debug for TokenContract {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TokenContract")
-            .field("name", &self.name)
-            .field("symbol", &self.symbol)
-            .field("total_supply", &self.total_supply)
-            .field("balances", &self.balances)
-            .field("allowances", &self.allowances)
-            .finish()
+    fn allowance(&self, owner: &Address, spender: &Address) -> u64 {
+        self.allowances
+            .get(owner)
+            .and_then(|spenders| spenders.get(spender))
+            .cloned()
+            .unwrap_or(0)
     }
-}
 
-fn main() {
-    let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+    fn set_name(&mut self, caller: &Address, name: String) -> Result<(), TokenError> {
+        self.set_metadata(caller, Some(name), None)
+    }
 
-    let (name, symbol, total_supply) = token.get_token_info();
-    println!(
-        "Token Info: {} ({}) - Total Supply: {}",
-        name, symbol, total_supply
-    );
+    fn set_symbol(&mut self, caller: &Address, symbol: String) -> Result<(), TokenError> {
+        self.set_metadata(caller, None, Some(symbol))
+    }
 
-    println!("Initial state: {:?}", token);
+    fn set_metadata(
+        &mut self,
+        caller: &Address,
+        name: Option<String>,
+        symbol: Option<String>,
+    ) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::SetMetadata { name, symbol })
+    }
 
-    // Perform some operations
-    token.transfer("owner", "alice", 1000).unwrap();
-    token.approve("alice", "bob", 500).unwrap();
-    token.transfer_from("alice", "bob", "bob", 300).unwrap();
-    token.mint("owner", 5000).unwrap();
-    token.burn("owner", 2000).unwrap();
+    fn mark_locked_address(
+        &mut self,
+        caller: &Address,
+        address: &Address,
+    ) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::LockAddress {
+            address: address.clone(),
+        })
+    }
 
-    println!("Final state: {:?}", token);
+    fn unmark_locked_address(
+        &mut self,
+        caller: &Address,
+        address: &Address,
+    ) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::UnlockAddress {
+            address: address.clone(),
+        })
+    }
 
-    // Print all events
-    println!("Events:");
-    for event in token.get_events() {
-        println!("{:?}", event);
+    /// Records that `address` holds `code`, so transfers to it are treated as
+    /// transfers to a contract
+    fn register_contract(&mut self, address: &Address, code: Box<dyn Contract>) {
+        self.contracts.insert(address.clone(), code);
     }
 
-    // Print event summary
-    token.print_event_summary();
+    fn register_burn_address(
+        &mut self,
+        caller: &Address,
+        address: &Address,
+    ) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::RegisterBurnAddress {
+            address: address.clone(),
+        })
+    }
+
+    /// Sets the contract whose `on_state_change` hook is called with every event,
+    /// or removes it with `None`
+    fn set_observer(
+        &mut self,
+        caller: &Address,
+        observer: Option<&Address>,
+    ) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::SetObserver {
+            observer: observer.cloned(),
+        })
+    }
+
+    /// Stops transfers, approvals, mints and burns until an owner unpauses the
+    /// token. Pausing twice is an error, so scripts notice a double pause.
+    fn pause(&mut self, caller: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::Pause)
+    }
+
+    fn unpause(&mut self, caller: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::Unpause)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stops `account` from sending, receiving or burning tokens. Its balance
+    /// stays on record and in the supply.
+    fn freeze_account(&mut self, caller: &Address, account: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::Freeze {
+            account: account.clone(),
+        })
+    }
+
+    fn unfreeze_account(&mut self, caller: &Address, account: &Address) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::Unfreeze {
+            account: account.clone(),
+        })
+    }
+
+    fn is_frozen(&self, account: &Address) -> bool {
+        self.frozen.contains(account)
+    }
+
+    fn is_burn_address(&self, address: &Address) -> bool {
+        self.burn_addresses.contains(address)
+    }
+
+    /// Tokens transferred to burn addresses since they were registered. They stay
+    /// in the total supply; `burn` is the supply-reducing mechanism.
+    fn total_burned(&self) -> u64 {
+        self.total_burned
+    }
+
+    fn set_transfer_fee(
+        &mut self,
+        caller: &Address,
+        fee_bps: u64,
+        min_fee: u64,
+        rounding: FeeRounding,
+    ) -> Result<(), TokenError> {
+        self.check_privileged(caller)?;
+        self.apply_operation(caller, OwnerOperation::SetTransferFee {
+            fee_bps,
+            min_fee,
+            rounding,
+        })
+    }
+
+    /// Opens a stream minting `rate_per_block` to `recipient` for `duration` blocks
+    /// from the current block, and returns its id
+    fn stream(
+        &mut self,
+        caller: &Address,
+        recipient: &Address,
+        rate_per_block: u64,
+        duration: u64,
+    ) -> Result<u64, TokenError> {
+        self.check_privileged(caller)?;
+        let id = self.next_stream_id;
+        self.apply_operation(caller, OwnerOperation::OpenStream {
+            recipient: recipient.clone(),
+            rate_per_block,
+            duration,
+            start: self.block,
+        })?;
+        Ok(id)
+    }
+
+    /// Mints to `recipient` everything its streams accrued by the current block and
+    /// not yet withdrawn, and returns the amount
+    fn withdraw_stream(&mut self, recipient: &Address) -> Result<u64, TokenError> {
+        let amount = self.withdrawable(recipient);
+        if amount == 0 {
+            return Err(TokenError::NothingToWithdraw(recipient.to_string()));
+        }
+        self.issue(recipient, amount)?;
+        for stream in self.streams.values_mut() {
+            if &stream.recipient == recipient {
+                stream.withdrawn = stream.accrued(self.block);
+            }
+        }
+        Ok(amount)
+    }
+
+    /// Accrued but not yet withdrawn amount of `recipient`'s streams at the current
+    /// block
+    fn withdrawable(&self, recipient: &Address) -> u64 {
+        self.streams
+            .values()
+            .filter(|stream| &stream.recipient == recipient)
+            .map(|stream| stream.accrued(self.block).saturating_sub(stream.withdrawn))
+            .sum()
+    }
+
+    /// Moves the block counter forward. Time is kept by the token rather than
+    /// passed in, so a recipient cannot withdraw against a future block.
+    fn advance_blocks(&mut self, blocks: u64) {
+        self.block = self.block.saturating_add(blocks);
+    }
+
+    fn block_number(&self) -> u64 {
+        self.block
+    }
+
+    fn get_stream(&self, id: u64) -> Option<&Stream> {
+        self.streams.get(&id)
+    }
+
+    fn is_owner(&self, account: &Address) -> bool {
+        self.owners.iter().any(|owner| owner == account)
+    }
+
+    /// Privileged operations can be called directly by an owner only while one
+    /// confirmation suffices; otherwise they go through `propose`, `confirm` and
+    /// `execute`
+    fn check_privileged(&self, caller: &Address) -> Result<(), TokenError> {
+        if !self.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        if self.threshold > 1 {
+            return Err(TokenError::NeedsConfirmations {
+                threshold: self.threshold,
+            });
+        }
+        Ok(())
+    }
+
+    fn operation_hash(operation: &OwnerOperation) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        operation.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Proposes `operation` with the proposer's confirmation and returns the hash
+    /// the other owners confirm it by
+    fn propose(&mut self, caller: &Address, operation: OwnerOperation) -> Result<u64, TokenError> {
+        if !self.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        let hash = Self::operation_hash(&operation);
+        if self.proposals.contains_key(&hash) {
+            return Err(TokenError::AlreadyProposed(hash));
+        }
+        let confirmations = HashSet::from([caller.clone()]);
+        self.proposals.insert(hash, (operation, confirmations));
+        Ok(hash)
+    }
+
+    fn confirm(&mut self, caller: &Address, hash: u64) -> Result<(), TokenError> {
+        if !self.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        let (_, confirmations) = self
+            .proposals
+            .get_mut(&hash)
+            .ok_or(TokenError::NotProposed(hash))?;
+        if !confirmations.insert(caller.clone()) {
+            return Err(TokenError::AlreadyConfirmed {
+                owner: caller.to_string(),
+                hash,
+            });
+        }
+        Ok(())
+    }
+
+    /// Executes a proposed operation once `threshold` current owners confirmed it
+    fn execute(&mut self, caller: &Address, hash: u64) -> Result<(), TokenError> {
+        if !self.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        let (operation, confirmations) = self
+            .proposals
+            .get(&hash)
+            .ok_or(TokenError::NotProposed(hash))?;
+        let confirmed = confirmations
+            .iter()
+            .filter(|owner| self.is_owner(owner))
+            .count();
+        if confirmed < self.threshold {
+            return Err(TokenError::NotEnoughConfirmations {
+                hash,
+                confirmed,
+                threshold: self.threshold,
+            });
+        }
+
+        self.apply_operation(caller, operation.clone())?;
+        self.proposals.remove(&hash);
+        Ok(())
+    }
+
+    /// Carries out an operation whose authorization has already been checked, on
+    /// behalf of `caller`
+    fn apply_operation(
+        &mut self,
+        caller: &Address,
+        operation: OwnerOperation,
+    ) -> Result<(), TokenError> {
+        match operation {
+            OwnerOperation::Mint { to, amount } => self.issue(&to, amount)?,
+            OwnerOperation::SetTransferFee {
+                fee_bps,
+                min_fee,
+                rounding,
+            } => {
+                if fee_bps > BPS_DENOMINATOR {
+                    return Err(TokenError::FeeTooHigh);
+                }
+                self.transfer_fee_bps = fee_bps;
+                self.min_transfer_fee = min_fee;
+                self.fee_rounding = rounding;
+            }
+            OwnerOperation::SetOwners { owners, threshold } => {
+                let distinct: HashSet<&Address> = owners.iter().collect();
+                if distinct.len() != owners.len() {
+                    return Err(TokenError::DuplicateOwners);
+                }
+                if threshold == 0 || threshold > owners.len() {
+                    return Err(TokenError::InvalidThreshold);
+                }
+                self.owners = owners;
+                self.threshold = threshold;
+            }
+            OwnerOperation::GrantMinter { account } => {
+                if !self.minters.insert(account.clone()) {
+                    return Err(TokenError::AlreadyMinter(account.to_string()));
+                }
+                self.emit(TokenEvent::MinterAdded {
+                    account: account.to_string(),
+                });
+            }
+            OwnerOperation::RevokeMinter { account } => {
+                if !self.minters.remove(&account) {
+                    return Err(TokenError::NotMinter(account.to_string()));
+                }
+                self.emit(TokenEvent::MinterRemoved {
+                    account: account.to_string(),
+                });
+            }
+            OwnerOperation::ProposeOwner { new_owner } => {
+                self.emit(TokenEvent::OwnershipProposed {
+                    proposed: new_owner.to_string(),
+                });
+                self.pending_owner = Some(new_owner);
+            }
+            OwnerOperation::RenounceOwnership => {
+                self.owners.clear();
+                self.minters.clear();
+                self.pending_owner = None;
+                self.emit(TokenEvent::OwnershipTransferred {
+                    new_owner: "0x0".to_string(),
+                });
+            }
+            OwnerOperation::OpenStream {
+                recipient,
+                rate_per_block,
+                duration,
+                start,
+            } => {
+                if rate_per_block == 0 || duration == 0 {
+                    return Err(TokenError::EmptyStream);
+                }
+                if rate_per_block.checked_mul(duration).is_none() {
+                    return Err(TokenError::Overflow);
+                }
+                let stream = Stream {
+                    recipient,
+                    rate_per_block,
+                    start,
+                    duration,
+                    withdrawn: 0,
+                };
+                self.streams.insert(self.next_stream_id, stream);
+                self.next_stream_id += 1;
+            }
+            OwnerOperation::Pause => {
+                self.check_not_paused()?;
+                self.paused = true;
+                self.emit(TokenEvent::Paused {
+                    by: caller.to_string(),
+                });
+            }
+            OwnerOperation::Unpause => {
+                if !self.paused {
+                    return Err(TokenError::NotPaused);
+                }
+                self.paused = false;
+                self.emit(TokenEvent::Unpaused {
+                    by: caller.to_string(),
+                });
+            }
+            OwnerOperation::Freeze { account } => {
+                if !self.frozen.insert(account.clone()) {
+                    return Err(TokenError::AlreadyFrozen(account.to_string()));
+                }
+                self.emit(TokenEvent::AccountFrozen {
+                    account: account.to_string(),
+                });
+            }
+            OwnerOperation::Unfreeze { account } => {
+                if !self.frozen.remove(&account) {
+                    return Err(TokenError::NotFrozen(account.to_string()));
+                }
+                self.emit(TokenEvent::AccountUnfrozen {
+                    account: account.to_string(),
+                });
+            }
+            OwnerOperation::SetMetadata { name, symbol } => {
+                if name.as_deref() == Some("") || symbol.as_deref() == Some("") {
+                    return Err(TokenError::EmptyMetadata);
+                }
+                if let Some(name) = name {
+                    self.name = name;
+                }
+                if let Some(symbol) = symbol {
+                    self.symbol = symbol;
+                }
+                self.emit(TokenEvent::MetadataChanged {
+                    name: self.name.clone(),
+                    symbol: self.symbol.clone(),
+                });
+            }
+            OwnerOperation::LockAddress { address } => {
+                if !self.locked_addresses.insert(address.clone()) {
+                    return Err(TokenError::AlreadyLocked(address.to_string()));
+                }
+            }
+            OwnerOperation::UnlockAddress { address } => {
+                if !self.locked_addresses.remove(&address) {
+                    return Err(TokenError::NotLocked(address.to_string()));
+                }
+            }
+            OwnerOperation::RegisterBurnAddress { address } => {
+                if !self.burn_addresses.insert(address.clone()) {
+                    return Err(TokenError::AlreadyBurnAddress(address.to_string()));
+                }
+            }
+            OwnerOperation::SetObserver { observer } => {
+                if let Some(observer) = &observer {
+                    if !self.contracts.contains_key(observer) {
+                        return Err(TokenError::NotAContract(observer.to_string()));
+                    }
+                }
+                self.observer = observer;
+            }
+        }
+        self.notify_observer();
+        Ok(())
+    }
+
+    /// Fee withheld from a transfer of `amount`. The floor keeps micro-transfers
+    /// from rounding the fee to zero, and the fee never exceeds the amount, so the
+    /// recipient is never debited.
+    fn transfer_fee(&self, amount: u64) -> u64 {
+        if self.transfer_fee_bps == 0 || amount == 0 {
+            return 0;
+        }
+        let scaled = amount as u128 * self.transfer_fee_bps as u128;
+        let denominator = BPS_DENOMINATOR as u128;
+        let proportional = match self.fee_rounding {
+            FeeRounding::Down => scaled / denominator,
+            FeeRounding::Up => scaled.div_ceil(denominator),
+        } as u64;
+        proportional.max(self.min_transfer_fee).min(amount)
+    }
+
+    /// Total supply minus the balances held by locked addresses
+    fn circulating_supply(&self) -> u64 {
+        let locked: u64 = self
+            .locked_addresses
+            .iter()
+            .map(|address| self.balance_of(address))
+            .sum();
+        self.total_supply - locked
+    }
+
+    /// Records the current balances and returns the id to query them with
+    fn snapshot(&mut self) -> u64 {
+        self.snapshots.push((self.balances.clone(), self.total_supply));
+        self.snapshots.len() as u64 - 1
+    }
+
+    fn balance_of_at(&self, account: &Address, snapshot_id: u64) -> Option<u64> {
+        self.snapshots
+            .get(snapshot_id as usize)
+            .map(|(balances, _)| *balances.get(account).unwrap_or(&0))
+    }
+
+    fn total_supply_at(&self, snapshot_id: u64) -> Option<u64> {
+        self.snapshots
+            .get(snapshot_id as usize)
+            .map(|(_, total_supply)| *total_supply)
+    }
+
+    /// Renders a raw amount in whole tokens, e.g. 1_500 with 3 decimals as "1.5"
+    fn format_amount(&self, raw: u64) -> String {
+        let decimals = self.decimals as usize;
+        let digits = format!("{:0>width$}", raw, width = decimals + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            whole.to_string()
+        } else {
+            format!("{}.{}", whole, fraction)
+        }
+    }
+
+    fn get_token_info(&self) -> (String, String, u64, u8) {
+        (
+            self.name.clone(),
+            self.symbol.clone(),
+            self.total_supply,
+            self.decimals,
+        )
+    }
+
+    fn operation_stats(&self) -> OperationStats {
+        self.stats
+    }
+
+    /// The whole state as JSON. Contract code and the clock are not part of it and
+    /// must be registered again after `from_json`.
+    #[cfg(feature = "serde")]
+    fn to_json(&self) -> Result<String, SnapshotError> {
+        serde_json::to_string(self).map_err(|error| SnapshotError::Json(error.to_string()))
+    }
+
+    /// Restores a state saved by `to_json`, refusing one whose accounting does not
+    /// hold
+    #[cfg(feature = "serde")]
+    fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        let token: TokenContract =
+            serde_json::from_str(json).map_err(|error| SnapshotError::Json(error.to_string()))?;
+        token.check_invariants()?;
+        Ok(token)
+    }
+
+    /// The invariants `assert_invariants` asserts, as an error for state that
+    /// comes from outside
+    fn check_invariants(&self) -> Result<(), SnapshotError> {
+        let balances: u128 = self.balances.values().map(|&balance| balance as u128).sum();
+        if balances != self.total_supply as u128 {
+            return Err(SnapshotError::SupplyMismatch {
+                balances,
+                total_supply: self.total_supply,
+            });
+        }
+        if let Some(owner) = self
+            .allowances
+            .keys()
+            .find(|owner| !self.balances.contains_key(*owner))
+        {
+            return Err(SnapshotError::UnknownAllowanceOwner(owner.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Checks the accounting invariants in debug builds: balances add up to the
+    /// total supply and every allowance belongs to a known account. Mint and burn
+    /// use "0x0" only as an event sentinel, it never holds a balance.
+    fn assert_invariants(&self) {
+        let sum: u128 = self.balances.values().map(|&balance| balance as u128).sum();
+        debug_assert_eq!(
+            sum, self.total_supply as u128,
+            "Sum of balances does not match total supply"
+        );
+        debug_assert!(
+            !self.balances.contains_key("0x0"),
+            "Sentinel account holds a balance"
+        );
+        for owner in self.allowances.keys() {
+            debug_assert!(
+                self.balances.contains_key(owner),
+                "Allowance references unknown owner {}",
+                owner
+            );
+        }
+    }
+
+    fn check_recipient(&self, to: &Address) -> Result<(), TokenError> {
+        if to.as_str() == "0x0" {
+            return Err(TokenError::InvalidRecipient(to.to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_not_frozen(&self, account: &Address) -> Result<(), TokenError> {
+        if self.is_frozen(account) {
+            return Err(TokenError::AccountFrozen(account.to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_not_paused(&self) -> Result<(), TokenError> {
+        if self.paused {
+            Err(TokenError::Paused)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_balance(&self, account: &Address, amount: u64) -> Result<(), TokenError> {
+        let available = self.spendable_balance(account);
+        if available < amount {
+            Err(TokenError::InsufficientBalance {
+                account: account.to_string(),
+                required: amount,
+                available,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_allowance(
+        &self,
+        owner: &Address,
+        spender: &Address,
+        amount: u64,
+    ) -> Result<(), TokenError> {
+        let available = self.allowance(owner, spender);
+        if available < amount {
+            Err(TokenError::InsufficientAllowance {
+                owner: owner.to_string(),
+                spender: spender.to_string(),
+                required: amount,
+                available,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn update_balances(
+        &mut self,
+        from: &Address,
+        to: &Address,
+        amount: u64,
+    ) -> Result<(), TokenError> {
+        let mut updated = HashMap::new();
+        self.stage_transfer(&mut updated, from, to, amount)?;
+        self.balances.extend(updated);
+        self.settle_transfer(from, to, amount);
+        Ok(())
+    }
+
+    /// Adds the new balances after a transfer to `updated`, on top of the ones
+    /// already staged there, without writing any. Computing them all first means
+    /// an overflow leaves the ledger untouched. The accounts may coincide, as in a
+    /// self-transfer, so each change applies on top of the previous ones.
+    fn stage_transfer(
+        &self,
+        updated: &mut HashMap<Address, u64>,
+        from: &Address,
+        to: &Address,
+        amount: u64,
+    ) -> Result<(), TokenError> {
+        let fee = self.transfer_fee(amount);
+        let mut changes = vec![(from, amount, 0), (to, 0, amount - fee)];
+        if fee > 0 {
+            changes.push((&self.treasury, 0, fee));
+        }
+        for (account, debit, credit) in changes {
+            let balance = updated
+                .get(account)
+                .copied()
+                .unwrap_or_else(|| self.balance_of(account))
+                .checked_sub(debit)
+                .ok_or(TokenError::Underflow)?
+                .checked_add(credit)
+                .ok_or(TokenError::Overflow)?;
+            updated.insert(account.clone(), balance);
+        }
+        Ok(())
+    }
+
+    /// Records the fee and burned tokens of a transfer whose balances are written
+    fn settle_transfer(&mut self, from: &Address, to: &Address, amount: u64) {
+        let fee = self.transfer_fee(amount);
+        if fee > 0 {
+            self.emit(TokenEvent::TransferFee {
+                from: from.to_string(),
+                treasury: self.treasury.to_string(),
+                amount: fee,
+            });
+        }
+        if self.is_burn_address(to) {
+            self.total_burned += amount - fee;
+        }
+    }
+
+    fn update_allowance(
+        &mut self,
+        owner: &Address,
+        spender: &Address,
+        amount: u64,
+    ) -> Result<(), TokenError> {
+        match self
+            .allowances
+            .get_mut(owner)
+            .and_then(|spenders| spenders.get_mut(spender))
+        {
+            Some(allowance) => {
+                *allowance = allowance.checked_sub(amount).ok_or(TokenError::Underflow)?;
+            }
+            // Only a zero amount can be spent without any approval on record
+            None => self.check_allowance(owner, spender, amount)?,
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, payload: TokenEvent) {
+        // The log is append-only, so the next position is the next sequence number
+        let position = self.events.len();
+        let event = Event {
+            sequence: position as u64,
+            timestamp: self.clock.as_ref().map(|clock| clock.now()),
+            payload,
+        };
+        for account in event.payload.accounts() {
+            self.events_by_account
+                .entry(account.to_string())
+                .or_default()
+                .push(position);
+        }
+        self.events.push(event);
+        self.unnotified.push(position);
+    }
+
+    /// Hands the observer the events emitted since the last notification. Called
+    /// once an operation has applied all its state changes, so the observer never
+    /// sees an event ahead of the state it describes.
+    fn notify_observer(&mut self) {
+        let positions = std::mem::take(&mut self.unnotified);
+        let Some(code) = self
+            .observer
+            .as_ref()
+            .and_then(|observer| self.contracts.get_mut(observer))
+        else {
+            return;
+        };
+        // Notifying the observer is best-effort: its failures never undo the
+        // operation that emitted the event
+        for position in positions {
+            let _ = code.on_state_change(&self.events[position]);
+        }
+    }
+
+    fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = Some(clock);
+    }
+
+    fn get_events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Events numbered `sequence` and later, so a consumer can resume after the
+    /// last event it processed
+    fn events_since(&self, sequence: u64) -> &[Event] {
+        let start = (sequence as usize).min(self.events.len());
+        &self.events[start..]
+    }
+
+    /// Events involving `account`, oldest first
+    fn events_for_account(&self, account: &Address) -> Vec<&Event> {
+        self.events_by_account
+            .get(account.as_str())
+            .map(|positions| {
+                positions
+                    .iter()
+                    .map(|&position| &self.events[position])
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn events_by_type(&self, kind: &str) -> Vec<&Event> {
+        self.events
+            .iter()
+            .filter(|event| event.payload.kind() == kind)
+            .collect()
+    }
+
+    /// Up to `limit` events from position `offset`, and the total number of events
+    fn events_page(&self, offset: usize, limit: usize) -> (&[Event], usize) {
+        let start = offset.min(self.events.len());
+        let end = start.saturating_add(limit).min(self.events.len());
+        (&self.events[start..end], self.events.len())
+    }
+
+    fn print_event_summary(&self) {
+        let mut transfer_volume = 0;
+        let mut mint_volume = 0;
+        let mut burn_volume = 0;
+
+        for event in &self.events {
+            match &event.payload {
+                TokenEvent::Transfer { amount, .. } | TokenEvent::TransferFrom { amount, .. } => {
+                    transfer_volume += amount
+                }
+                TokenEvent::Mint { amount, .. } => mint_volume += amount,
+                TokenEvent::Burn { amount, .. } => burn_volume += amount,
+                _ => {}
+            }
+        }
+
+        println!("Event Summary:");
+        if let (Some(first), Some(last)) = (self.events.first(), self.events.last()) {
+            println!("Sequences: {} to {}", first.sequence, last.sequence);
+        }
+        println!("Total Transfer Volume: {}", transfer_volume);
+        println!("Total Minted: {}", mint_volume);
+        println!("Total Burned: {}", burn_volume);
+    }
+}
+
+impl fmt::Debug for TokenContract {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenContract")
+            .field("name", &self.name)
+            .field("symbol", &self.symbol)
+            .field("decimals", &self.decimals)
+            .field("total_supply", &self.total_supply)
+            .field("balances", &self.balances)
+            .field("allowances", &self.allowances)
+            .finish()
+    }
+}
+
+/// Privileged token operation, run directly by a single owner, through the
+/// owners' multisig or after the timelock delay
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum OwnerOperation {
+    Mint {
+        to: Address,
+        amount: u64,
+    },
+    SetTransferFee {
+        fee_bps: u64,
+        min_fee: u64,
+        rounding: FeeRounding,
+    },
+    SetOwners {
+        owners: Vec<Address>,
+        threshold: usize,
+    },
+    GrantMinter {
+        account: Address,
+    },
+    RevokeMinter {
+        account: Address,
+    },
+    OpenStream {
+        recipient: Address,
+        rate_per_block: u64,
+        duration: u64,
+        start: u64,
+    },
+    ProposeOwner {
+        new_owner: Address,
+    },
+    RenounceOwnership,
+    Pause,
+    Unpause,
+    Freeze {
+        account: Address,
+    },
+    Unfreeze {
+        account: Address,
+    },
+    /// Replaces the name and/or the symbol
+    SetMetadata {
+        name: Option<String>,
+        symbol: Option<String>,
+    },
+    LockAddress {
+        address: Address,
+    },
+    UnlockAddress {
+        address: Address,
+    },
+    RegisterBurnAddress {
+        address: Address,
+    },
+    SetObserver {
+        observer: Option<Address>,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct QueuedOperation {
+    operation: OwnerOperation,
+    /// Block from which the operation may run
+    eta: u64,
+    confirmations: HashSet<Address>,
+}
+
+/// Wraps a `TokenContract` so that privileged operations are queued and only
+/// executable `delay` blocks later, giving holders time to react.
+///
+/// Time is the token's block counter. The timelock also takes over the token's
+/// multisig: a queued operation runs once `threshold` current owners confirmed
+/// it, while any single owner may cancel it.
+struct Timelock {
+    token: TokenContract,
+    delay: u64,
+    queue: HashMap<u64, QueuedOperation>,
+    next_id: u64,
+}
+
+impl Timelock {
+    fn new(token: TokenContract, delay: u64) -> Self {
+        Timelock {
+            token,
+            delay,
+            queue: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Queues `operation` with the caller's confirmation; the delay starts now
+    fn queue_operation(&mut self, caller: &Address, operation: OwnerOperation) -> Result<u64, TokenError> {
+        if !self.token.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        let eta = self
+            .token
+            .block_number()
+            .checked_add(self.delay)
+            .ok_or(TokenError::Overflow)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queue.insert(
+            id,
+            QueuedOperation {
+                operation,
+                eta,
+                confirmations: HashSet::from([caller.clone()]),
+            },
+        );
+        Ok(id)
+    }
+
+    fn confirm_operation(&mut self, caller: &Address, id: u64) -> Result<(), TokenError> {
+        if !self.token.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        let queued = self.queue.get_mut(&id).ok_or(TokenError::NotQueued(id))?;
+        if !queued.confirmations.insert(caller.clone()) {
+            return Err(TokenError::AlreadyConfirmed {
+                owner: caller.to_string(),
+                hash: id,
+            });
+        }
+        Ok(())
+    }
+
+    /// Carries out a queued operation once enough current owners confirmed it and
+    /// its delay has elapsed. An operation the token refuses stays queued.
+    fn execute_operation(&mut self, caller: &Address, id: u64) -> Result<(), TokenError> {
+        if !self.token.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        let queued = self.queue.get(&id).ok_or(TokenError::NotQueued(id))?;
+        let confirmed = queued
+            .confirmations
+            .iter()
+            .filter(|owner| self.token.is_owner(owner))
+            .count();
+        if confirmed < self.token.threshold {
+            return Err(TokenError::Unconfirmed {
+                id,
+                confirmed,
+                threshold: self.token.threshold,
+            });
+        }
+        if self.token.block_number() < queued.eta {
+            return Err(TokenError::Timelocked { id, eta: queued.eta });
+        }
+
+        self.token.apply_operation(caller, queued.operation.clone())?;
+        self.queue.remove(&id);
+        Ok(())
+    }
+
+    fn cancel_operation(&mut self, caller: &Address, id: u64) -> Result<(), TokenError> {
+        if !self.token.is_owner(caller) {
+            return Err(TokenError::NotOwner);
+        }
+        self.queue
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(TokenError::NotQueued(id))
+    }
+
+    fn advance_blocks(&mut self, blocks: u64) {
+        self.token.advance_blocks(blocks);
+    }
+
+    fn pending_operations(&self) -> Vec<(u64, &QueuedOperation)> {
+        let mut pending: Vec<_> = self.queue.iter().map(|(id, op)| (*id, op)).collect();
+        pending.sort_by_key(|(id, _)| *id);
+        pending
+    }
+
+    fn token(&self) -> &TokenContract {
+        &self.token
+    }
+}
+
+#[derive(Debug, Default)]
+struct RewardPool {
+    total_reward: u64,
+    claimed: HashSet<Address>,
+}
+
+/// Pull-based distribution of rewards: holders claim their pro-rata share of a
+/// pool according to their balance in a token snapshot, once per snapshot.
+struct Distributor {
+    address: Address,
+    pools: HashMap<u64, RewardPool>,
+}
+
+impl Distributor {
+    fn new(address: &Address) -> Self {
+        Distributor {
+            address: address.clone(),
+            pools: HashMap::new(),
+        }
+    }
+
+    /// Moves `amount` tokens from `from` into the reward pool of `snapshot_id` and
+    /// returns what the pool was credited: the tokens the distributor received,
+    /// net of any transfer fee, so the pool never promises more than it holds
+    fn fund(
+        &mut self,
+        token: &mut TokenContract,
+        from: &Address,
+        snapshot_id: u64,
+        amount: u64,
+    ) -> Result<u64, TokenError> {
+        if token.total_supply_at(snapshot_id).is_none() {
+            return Err(TokenError::UnknownSnapshot(snapshot_id));
+        }
+        let before = token.balance_of(&self.address);
+        token.transfer(from, &self.address, amount)?;
+        let received = token.balance_of(&self.address) - before;
+        self.pools.entry(snapshot_id).or_default().total_reward += received;
+        Ok(received)
+    }
+
+    /// Pays `holder` their share of the pool of `snapshot_id` and returns it
+    fn claim(
+        &mut self,
+        token: &mut TokenContract,
+        holder: &Address,
+        snapshot_id: u64,
+    ) -> Result<u64, TokenError> {
+        let pool = self
+            .pools
+            .get(&snapshot_id)
+            .ok_or(TokenError::NoRewards(snapshot_id))?;
+        if pool.claimed.contains(holder) {
+            return Err(TokenError::AlreadyClaimed {
+                account: holder.to_string(),
+                snapshot_id,
+            });
+        }
+
+        let share = self.claimable(token, holder, snapshot_id);
+        if share == 0 {
+            return Err(TokenError::NothingToClaim(holder.to_string()));
+        }
+        token.transfer(&self.address, holder, share)?;
+        self.pools
+            .get_mut(&snapshot_id)
+            .unwrap()
+            .claimed
+            .insert(holder.clone());
+        Ok(share)
+    }
+
+    fn claimable(&self, token: &TokenContract, holder: &Address, snapshot_id: u64) -> u64 {
+        let (Some(pool), Some(balance), Some(supply)) = (
+            self.pools.get(&snapshot_id),
+            token.balance_of_at(holder, snapshot_id),
+            token.total_supply_at(snapshot_id),
+        ) else {
+            return 0;
+        };
+        if supply == 0 || pool.claimed.contains(holder) {
+            return 0;
+        }
+        (pool.total_reward as u128 * balance as u128 / supply as u128) as u64
+    }
+}
+
+/// Airdrop of fixed amounts to a list of accounts, committed to by the merkle
+/// root of their `(account, amount)` leaves. Each account claims its own amount
+/// once by proving its leaf is in the tree, so the owner only funds the airdrop.
+///
+/// Nodes are SHA-256 hashes, so off-chain tools can build the tree:
+/// - leaf: `0x00 || len(account) as u64 BE || account UTF-8 || amount as u64 BE`
+/// - inner node: `0x01 || min(a, b) || max(a, b)`
+struct Airdrop {
+    address: Address,
+    merkle_root: [u8; 32],
+    claimed: HashSet<Address>,
+}
+
+impl Airdrop {
+    fn new(address: &Address, merkle_root: [u8; 32]) -> Self {
+        Airdrop {
+            address: address.clone(),
+            merkle_root,
+            claimed: HashSet::new(),
+        }
+    }
+
+    fn leaf_hash(account: &Address, amount: u64) -> [u8; 32] {
+        let account = account.as_str().as_bytes();
+        let mut hasher = Sha256::new();
+        // Tagged apart from inner nodes, so a node cannot pass for a leaf
+        hasher.update([0u8]);
+        hasher.update((account.len() as u64).to_be_bytes());
+        hasher.update(account);
+        hasher.update(amount.to_be_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Parent of two nodes. The pair is sorted, so proofs need no left/right flags.
+    fn node_hash(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update([1u8]);
+        hasher.update(a.min(b));
+        hasher.update(a.max(b));
+        hasher.finalize().into()
+    }
+
+    /// Whether `proof`, the sibling hashes from the leaf up, leads from the leaf of
+    /// `account` and `amount` to the merkle root
+    fn verify(&self, account: &Address, amount: u64, proof: &[[u8; 32]]) -> bool {
+        let root = proof
+            .iter()
+            .fold(Self::leaf_hash(account, amount), |node, sibling| {
+                Self::node_hash(node, *sibling)
+            });
+        root == self.merkle_root
+    }
+
+    /// Pays `account` its airdropped `amount` from the airdrop's balance
+    fn claim(
+        &mut self,
+        token: &mut TokenContract,
+        account: &Address,
+        amount: u64,
+        proof: &[[u8; 32]],
+    ) -> Result<(), TokenError> {
+        if self.claimed.contains(account) {
+            return Err(TokenError::AirdropClaimed(account.to_string()));
+        }
+        if !self.verify(account, amount, proof) {
+            return Err(TokenError::InvalidProof(account.to_string()));
+        }
+        token.transfer(&self.address, account, amount)?;
+        self.claimed.insert(account.clone());
+        Ok(())
+    }
+
+    fn is_claimed(&self, account: &Address) -> bool {
+        self.claimed.contains(account)
+    }
+}
+
+fn main() {
+    let mut token =
+        TokenContract::new_with_decimals("Yato".to_string(), "YTO".to_string(), 1_000_000, 3);
+
+    let (name, symbol, total_supply, decimals) = token.get_token_info();
+    println!(
+        "Token Info: {} ({}) - Total Supply: {} {} ({} decimals)",
+        name,
+        symbol,
+        token.format_amount(total_supply),
+        symbol,
+        decimals
+    );
+
+    println!("Initial state: {:?}", token);
+
+    let owner = Address::try_from("owner").unwrap();
+    let alice = Address::try_from("alice").unwrap();
+    let bob = Address::try_from("bob").unwrap();
+
+    // Perform some operations
+    token.transfer(&owner, &alice, 1000).unwrap();
+    token.approve(&alice, &bob, 500).unwrap();
+    token.transfer_from(&alice, &bob, &bob, 300).unwrap();
+    token.mint(&owner, &owner, 5000).unwrap();
+    token.burn(&owner, 2000).unwrap();
+
+    // Bob only holds what he took from Alice's allowance
+    if let Err(error) = token.transfer(&bob, &alice, 500) {
+        println!("Transfer rejected: {}", error);
+    }
+
+    // Account names are checked, so a typo cannot open a new account
+    if let Err(error) = Address::try_from("Alice") {
+        println!("Address rejected: {}", error);
+    }
+
+    println!("Final state: {:?}", token);
+    for account in [&owner, &alice, &bob] {
+        let balance = token.format_amount(token.balance_of(account));
+        println!("{}: {} {}", account, balance, symbol);
+    }
+
+    // Print all events
+    println!("Events:");
+    for event in token.get_events() {
+        println!("{:?}", event);
+    }
+
+    // Print event summary
+    token.print_event_summary();
+
+    println!("Operation Stats: {:?}", token.operation_stats());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use proptest::test_runner::RngSeed;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const ACCOUNTS: [&str; 4] = ["owner", "alice", "bob", "carol"];
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Transfer { from: usize, to: usize, amount: u64 },
+        TransferFrom { from: usize, to: usize, spender: usize, amount: u64 },
+        Approve { owner: usize, spender: usize, amount: u64 },
+        Mint { caller: usize, to: usize, amount: u64 },
+        Burn { from: usize, amount: u64 },
+    }
+
+    fn account() -> impl Strategy<Value = usize> {
+        0..ACCOUNTS.len()
+    }
+
+    // Large enough to hit the insufficient balance and allowance paths, small
+    // enough that a sequence of mints cannot overflow the total supply.
+    fn amount() -> impl Strategy<Value = u64> {
+        prop_oneof![0..1_000u64, 0..2_000_000u64]
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (account(), account(), amount()).prop_map(|(from, to, amount)| Op::Transfer { from, to, amount }),
+            (account(), account(), account(), amount())
+                .prop_map(|(from, to, spender, amount)| Op::TransferFrom { from, to, spender, amount }),
+            (account(), account(), amount()).prop_map(|(owner, spender, amount)| Op::Approve { owner, spender, amount }),
+            (account(), account(), amount()).prop_map(|(caller, to, amount)| Op::Mint { caller, to, amount }),
+            (account(), amount()).prop_map(|(from, amount)| Op::Burn { from, amount }),
+        ]
+    }
+
+    fn apply(token: &mut TokenContract, op: &Op) -> Result<(), TokenError> {
+        let account = |index: usize| addr(ACCOUNTS[index]);
+        match *op {
+            Op::Transfer { from, to, amount } => token.transfer(&account(from), &account(to), amount),
+            Op::TransferFrom { from, to, spender, amount } => {
+                token.transfer_from(&account(from), &account(to), &account(spender), amount)
+            }
+            Op::Approve { owner, spender, amount } => token.approve(&account(owner), &account(spender), amount),
+            Op::Mint { caller, to, amount } => token.mint(&account(caller), &account(to), amount),
+            Op::Burn { from, amount } => token.burn(&account(from), amount),
+        }
+    }
+
+    fn addr(name: &str) -> Address {
+        Address::try_from(name).unwrap()
+    }
+
+    type Accounting = (u64, HashMap<Address, u64>, HashMap<Address, HashMap<Address, u64>>);
+
+    fn accounting(token: &TokenContract) -> Accounting {
+        (token.total_supply, token.balances.clone(), token.allowances.clone())
+    }
+
+    #[test]
+    fn circulating_supply_excludes_locked_addresses() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("treasury"), 400_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.circulating_supply(), 1_000_000);
+
+        token.mark_locked_address(&addr("owner"), &addr("treasury")).unwrap();
+        assert_eq!(token.circulating_supply(), 600_000);
+        assert_eq!(token.total_supply, 1_000_000);
+        assert_eq!(token.mark_locked_address(&addr("alice"), &addr("treasury")), Err(TokenError::NotOwner));
+
+        token.unmark_locked_address(&addr("owner"), &addr("treasury")).unwrap();
+        assert_eq!(token.circulating_supply(), 1_000_000);
+        assert_eq!(
+            token.unmark_locked_address(&addr("owner"), &addr("treasury")),
+            Err(TokenError::NotLocked("treasury".to_string()))
+        );
+    }
+
+    #[test]
+    fn burn_addresses_are_counted_apart_from_supply_burns() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        assert_eq!(token.register_burn_address(&addr("alice"), &addr("dead")), Err(TokenError::NotOwner));
+        token.register_burn_address(&addr("owner"), &addr("dead")).unwrap();
+        assert!(token.is_burn_address(&addr("dead")));
+
+        token.transfer(&addr("owner"), &addr("dead"), 300).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.total_burned(), 300);
+        assert_eq!(token.total_supply, 1_000_000);
+        assert_eq!(token.operation_stats().burn_count, 0);
+
+        token.burn(&addr("owner"), 200).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.total_burned(), 300);
+        assert_eq!(token.total_supply, 999_800);
+
+        let locked = TokenError::InsufficientBalance { account: "dead".to_string(), required: 1, available: 0 };
+        assert_eq!(token.transfer(&addr("dead"), &addr("alice"), 1), Err(locked.clone()));
+        assert_eq!(token.burn(&addr("dead"), 1), Err(locked));
+        assert_eq!(token.balance_of(&addr("dead")), 300);
+    }
+
+    #[test]
+    fn transfer_fee_floor_applies_to_micro_transfers() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 10_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.set_transfer_fee(&addr("alice"), 30, 1, FeeRounding::Down), Err(TokenError::NotOwner));
+        assert_eq!(token.set_transfer_fee(&addr("owner"), 10_001, 1, FeeRounding::Down), Err(TokenError::FeeTooHigh));
+        token.set_transfer_fee(&addr("owner"), 30, 1, FeeRounding::Down).unwrap();
+
+        // 0.3% of 1 rounds to nothing, the floor still charges 1 and leaves the recipient 0
+        token.transfer(&addr("alice"), &addr("bob"), 1).unwrap();
+        token.assert_invariants();
+        assert_eq!((token.balance_of(&addr("alice")), token.balance_of(&addr("bob"))), (9_999, 0));
+        assert_eq!(token.balance_of(&addr("owner")), 990_001);
+
+        // 0.3% of 1_000 is exactly 3, above the floor
+        token.transfer(&addr("alice"), &addr("bob"), 1_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("bob")), 997);
+
+        // 0.3% of 500 is 1.5: rounded down to 1 or up to 2
+        token.transfer(&addr("alice"), &addr("carol"), 500).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("carol")), 499);
+        token.set_transfer_fee(&addr("owner"), 30, 1, FeeRounding::Up).unwrap();
+        token.transfer(&addr("alice"), &addr("carol"), 500).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("carol")), 997);
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn transfer_fee_never_exceeds_the_amount() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.set_transfer_fee(&addr("owner"), 30, 5, FeeRounding::Up).unwrap();
+        assert_eq!(token.transfer_fee(0), 0);
+        assert_eq!(token.transfer_fee(3), 3);
+        assert_eq!(token.transfer_fee(u64::MAX), (u64::MAX as u128 * 30).div_ceil(10_000) as u64);
+
+        token.transfer(&addr("owner"), &addr("alice"), 3).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("alice")), 0);
+        assert_eq!(token.balance_of(&addr("owner")), 1_000_000);
+    }
+
+    #[test]
+    fn two_of_three_owners_are_needed_to_mint() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let owners = vec![addr("owner"), addr("alice"), addr("bob")];
+        let set_owners = OwnerOperation::SetOwners { owners, threshold: 2 };
+        let hash = token.propose(&addr("owner"), set_owners).unwrap();
+        token.execute(&addr("owner"), hash).unwrap();
+        token.assert_invariants();
+
+        assert_eq!(
+            token.mint(&addr("owner"), &addr("owner"), 500),
+            Err(TokenError::NeedsConfirmations { threshold: 2 })
+        );
+        let mint = OwnerOperation::Mint { to: addr("carol"), amount: 500 };
+        assert_eq!(token.propose(&addr("carol"), mint.clone()), Err(TokenError::NotOwner));
+        let hash = token.propose(&addr("alice"), mint.clone()).unwrap();
+        assert_eq!(
+            token.execute(&addr("alice"), hash),
+            Err(TokenError::NotEnoughConfirmations { hash, confirmed: 1, threshold: 2 })
+        );
+        assert_eq!(
+            token.confirm(&addr("alice"), hash),
+            Err(TokenError::AlreadyConfirmed { owner: "alice".to_string(), hash })
+        );
+        assert_eq!(token.balance_of(&addr("carol")), 0);
+
+        token.confirm(&addr("bob"), hash).unwrap();
+        token.execute(&addr("alice"), hash).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("carol")), 500);
+        assert_eq!(token.total_supply, 1_000_500);
+        assert_eq!(token.execute(&addr("alice"), hash), Err(TokenError::NotProposed(hash)));
+    }
+
+    #[test]
+    fn owners_rename_the_token() {
+        let mut token = TokenContract::new_with_decimals("Yato".to_string(), "YTO".to_string(), 1_000, 3);
+        let owner = addr("owner");
+        assert_eq!(token.set_name(&addr("alice"), "Alice".to_string()), Err(TokenError::NotOwner));
+        assert_eq!(token.set_symbol(&addr("alice"), "ALC".to_string()), Err(TokenError::NotOwner));
+        assert_eq!(token.set_name(&owner, String::new()), Err(TokenError::EmptyMetadata));
+        assert_eq!(token.set_symbol(&owner, String::new()), Err(TokenError::EmptyMetadata));
+        assert!(token.events_by_type("MetadataChanged").is_empty());
+
+        token.set_name(&owner, "Yato Gold".to_string()).unwrap();
+        token.set_symbol(&owner, "YTG".to_string()).unwrap();
+        let changes: Vec<&TokenEvent> = token
+            .events_by_type("MetadataChanged")
+            .into_iter()
+            .map(|event| &event.payload)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                &TokenEvent::MetadataChanged {
+                    name: "Yato Gold".to_string(),
+                    symbol: "YTO".to_string(),
+                },
+                &TokenEvent::MetadataChanged {
+                    name: "Yato Gold".to_string(),
+                    symbol: "YTG".to_string(),
+                },
+            ]
+        );
+        assert_eq!(token.get_token_info(), ("Yato Gold".to_string(), "YTG".to_string(), 1_000, 3));
+    }
+
+    #[test]
+    fn operation_stats_count_only_successful_operations() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice, bob) = (addr("owner"), addr("alice"), addr("bob"));
+
+        // The scenario `main` runs, including its rejected transfer
+        token.transfer(&owner, &alice, 1000).unwrap();
+        token.assert_invariants();
+        token.approve(&alice, &bob, 500).unwrap();
+        token.assert_invariants();
+        token.transfer_from(&alice, &bob, &bob, 300).unwrap();
+        token.assert_invariants();
+        token.mint(&owner, &owner, 5000).unwrap();
+        token.assert_invariants();
+        token.burn(&owner, 2000).unwrap();
+        token.assert_invariants();
+        assert!(token.transfer(&bob, &alice, 500).is_err());
+        assert_eq!(
+            token.operation_stats(),
+            OperationStats {
+                transfer_count: 2,
+                mint_count: 1,
+                burn_count: 1,
+                approve_count: 1,
+            }
+        );
+
+        // Every leg of a batch counts, allowance adjustments count as approvals
+        token.batch_transfer(&owner, &[(&alice, 10), (&bob, 20)]).unwrap();
+        token.assert_invariants();
+        token.increase_allowance(&alice, &bob, 100).unwrap();
+        token.assert_invariants();
+        token.decrease_allowance(&alice, &bob, 50).unwrap();
+        token.assert_invariants();
+        token.burn_from(&alice, &bob, 50).unwrap();
+        token.assert_invariants();
+        assert!(token.mint(&alice, &alice, 1).is_err());
+        assert!(token.burn_from(&alice, &bob, 1_000).is_err());
+        assert_eq!(
+            token.operation_stats(),
+            OperationStats {
+                transfer_count: 4,
+                mint_count: 1,
+                burn_count: 2,
+                approve_count: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn two_of_three_owners_are_needed_to_pause_and_freeze() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let owners = vec![addr("owner"), addr("alice"), addr("bob")];
+        let set_owners = OwnerOperation::SetOwners { owners, threshold: 2 };
+        let hash = token.propose(&addr("owner"), set_owners).unwrap();
+        token.execute(&addr("owner"), hash).unwrap();
+        token.assert_invariants();
+
+        let needs_two = Err(TokenError::NeedsConfirmations { threshold: 2 });
+        let (owner, carol) = (addr("owner"), addr("carol"));
+        assert_eq!(token.pause(&owner), needs_two);
+        assert_eq!(token.unpause(&owner), needs_two);
+        assert_eq!(token.freeze_account(&owner, &carol), needs_two);
+        assert_eq!(token.unfreeze_account(&owner, &carol), needs_two);
+        assert_eq!(token.set_name(&owner, "Other".to_string()), needs_two);
+        assert_eq!(token.mark_locked_address(&owner, &carol), needs_two);
+        assert_eq!(token.unmark_locked_address(&owner, &carol), needs_two);
+        assert_eq!(token.register_burn_address(&owner, &carol), needs_two);
+        assert_eq!(token.set_observer(&owner, None), needs_two);
+        assert!(!token.is_paused() && !token.is_frozen(&carol));
+
+        let hash = token.propose(&addr("alice"), OwnerOperation::Pause).unwrap();
+        token.confirm(&addr("bob"), hash).unwrap();
+        token.execute(&addr("bob"), hash).unwrap();
+        token.assert_invariants();
+        assert!(token.is_paused());
+        let freeze = OwnerOperation::Freeze { account: carol.clone() };
+        let hash = token.propose(&owner, freeze).unwrap();
+        token.confirm(&addr("alice"), hash).unwrap();
+        token.execute(&owner, hash).unwrap();
+        token.assert_invariants();
+        assert!(token.is_frozen(&carol));
+
+        let paused_by: Vec<String> = token
+            .events_by_type("Paused")
+            .iter()
+            .map(|event| event.payload.accounts()[0].to_string())
+            .collect();
+        assert_eq!(paused_by, ["bob"]);
+    }
+
+    #[test]
+    fn overflowing_balances_are_rejected_untouched() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.mint(&addr("owner"), &addr("owner"), u64::MAX - 1_000_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("owner")), u64::MAX);
+        let before = accounting(&token);
+        assert_eq!(token.mint(&addr("owner"), &addr("owner"), 1), Err(TokenError::Overflow));
+        assert_eq!(accounting(&token), before);
+
+        token.transfer(&addr("owner"), &addr("alice"), u64::MAX - 1).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("alice")), u64::MAX - 1);
+
+        // Balances adding up to more than u64::MAX cannot arise through the API, so
+        // set one up directly to reach the recipient's guard
+        token.balances.insert(addr("bob"), 2);
+        let before = accounting(&token);
+        assert_eq!(token.transfer(&addr("bob"), &addr("alice"), 2), Err(TokenError::Overflow));
+        assert_eq!(accounting(&token), before);
+        token.transfer(&addr("bob"), &addr("alice"), 1).unwrap();
+        assert_eq!(token.balance_of(&addr("alice")), u64::MAX);
+    }
+
+    #[test]
+    fn arithmetic_at_the_u64_boundaries_is_checked() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 1_000_000).unwrap();
+        token.assert_invariants();
+        token.mint(&addr("owner"), &addr("owner"), u64::MAX / 2).unwrap();
+        token.assert_invariants();
+        let before = accounting(&token);
+        // The owner's balance would fit, the total supply would not
+        assert_eq!(token.mint(&addr("owner"), &addr("owner"), u64::MAX / 2), Err(TokenError::Overflow));
+        assert_eq!(accounting(&token), before);
+
+        token.burn(&addr("owner"), u64::MAX / 2).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("owner")), 0);
+        assert_eq!(token.total_supply, 1_000_000);
+
+        token.transfer(&addr("alice"), &addr("bob"), 1_000_000).unwrap();
+        token.assert_invariants();
+        assert_eq!((token.balance_of(&addr("alice")), token.balance_of(&addr("bob"))), (0, 1_000_000));
+        token.burn(&addr("bob"), 1_000_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.total_supply, 0);
+        assert_eq!(token.burn(&addr("bob"), 1), Err(TokenError::InsufficientBalance {
+            account: "bob".to_string(),
+            required: 1,
+            available: 0,
+        }));
+        token.approve(&addr("bob"), &addr("carol"), 1).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.update_allowance(&addr("bob"), &addr("carol"), 2), Err(TokenError::Underflow));
+        assert_eq!(token.allowance(&addr("bob"), &addr("carol")), 1);
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn pausing_stops_transfers_mints_and_burns() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice, bob) = (addr("owner"), addr("alice"), addr("bob"));
+        token.approve(&owner, &alice, 500).unwrap();
+        token.assert_invariants();
+        let before = accounting(&token);
+        assert_eq!(token.pause(&alice), Err(TokenError::NotOwner));
+        token.pause(&owner).unwrap();
+        assert!(token.is_paused());
+        assert_eq!(token.pause(&owner), Err(TokenError::Paused));
+        let events = token.get_events().len();
+
+        assert_eq!(token.transfer(&owner, &alice, 100), Err(TokenError::Paused));
+        assert_eq!(token.batch_transfer(&owner, &[(&alice, 100)]), Err(TokenError::Paused));
+        assert_eq!(token.transfer_from(&owner, &bob, &alice, 100), Err(TokenError::Paused));
+        assert_eq!(token.approve(&owner, &bob, 100), Err(TokenError::Paused));
+        assert_eq!(token.increase_allowance(&owner, &alice, 100), Err(TokenError::Paused));
+        assert_eq!(token.decrease_allowance(&owner, &alice, 100), Err(TokenError::Paused));
+        assert_eq!(token.mint(&owner, &owner, 100), Err(TokenError::Paused));
+        assert_eq!(token.burn(&owner, 100), Err(TokenError::Paused));
+        assert_eq!(token.burn_from(&owner, &alice, 100), Err(TokenError::Paused));
+        // Views keep working
+        assert_eq!(token.balance_of(&owner), 1_000_000);
+        assert_eq!(token.allowance(&owner, &alice), 500);
+        assert_eq!(token.get_events().len(), events);
+
+        assert_eq!(token.unpause(&alice), Err(TokenError::NotOwner));
+        token.unpause(&owner).unwrap();
+        assert_eq!(accounting(&token), before);
+        assert_eq!(token.unpause(&addr("owner")), Err(TokenError::NotPaused));
+        token.transfer(&addr("owner"), &addr("alice"), 100).unwrap();
+        token.assert_invariants();
+
+        let pauses: Vec<&str> = token
+            .get_events()
+            .iter()
+            .map(|event| event.payload.kind())
+            .filter(|kind| matches!(*kind, "Paused" | "Unpaused"))
+            .collect();
+        assert_eq!(pauses, ["Paused", "Unpaused"]);
+    }
+
+    #[test]
+    fn minters_mint_to_any_account_until_revoked() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.mint(&addr("owner"), &addr("alice"), 100).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.mint(&addr("bob"), &addr("carol"), 50), Err(TokenError::NotOwner));
+        assert_eq!(token.grant_minter(&addr("alice"), &addr("bob")), Err(TokenError::NotOwner));
+
+        token.grant_minter(&addr("owner"), &addr("bob")).unwrap();
+        assert_eq!(token.grant_minter(&addr("owner"), &addr("bob")), Err(TokenError::AlreadyMinter("bob".to_string())));
+        token.mint(&addr("bob"), &addr("carol"), 50).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("alice")), 100);
+        assert_eq!(token.balance_of(&addr("carol")), 50);
+        assert_eq!(token.balance_of(&addr("bob")), 0);
+
+        token.revoke_minter(&addr("owner"), &addr("bob")).unwrap();
+        assert_eq!(token.revoke_minter(&addr("owner"), &addr("bob")), Err(TokenError::NotMinter("bob".to_string())));
+        assert_eq!(token.mint(&addr("bob"), &addr("carol"), 50), Err(TokenError::NotOwner));
+        assert_eq!(token.total_supply, 1_000_150);
+
+        let roles: Vec<&str> = token
+            .get_events()
+            .iter()
+            .map(|event| event.payload.kind())
+            .filter(|kind| kind.starts_with("Minter"))
+            .collect();
+        assert_eq!(roles, ["MinterAdded", "MinterRemoved"]);
+    }
+
+    #[test]
+    fn burn_from_needs_both_balance_and_allowance() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 100).unwrap();
+        token.assert_invariants();
+        token.approve(&addr("alice"), &addr("bob"), 500).unwrap();
+        token.assert_invariants();
+        let before = accounting(&token);
+        assert_eq!(
+            token.burn_from(&addr("alice"), &addr("bob"), 200),
+            Err(TokenError::InsufficientBalance { account: "alice".to_string(), required: 200, available: 100 })
+        );
+        assert_eq!(accounting(&token), before);
+        token.transfer(&addr("owner"), &addr("alice"), 900).unwrap();
+        token.assert_invariants();
+        let before = accounting(&token);
+        assert_eq!(
+            token.burn_from(&addr("alice"), &addr("bob"), 600),
+            Err(TokenError::InsufficientAllowance {
+                owner: "alice".to_string(),
+                spender: "bob".to_string(),
+                required: 600,
+                available: 500,
+            })
+        );
+        assert_eq!(accounting(&token), before);
+
+        token.burn_from(&addr("alice"), &addr("bob"), 300).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("alice")), 700);
+        assert_eq!(token.allowance(&addr("alice"), &addr("bob")), 200);
+        assert_eq!(token.total_supply, 999_700);
+        let burn = TokenEvent::Burn { from: "alice".to_string(), spender: Some("bob".to_string()), amount: 300 };
+        assert_eq!(token.get_events().last().map(|event| &event.payload), Some(&burn));
+        assert_eq!(burn.to_string(), "Event { type: Burn, from: alice, to: 0x0, amount: 300, spender: bob }");
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn burn_from_consumes_the_allowance_exactly() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let no_allowance = TokenError::InsufficientAllowance {
+            owner: "owner".to_string(),
+            spender: "bob".to_string(),
+            required: 1,
+            available: 0,
+        };
+        assert_eq!(token.burn_from(&addr("owner"), &addr("bob"), 1), Err(no_allowance.clone()));
+        assert_eq!(token.update_allowance(&addr("owner"), &addr("bob"), 1), Err(no_allowance));
+        assert_eq!(token.update_allowance(&addr("owner"), &addr("bob"), 0), Ok(()));
+
+        token.approve(&addr("owner"), &addr("bob"), 400).unwrap();
+        token.assert_invariants();
+        assert!(matches!(
+            token.burn_from(&addr("owner"), &addr("bob"), 401),
+            Err(TokenError::InsufficientAllowance { required: 401, available: 400, .. })
+        ));
+        token.burn_from(&addr("owner"), &addr("bob"), 400).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.allowance(&addr("owner"), &addr("bob")), 0);
+        assert_eq!(token.total_supply, 999_600);
+        assert!(token.burn_from(&addr("owner"), &addr("bob"), 1).is_err());
+        assert_eq!(token.operation_stats().burn_count, 1);
+    }
+
+    #[test]
+    fn allowances_are_adjusted_by_deltas() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let before = accounting(&token);
+        assert_eq!(
+            token.decrease_allowance(&addr("alice"), &addr("bob"), 1),
+            Err(TokenError::InsufficientAllowance {
+                owner: "alice".to_string(),
+                spender: "bob".to_string(),
+                required: 1,
+                available: 0,
+            })
+        );
+        assert_eq!(accounting(&token), before);
+
+        token.increase_allowance(&addr("alice"), &addr("bob"), 300).unwrap();
+        token.assert_invariants();
+        token.increase_allowance(&addr("alice"), &addr("bob"), 200).unwrap();
+        token.assert_invariants();
+        token.decrease_allowance(&addr("alice"), &addr("bob"), 100).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.allowance(&addr("alice"), &addr("bob")), 400);
+        assert_eq!(token.increase_allowance(&addr("alice"), &addr("bob"), u64::MAX), Err(TokenError::Overflow));
+
+        // Approval events carry the resulting allowance, not the delta
+        let approvals: Vec<u64> = token
+            .get_events()
+            .iter()
+            .filter_map(|event| match &event.payload {
+                TokenEvent::Approval { amount, .. } => Some(*amount),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(approvals, [300, 500, 400]);
+        token.decrease_allowance(&addr("alice"), &addr("bob"), 400).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.allowance(&addr("alice"), &addr("bob")), 0);
+    }
+
+    #[test]
+    fn self_transfer_of_the_full_balance_changes_nothing() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 1_000).unwrap();
+        token.assert_invariants();
+        token.approve(&addr("alice"), &addr("bob"), 1_000).unwrap();
+        token.assert_invariants();
+        let before = accounting(&token).1;
+
+        token.transfer(&addr("alice"), &addr("alice"), 1_000).unwrap();
+        token.assert_invariants();
+        token.transfer_from(&addr("alice"), &addr("alice"), &addr("bob"), 1_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("alice")), 1_000);
+        assert_eq!(accounting(&token).1, before);
+        // Still a transfer: the events are emitted and the allowance is spent
+        assert_eq!(token.get_events().last().map(|event| event.payload.kind()), Some("TransferFrom"));
+        assert_eq!(token.allowance(&addr("alice"), &addr("bob")), 0);
+        assert_eq!(token.operation_stats().transfer_count, 3);
+    }
+
+    #[test]
+    fn errors_report_the_amounts_involved() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 300).unwrap();
+        token.assert_invariants();
+        let error = token.transfer(&addr("alice"), &addr("bob"), 500).unwrap_err();
+        assert_eq!(error.to_string(), "Insufficient balance for alice: 500 required, 300 available");
+        let error = token.transfer_from(&addr("alice"), &addr("bob"), &addr("carol"), 100).unwrap_err();
+        assert_eq!(error.to_string(), "Insufficient allowance for carol from alice: 100 required, 0 available");
+
+        // Usable wherever a standard error is expected
+        let error: Box<dyn std::error::Error> = Box::new(token.mint(&addr("alice"), &addr("alice"), 1).unwrap_err());
+        assert_eq!(error.to_string(), "Caller is not an owner");
+    }
+
+    #[test]
+    fn approval_events_show_the_replaced_allowance() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 1_000).unwrap();
+        token.assert_invariants();
+        token.approve(&addr("alice"), &addr("bob"), 500).unwrap();
+        token.assert_invariants();
+        token.transfer_from(&addr("alice"), &addr("bob"), &addr("bob"), 500).unwrap();
+        token.assert_invariants();
+        token.approve(&addr("alice"), &addr("bob"), 200).unwrap();
+        token.assert_invariants();
+
+        // The 500 was spent before it was lowered to 200
+        let approvals: Vec<(u64, u64)> = token
+            .get_events()
+            .iter()
+            .filter_map(|event| match &event.payload {
+                TokenEvent::Approval { amount, prev_amount, .. } => Some((*prev_amount, *amount)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(approvals, [(0, 500), (0, 200)]);
+        assert_eq!(
+            token.get_events().last().unwrap().payload.to_string(),
+            "Event { type: Approval, from: alice, to: bob, amount: 200, previous: 0 }"
+        );
+
+        token.increase_allowance(&addr("alice"), &addr("bob"), 50).unwrap();
+        token.assert_invariants();
+        assert!(matches!(
+            token.get_events().last().map(|event| &event.payload),
+            Some(TokenEvent::Approval { amount: 250, prev_amount: 200, .. })
+        ));
+    }
+
+    #[test]
+    fn account_index_matches_a_full_scan() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.set_transfer_fee(&addr("owner"), 10, 1, FeeRounding::Down).unwrap();
+        for round in 0..60u64 {
+            let (from, to) = (&addr(ACCOUNTS[round as usize % 4]), &addr(ACCOUNTS[(round as usize + 1) % 4]));
+            let _ = token.transfer(&addr("owner"), from, 1_000);
+            let _ = token.transfer(from, to, round);
+            let _ = token.approve(from, to, round * 2);
+            let _ = token.transfer_from(from, from, to, round);
+            let _ = token.burn_from(from, to, 1);
+        }
+        assert!(token.get_events().len() > 300);
+
+        for account in ACCOUNTS.iter().chain(&["dave"]) {
+            let scanned: Vec<&Event> = token
+                .get_events()
+                .iter()
+                .filter(|event| event.payload.accounts().contains(account))
+                .collect();
+            assert_eq!(token.events_for_account(&addr(account)), scanned, "{}", account);
+        }
+        let fees = token.events_by_type("TransferFee");
+        assert!(!fees.is_empty());
+        assert!(fees.iter().all(|event| matches!(event.payload, TokenEvent::TransferFee { .. })));
+
+        let total = token.get_events().len();
+        let (page, count) = token.events_page(10, 25);
+        assert_eq!((page, count), (&token.get_events()[10..35], total));
+        assert_eq!(token.events_page(total - 5, 25).0.len(), 5);
+        assert!(token.events_page(total + 1, usize::MAX).0.is_empty());
+    }
+
+    #[test]
+    fn emptied_accounts_drop_out_of_the_holders() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 300).unwrap();
+        token.assert_invariants();
+        token.transfer(&addr("owner"), &addr("bob"), 300).unwrap();
+        token.assert_invariants();
+        token.transfer(&addr("owner"), &addr("carol"), 500).unwrap();
+        token.assert_invariants();
+        token.approve(&addr("carol"), &addr("bob"), 100).unwrap();
+        token.assert_invariants();
+        let holders = |token: &TokenContract| {
+            token
+                .holders()
+                .into_iter()
+                .map(|(account, balance)| format!("{}:{}", account, balance))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(holders(&token), ["owner:998900", "carol:500", "alice:300", "bob:300"]);
+
+        token.transfer(&addr("carol"), &addr("alice"), 500).unwrap();
+        token.assert_invariants();
+        token.burn(&addr("bob"), 300).unwrap();
+        token.assert_invariants();
+        assert_eq!(holders(&token), ["owner:998900", "alice:800"]);
+        assert_eq!(token.holder_count(), 2);
+        token.assert_invariants();
+    }
+
+    /// Clock the test moves by hand
+    struct FakeClock(Rc<RefCell<u64>>);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            *self.0.borrow()
+        }
+    }
+
+    #[test]
+    fn events_are_numbered_and_timestamped() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 100).unwrap();
+        token.assert_invariants();
+        let time = Rc::new(RefCell::new(1_700));
+        token.set_clock(Box::new(FakeClock(Rc::clone(&time))));
+        token.approve(&addr("alice"), &addr("bob"), 50).unwrap();
+        token.assert_invariants();
+        *time.borrow_mut() = 1_760;
+        token.transfer_from(&addr("alice"), &addr("carol"), &addr("bob"), 50).unwrap();
+        token.assert_invariants();
+
+        let stamps: Vec<(u64, Option<u64>)> = token
+            .get_events()
+            .iter()
+            .map(|event| (event.sequence, event.timestamp))
+            .collect();
+        assert_eq!(stamps, [(0, None), (1, Some(1_700)), (2, Some(1_760))]);
+        assert_eq!(
+            format!("{:?}", token.get_events()[1]),
+            "#1 @1700 Event { type: Approval, from: alice, to: bob, amount: 50, previous: 0 }"
+        );
+
+        // A consumer that processed up to #1 resumes from #2
+        let newer = token.events_since(2);
+        assert_eq!(newer.len(), 1);
+        assert_eq!(newer[0].payload.kind(), "TransferFrom");
+        assert!(token.events_since(3).is_empty());
+        assert_eq!(token.events_since(0).len(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_snapshot_round_trips_and_rejects_tampering() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.transfer(&addr("owner"), &addr("alice"), 1_000).unwrap();
+        token.assert_invariants();
+        token.approve(&addr("alice"), &addr("bob"), 500).unwrap();
+        token.assert_invariants();
+        token.set_transfer_fee(&addr("owner"), 30, 1, FeeRounding::Up).unwrap();
+        token.snapshot();
+        let json = token.to_json().unwrap();
+
+        let mut restored = TokenContract::from_json(&json).unwrap();
+        assert_eq!(accounting(&restored), accounting(&token));
+        assert_eq!(restored.get_events(), token.get_events());
+        assert_eq!(restored.balance_of_at(&addr("alice"), 0), Some(1_000));
+        restored.transfer_from(&addr("alice"), &addr("carol"), &addr("bob"), 500).unwrap();
+        assert_eq!(restored.balance_of(&addr("carol")), 498);
+
+        let inflated = json.replacen("\"alice\":1000", "\"alice\":9000", 1);
+        assert_ne!(inflated, json);
+        assert!(matches!(
+            TokenContract::from_json(&inflated),
+            Err(SnapshotError::SupplyMismatch { balances: 1_008_000, total_supply: 1_000_000 })
+        ));
+        let negative = json.replacen("\"bob\":500", "\"bob\":-500", 1);
+        assert!(matches!(TokenContract::from_json(&negative), Err(SnapshotError::Json(_))));
+    }
+
+    #[test]
+    fn batch_transfer_is_all_or_nothing() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (alice, bob, carol) = (addr("alice"), addr("bob"), addr("carol"));
+        token.transfer(&addr("owner"), &alice, 1_000).unwrap();
+        token.assert_invariants();
+        let events = token.get_events().len();
+        let before = accounting(&token);
+
+        let payouts = [(&bob, 100), (&carol, 200), (&addr("0x0"), 50), (&addr("dave"), 300), (&addr("erin"), 100)];
+        assert_eq!(
+            token.batch_transfer(&alice, &payouts),
+            Err(TokenError::InvalidRecipient("0x0".to_string()))
+        );
+        assert_eq!((token.balance_of(&bob), token.balance_of(&carol)), (0, 0));
+        assert_eq!(
+            token.batch_transfer(&alice, &[(&bob, 600), (&carol, 500)]),
+            Err(TokenError::InsufficientBalance { account: "alice".to_string(), required: 1_100, available: 1_000 })
+        );
+        assert_eq!(token.batch_transfer(&alice, &[(&bob, u64::MAX), (&carol, 1)]), Err(TokenError::Overflow));
+        assert_eq!(accounting(&token), before);
+        assert_eq!(token.get_events().len(), events);
+
+        token.set_transfer_fee(&addr("owner"), 100, 0, FeeRounding::Down).unwrap();
+        token.batch_transfer(&alice, &[(&bob, 100), (&carol, 200), (&bob, 300)]).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&alice), 400);
+        assert_eq!((token.balance_of(&bob), token.balance_of(&carol)), (396, 198));
+        let transfers = token.events_by_type("Transfer").len();
+        assert_eq!(transfers, 4);
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn malformed_addresses_are_rejected() {
+        assert_eq!(Address::try_from(""), Err(AddressError::Empty));
+        assert_eq!(Address::try_from("ali ce"), Err(AddressError::InvalidCharacter(' ')));
+        assert_eq!(Address::try_from("Alice"), Err(AddressError::InvalidCharacter('A')));
+        assert_eq!(Address::try_from("a".repeat(65).as_str()), Err(AddressError::TooLong(65)));
+        assert_eq!(format!("0x{}", "f".repeat(65)).parse::<Address>(), Err(AddressError::TooLong(65)));
+        assert!(Address::try_from("a".repeat(64).as_str()).is_ok());
+        assert_eq!("0xDEADbeef".parse::<Address>().map(|address| address.to_string()), Ok("0xdeadbeef".to_string()));
+        assert_eq!(Address::try_from("0xDEADBEEF"), Address::try_from("0xdeadbeef"));
+
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        assert_eq!(token.balance_of(&addr("alicee")), 0);
+        assert_eq!(token.balance_of(&addr("owner")), 1_000_000);
+        // Both spellings of a hex address credit the same account
+        token.transfer(&addr("owner"), &addr("0xABC"), 10).unwrap();
+        token.transfer(&addr("owner"), &addr("0xabc"), 5).unwrap();
+        assert_eq!(token.balance_of(&addr("0xAbC")), 15);
+        assert_eq!(token.balance_of(&addr("owner")), 999_985);
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn ownership_moves_only_once_accepted() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice, bob) = (addr("owner"), addr("alice"), addr("bob"));
+        assert_eq!(token.propose_owner(&alice, &alice), Err(TokenError::NotOwner));
+        assert_eq!(token.accept_ownership(&alice), Err(TokenError::NotProposedOwner("alice".to_string())));
+        token.propose_owner(&owner, &addr("alicee")).unwrap();
+        // A typo is fixed by proposing again; the owner keeps minting meanwhile
+        token.propose_owner(&owner, &alice).unwrap();
+        assert_eq!(token.accept_ownership(&bob), Err(TokenError::NotProposedOwner("bob".to_string())));
+        token.mint(&owner, &bob, 100).unwrap();
+        token.assert_invariants();
+
+        token.accept_ownership(&alice).unwrap();
+        assert_eq!(token.mint(&owner, &bob, 100), Err(TokenError::NotOwner));
+        token.mint(&alice, &bob, 100).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.accept_ownership(&alice), Err(TokenError::NotProposedOwner("alice".to_string())));
+        assert_eq!(token.balance_of(&bob), 200);
+
+        let transfers: Vec<String> = token
+            .get_events()
+            .iter()
+            .filter_map(|event| match &event.payload {
+                TokenEvent::OwnershipProposed { proposed } => Some(format!("proposed:{}", proposed)),
+                TokenEvent::OwnershipTransferred { new_owner } => Some(format!("owner:{}", new_owner)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(transfers, ["proposed:alicee", "proposed:alice", "owner:alice"]);
+    }
+
+    #[test]
+    fn renouncing_ownership_disables_minting_for_good() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice) = (addr("owner"), addr("alice"));
+        token.grant_minter(&owner, &alice).unwrap();
+        token.propose_owner(&owner, &alice).unwrap();
+        assert_eq!(token.renounce_ownership(&alice), Err(TokenError::NotOwner));
+        token.renounce_ownership(&owner).unwrap();
+
+        assert_eq!(token.mint(&owner, &owner, 100), Err(TokenError::NotOwner));
+        assert_eq!(token.mint(&alice, &alice, 100), Err(TokenError::NotOwner));
+        assert_eq!(token.accept_ownership(&alice), Err(TokenError::NotProposedOwner("alice".to_string())));
+        assert_eq!(token.propose_owner(&owner, &alice), Err(TokenError::NotOwner));
+        assert_eq!(token.total_supply, 1_000_000);
+        assert!(matches!(
+            token.get_events().last().map(|event| &event.payload),
+            Some(TokenEvent::OwnershipTransferred { new_owner }) if new_owner == "0x0"
+        ));
+    }
+
+    #[test]
+    fn frozen_accounts_cannot_send_receive_or_burn() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice, bob) = (addr("owner"), addr("alice"), addr("bob"));
+        token.transfer(&owner, &alice, 1_000).unwrap();
+        token.assert_invariants();
+        token.approve(&alice, &bob, 500).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.freeze_account(&alice, &bob), Err(TokenError::NotOwner));
+        token.freeze_account(&owner, &alice).unwrap();
+        assert_eq!(token.freeze_account(&owner, &alice), Err(TokenError::AlreadyFrozen("alice".to_string())));
+        assert!(token.is_frozen(&alice));
+
+        let before = accounting(&token);
+        let frozen = Err(TokenError::AccountFrozen("alice".to_string()));
+        assert_eq!(token.transfer(&alice, &bob, 100), frozen);
+        assert_eq!(token.transfer(&bob, &alice, 0), frozen);
+        assert_eq!(token.transfer(&owner, &alice, 100), frozen);
+        assert_eq!(token.transfer_from(&alice, &bob, &bob, 100), frozen);
+        assert_eq!(token.batch_transfer(&owner, &[(&bob, 100), (&alice, 100)]), frozen);
+        assert_eq!(token.burn(&alice, 100), frozen);
+        assert_eq!(token.burn_from(&alice, &bob, 100), frozen);
+        assert_eq!(accounting(&token), before);
+        assert_eq!(token.balance_of(&alice), 1_000);
+
+        // Accounts that never held tokens can be frozen ahead of time
+        token.freeze_account(&owner, &addr("dave")).unwrap();
+        assert_eq!(token.transfer(&owner, &addr("dave"), 1), Err(TokenError::AccountFrozen("dave".to_string())));
+
+        token.unfreeze_account(&owner, &alice).unwrap();
+        assert_eq!(token.unfreeze_account(&owner, &alice), Err(TokenError::NotFrozen("alice".to_string())));
+        token.transfer(&alice, &bob, 100).unwrap();
+        token.assert_invariants();
+        token.transfer_from(&alice, &bob, &bob, 100).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&bob), 200);
+        let kinds: Vec<&str> = token
+            .get_events()
+            .iter()
+            .map(|event| event.payload.kind())
+            .filter(|kind| kind.starts_with("Account"))
+            .collect();
+        assert_eq!(kinds, ["AccountFrozen", "AccountFrozen", "AccountUnfrozen"]);
+    }
+
+    #[test]
+    fn spendable_balance_deducts_vesting_and_freezes() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice, bob) = (addr("owner"), addr("alice"), addr("bob"));
+        token.transfer(&owner, &alice, 1_000).unwrap();
+        token.assert_invariants();
+        token.transfer_vested(&owner, &alice, 600, 100).unwrap();
+        token.assert_invariants();
+        token.transfer_vested(&owner, &alice, 400, 200).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&alice), 2_000);
+        assert_eq!(token.spendable_balance(&alice), 1_000);
+        assert_eq!(
+            token.transfer(&alice, &bob, 1_001),
+            Err(TokenError::InsufficientBalance {
+                account: "alice".to_string(),
+                required: 1_001,
+                available: 1_000,
+            })
+        );
+
+        token.freeze_account(&owner, &alice).unwrap();
+        assert_eq!(token.spendable_balance(&alice), 0);
+        token.advance_blocks(100);
+        assert_eq!(token.spendable_balance(&alice), 0);
+        token.unfreeze_account(&owner, &alice).unwrap();
+
+        // The first grant vested while the account was frozen
+        assert_eq!(token.spendable_balance(&alice), 1_600);
+        token.transfer(&alice, &bob, 1_600).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.spendable_balance(&alice), 0);
+        assert!(matches!(
+            token.burn(&alice, 1),
+            Err(TokenError::InsufficientBalance { available: 0, .. })
+        ));
+        token.advance_blocks(100);
+        assert_eq!(token.spendable_balance(&alice), 400);
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn distributor_pays_each_holder_its_snapshot_share_once() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice, bob, carol) = (addr("owner"), addr("alice"), addr("bob"), addr("carol"));
+        let mut distributor = Distributor::new(&addr("distributor"));
+        token.transfer(&owner, &alice, 200_000).unwrap();
+        token.assert_invariants();
+        token.transfer(&owner, &bob, 300_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(distributor.fund(&mut token, &owner, 0, 10_000), Err(TokenError::UnknownSnapshot(0)));
+        let snapshot_id = token.snapshot();
+        assert_eq!(distributor.claim(&mut token, &alice, snapshot_id), Err(TokenError::NoRewards(snapshot_id)));
+
+        // Moves after the snapshot do not change the shares
+        token.transfer(&bob, &carol, 300_000).unwrap();
+        token.assert_invariants();
+        distributor.fund(&mut token, &owner, snapshot_id, 10_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(distributor.claimable(&token, &bob, snapshot_id), 3_000);
+        assert_eq!(distributor.claim(&mut token, &alice, snapshot_id), Ok(2_000));
+        assert_eq!(distributor.claim(&mut token, &bob, snapshot_id), Ok(3_000));
+        assert_eq!(distributor.claim(&mut token, &owner, snapshot_id), Ok(5_000));
+        assert_eq!(
+            distributor.claim(&mut token, &carol, snapshot_id),
+            Err(TokenError::NothingToClaim("carol".to_string()))
+        );
+        assert_eq!(
+            distributor.claim(&mut token, &alice, snapshot_id),
+            Err(TokenError::AlreadyClaimed {
+                account: "alice".to_string(),
+                snapshot_id,
+            })
+        );
+        assert_eq!(distributor.claimable(&token, &alice, snapshot_id), 0);
+
+        assert_eq!(token.balance_of(&alice), 202_000);
+        assert_eq!(token.balance_of(&bob), 3_000);
+        assert_eq!(token.balance_of(&addr("distributor")), 0);
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn distributor_funds_what_it_receives_after_the_fee() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let (owner, alice, bob) = (addr("owner"), addr("alice"), addr("bob"));
+        let mut distributor = Distributor::new(&addr("distributor"));
+        token.transfer(&owner, &alice, 200_000).unwrap();
+        token.transfer(&owner, &bob, 300_000).unwrap();
+        token.assert_invariants();
+        let snapshot_id = token.snapshot();
+
+        // A 1% fee leaves the distributor 9_900 of the 10_000 sent
+        token.set_transfer_fee(&owner, 100, 0, FeeRounding::Down).unwrap();
+        assert_eq!(distributor.fund(&mut token, &owner, snapshot_id, 10_000), Ok(9_900));
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("distributor")), 9_900);
+        assert_eq!(distributor.claimable(&token, &alice, snapshot_id), 1_980);
+
+        // The shares add up to the balance, so the last holder is paid in full
+        assert_eq!(distributor.claim(&mut token, &alice, snapshot_id), Ok(1_980));
+        assert_eq!(distributor.claim(&mut token, &bob, snapshot_id), Ok(2_970));
+        assert_eq!(distributor.claim(&mut token, &owner, snapshot_id), Ok(4_950));
+        assert_eq!(token.balance_of(&addr("distributor")), 0);
+        token.assert_invariants();
+    }
+
+    #[test]
+    fn timelocked_operations_wait_for_the_delay() {
+        let token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let mut timelock = Timelock::new(token, 50);
+        let mint = OwnerOperation::Mint {
+            to: addr("alice"),
+            amount: 500,
+        };
+        assert_eq!(
+            timelock.queue_operation(&addr("alice"), mint.clone()),
+            Err(TokenError::NotOwner)
+        );
+        timelock.advance_blocks(100);
+        let minted = timelock.queue_operation(&addr("owner"), mint).unwrap();
+        timelock.advance_blocks(10);
+        let paused = timelock.queue_operation(&addr("owner"), OwnerOperation::Pause).unwrap();
+        let pending: Vec<(u64, u64)> = timelock
+            .pending_operations()
+            .iter()
+            .map(|(id, queued)| (*id, queued.eta))
+            .collect();
+        assert_eq!(pending, [(minted, 150), (paused, 160)]);
+
+        // Holders see the mint coming; only the token's clock can bring it due
+        timelock.advance_blocks(39);
+        assert_eq!(
+            timelock.execute_operation(&addr("owner"), minted),
+            Err(TokenError::Timelocked { id: minted, eta: 150 })
+        );
+        assert_eq!(timelock.token().balance_of(&addr("alice")), 0);
+        timelock.advance_blocks(1);
+        timelock.execute_operation(&addr("owner"), minted).unwrap();
+        assert_eq!(timelock.token().balance_of(&addr("alice")), 500);
+        assert_eq!(timelock.token().total_supply, 1_000_500);
+        timelock.token().assert_invariants();
+        assert_eq!(
+            timelock.execute_operation(&addr("owner"), minted),
+            Err(TokenError::NotQueued(minted))
+        );
+
+        // A cancelled pause never takes effect
+        assert_eq!(timelock.cancel_operation(&addr("alice"), paused), Err(TokenError::NotOwner));
+        timelock.cancel_operation(&addr("owner"), paused).unwrap();
+        assert!(timelock.pending_operations().is_empty());
+        timelock.advance_blocks(50);
+        assert_eq!(
+            timelock.execute_operation(&addr("owner"), paused),
+            Err(TokenError::NotQueued(paused))
+        );
+        assert_eq!(timelock.cancel_operation(&addr("owner"), paused), Err(TokenError::NotQueued(paused)));
+        assert!(!timelock.token().is_paused());
+
+        let paused = timelock.queue_operation(&addr("owner"), OwnerOperation::Pause).unwrap();
+        timelock.advance_blocks(50);
+        timelock.execute_operation(&addr("owner"), paused).unwrap();
+        assert!(timelock.token().is_paused());
+        timelock.token().assert_invariants();
+
+        let token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000);
+        let mut timelock = Timelock::new(token, u64::MAX);
+        timelock.advance_blocks(1);
+        assert_eq!(
+            timelock.queue_operation(&addr("owner"), OwnerOperation::Pause),
+            Err(TokenError::Overflow)
+        );
+    }
+
+    #[test]
+    fn timelocked_operations_need_the_owner_threshold() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let owners = vec![addr("owner"), addr("bob"), addr("carol")];
+        token
+            .apply_operation(&addr("owner"), OwnerOperation::SetOwners { owners, threshold: 2 })
+            .unwrap();
+        let mut timelock = Timelock::new(token, 10);
+        let mint = OwnerOperation::Mint {
+            to: addr("alice"),
+            amount: 500,
+        };
+
+        // Queueing needs one owner; running it needs two of three and the delay
+        let minted = timelock.queue_operation(&addr("bob"), mint).unwrap();
+        timelock.advance_blocks(10);
+        assert_eq!(
+            timelock.execute_operation(&addr("bob"), minted),
+            Err(TokenError::Unconfirmed {
+                id: minted,
+                confirmed: 1,
+                threshold: 2
+            })
+        );
+        assert_eq!(
+            timelock.confirm_operation(&addr("bob"), minted),
+            Err(TokenError::AlreadyConfirmed {
+                owner: addr("bob").to_string(),
+                hash: minted
+            })
+        );
+        assert_eq!(timelock.confirm_operation(&addr("alice"), minted), Err(TokenError::NotOwner));
+        timelock.confirm_operation(&addr("carol"), minted).unwrap();
+        timelock.execute_operation(&addr("owner"), minted).unwrap();
+        assert_eq!(timelock.token().balance_of(&addr("alice")), 500);
+        timelock.token().assert_invariants();
+
+        // Confirmations do not shorten the delay, and one owner can veto
+        let paused = timelock.queue_operation(&addr("owner"), OwnerOperation::Pause).unwrap();
+        timelock.confirm_operation(&addr("bob"), paused).unwrap();
+        assert_eq!(
+            timelock.execute_operation(&addr("bob"), paused),
+            Err(TokenError::Timelocked { id: paused, eta: 20 })
+        );
+        timelock.cancel_operation(&addr("carol"), paused).unwrap();
+        timelock.advance_blocks(10);
+        assert_eq!(
+            timelock.execute_operation(&addr("bob"), paused),
+            Err(TokenError::NotQueued(paused))
+        );
+        assert!(!timelock.token().is_paused());
+    }
+
+    struct Vault;
+
+    impl Contract for Vault {
+        fn can_receive(&self, _from: &Address, _amount: u64) -> Option<bool> {
+            Some(true)
+        }
+    }
+
+    struct Registry;
+
+    impl Contract for Registry {}
+
+    #[test]
+    fn amounts_are_formatted_with_the_token_decimals() {
+        let token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        assert_eq!(token.get_token_info().3, 18);
+        assert_eq!(token.format_amount(1_000_000), "0.000000000001");
+        assert_eq!(token.format_amount(2_500_000_000_000_000_000), "2.5");
+
+        let token = TokenContract::new_with_decimals("Yato".to_string(), "YTO".to_string(), 0, 3);
+        let cases = [(0, "0"), (5, "0.005"), (1_000, "1"), (1_234_500, "1234.5")];
+        for (raw, formatted) in cases {
+            assert_eq!(token.format_amount(raw), formatted);
+        }
+        let token = TokenContract::new_with_decimals("Yato".to_string(), "YTO".to_string(), 0, 0);
+        assert_eq!(token.format_amount(u64::MAX), u64::MAX.to_string());
+    }
+
+    #[test]
+    fn concurrent_streams_mint_exactly_their_budgets() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        assert_eq!(token.stream(&addr("alice"), &addr("alice"), 10, 100), Err(TokenError::NotOwner));
+        assert_eq!(token.stream(&addr("owner"), &addr("alice"), 0, 100), Err(TokenError::EmptyStream));
+        assert_eq!(token.stream(&addr("owner"), &addr("alice"), u64::MAX, 2), Err(TokenError::Overflow));
+        let first = token.stream(&addr("owner"), &addr("alice"), 10, 100).unwrap();
+
+        token.advance_blocks(30);
+        assert_eq!(token.withdraw_stream(&addr("alice")), Ok(300));
+        token.advance_blocks(20);
+        let second = token.stream(&addr("owner"), &addr("alice"), 5, 20).unwrap();
+        assert_eq!(token.get_stream(second).unwrap().start, 50);
+        token.advance_blocks(10);
+        assert_eq!(token.withdrawable(&addr("alice")), 350);
+        assert_eq!(token.withdraw_stream(&addr("alice")), Ok(350));
+        assert_eq!(
+            token.withdraw_stream(&addr("alice")),
+            Err(TokenError::NothingToWithdraw("alice".to_string()))
+        );
+
+        // Both streams ended long ago; only what is left of their budgets is paid
+        token.advance_blocks(440);
+        assert_eq!(token.block_number(), 500);
+        assert_eq!(token.withdraw_stream(&addr("alice")), Ok(450));
+        token.advance_blocks(500);
+        assert!(token.withdraw_stream(&addr("alice")).is_err());
+        for id in [first, second] {
+            let stream = token.get_stream(id).unwrap();
+            assert_eq!(stream.withdrawn, stream.budget());
+        }
+        assert_eq!(token.balance_of(&addr("alice")), 1_100);
+        assert_eq!(token.total_supply, 1_001_100);
+    }
+
+    /// Records the sequence number and type of every event it is notified of, and
+    /// fails on mints
+    struct Indexer(Rc<RefCell<Vec<(u64, &'static str)>>>);
+
+    impl Contract for Indexer {
+        fn on_state_change(&mut self, event: &Event) -> Result<(), String> {
+            self.0.borrow_mut().push((event.sequence, event.payload.kind()));
+            if let TokenEvent::Mint { .. } = event.payload {
+                return Err("Indexer is out of space".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn observer_is_notified_of_every_state_change() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        assert_eq!(
+            token.set_observer(&addr("owner"), Some(&addr("indexer"))),
+            Err(TokenError::NotAContract("indexer".to_string()))
+        );
+        token.register_contract(&addr("indexer"), Box::new(Indexer(Rc::clone(&seen))));
+        assert_eq!(token.set_observer(&addr("alice"), Some(&addr("indexer"))), Err(TokenError::NotOwner));
+        token.set_observer(&addr("owner"), Some(&addr("indexer"))).unwrap();
+
+        token.transfer(&addr("owner"), &addr("alice"), 1_000).unwrap();
+        token.assert_invariants();
+        token.approve(&addr("alice"), &addr("bob"), 500).unwrap();
+        token.assert_invariants();
+        token.transfer_from(&addr("alice"), &addr("carol"), &addr("bob"), 200).unwrap();
+        token.assert_invariants();
+        token.mint(&addr("owner"), &addr("owner"), 300).unwrap();
+        token.assert_invariants();
+        token.burn(&addr("alice"), 100).unwrap();
+        token.assert_invariants();
+        token.burn_from(&addr("alice"), &addr("bob"), 50).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.allowance(&addr("alice"), &addr("bob")), 250);
+        assert_eq!(
+            token.transfer(&addr("carol"), &addr("alice"), 1_000),
+            Err(TokenError::InsufficientBalance { account: "carol".to_string(), required: 1_000, available: 200 })
+        );
+
+        let kinds: Vec<&str> = seen.borrow().iter().map(|(_, kind)| *kind).collect();
+        assert_eq!(kinds, vec!["Transfer", "Approval", "TransferFrom", "Mint", "Burn", "Burn"]);
+        // Every notification refers to an event already in the log
+        for (sequence, kind) in seen.borrow().iter() {
+            assert_eq!(token.get_events()[*sequence as usize].payload.kind(), *kind);
+        }
+        // The failed notification did not undo the mint
+        assert_eq!(token.balance_of(&addr("owner")), 999_300);
+
+        token.set_observer(&addr("owner"), None).unwrap();
+        token.transfer(&addr("owner"), &addr("alice"), 1_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(seen.borrow().len(), 6);
+    }
+
+    /// Merkle root over `leaves` and the proof of the leaf at `index`. A node
+    /// without a sibling moves up a level unchanged.
+    fn merkle_tree(leaves: &[[u8; 32]], mut index: usize) -> ([u8; 32], Vec<[u8; 32]>) {
+        let mut level = leaves.to_vec();
+        let mut proof = Vec::new();
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => Airdrop::node_hash(*a, *b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            index /= 2;
+        }
+        (level[0], proof)
+    }
+
+    #[test]
+    fn airdrop_pays_proven_claims_once() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        let entries = [(&addr("alice"), 100), (&addr("bob"), 250), (&addr("carol"), 75)];
+        let leaves: Vec<[u8; 32]> = entries
+            .iter()
+            .map(|(account, amount)| Airdrop::leaf_hash(account, *amount))
+            .collect();
+        // Leaves follow the documented encoding, so other tools reproduce the root
+        let alice_leaf: String = leaves[0].iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(alice_leaf, "07e09ae43a4f41a470ed2404b700ef2339d7cdededc7f828c6ebb04483469fcf");
+        let (root, _) = merkle_tree(&leaves, 0);
+        let mut airdrop = Airdrop::new(&addr("airdrop"), root);
+        token.transfer(&addr("owner"), &addr("airdrop"), 425).unwrap();
+        token.assert_invariants();
+
+        for (index, (account, amount)) in entries.iter().enumerate() {
+            let (_, proof) = merkle_tree(&leaves, index);
+            airdrop.claim(&mut token, account, *amount, &proof).unwrap();
+            assert_eq!(token.balance_of(account), *amount);
+            assert!(airdrop.is_claimed(account));
+        }
+        assert_eq!(token.balance_of(&addr("airdrop")), 0);
+
+        // Claiming again, a larger amount, or with someone else's proof all fail
+        let (_, bob_proof) = merkle_tree(&leaves, 1);
+        assert_eq!(
+            airdrop.claim(&mut token, &addr("bob"), 250, &bob_proof),
+            Err(TokenError::AirdropClaimed("bob".to_string()))
+        );
+        token.transfer(&addr("owner"), &addr("airdrop"), 1_000).unwrap();
+        token.assert_invariants();
+        let mut fresh = Airdrop::new(&addr("airdrop"), root);
+        assert_eq!(
+            fresh.claim(&mut token, &addr("bob"), 500, &bob_proof),
+            Err(TokenError::InvalidProof("bob".to_string()))
+        );
+        assert_eq!(
+            fresh.claim(&mut token, &addr("dave"), 250, &bob_proof),
+            Err(TokenError::InvalidProof("dave".to_string()))
+        );
+        assert_eq!(
+            fresh.claim(&mut token, &addr("bob"), 250, &[]),
+            Err(TokenError::InvalidProof("bob".to_string()))
+        );
+        assert_eq!(token.balance_of(&addr("airdrop")), 1_000);
+
+        // A valid claim on an unfunded airdrop fails with the transfer's error
+        let mut unfunded = Airdrop::new(&addr("unfunded"), root);
+        assert_eq!(
+            unfunded.claim(&mut token, &addr("bob"), 250, &bob_proof),
+            Err(TokenError::InsufficientBalance {
+                account: "unfunded".to_string(),
+                required: 250,
+                available: 0,
+            })
+        );
+        assert!(!unfunded.is_claimed(&addr("bob")));
+    }
+
+    #[test]
+    fn safe_transfer_checks_contract_recipients() {
+        let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+        token.register_contract(&addr("vault"), Box::new(Vault));
+        token.register_contract(&addr("registry"), Box::new(Registry));
+
+        token.safe_transfer(&addr("owner"), &addr("alice"), 1_000).unwrap();
+        token.assert_invariants();
+        token.safe_transfer(&addr("owner"), &addr("vault"), 2_000).unwrap();
+        token.assert_invariants();
+        assert_eq!(token.balance_of(&addr("alice")), 1_000);
+        assert_eq!(token.balance_of(&addr("vault")), 2_000);
+
+        let events = token.get_events().len();
+        assert_eq!(
+            token.safe_transfer(&addr("owner"), &addr("registry"), 3_000),
+            Err(TokenError::CannotReceive("registry".to_string()))
+        );
+        assert_eq!(token.balance_of(&addr("registry")), 0);
+        assert_eq!(token.balance_of(&addr("owner")), 997_000);
+        assert_eq!(token.get_events().len(), events);
+    }
+
+    proptest! {
+        // A fixed seed keeps the generated cases identical from run to run
+        #![proptest_config(ProptestConfig {
+            cases: 4096,
+            rng_seed: RngSeed::Fixed(0x5954_4f00),
+            failure_persistence: None,
+            ..ProptestConfig::default()
+        })]
+
+        #[test]
+        fn accounting_invariants_hold(ops in prop::collection::vec(op(), 1..64)) {
+            let mut token = TokenContract::new("Yato".to_string(), "YTO".to_string(), 1_000_000);
+
+            for op in &ops {
+                let before = accounting(&token);
+                // Underflow panics in test builds, so reaching the checks below means
+                // no balance or allowance went negative
+                let result = apply(&mut token, op);
+                if result.is_err() {
+                    prop_assert_eq!(&accounting(&token), &before, "Failed {:?} changed the accounting", op);
+                }
+
+                let sum: u128 = token.balances.values().map(|&balance| balance as u128).sum();
+                prop_assert_eq!(sum, token.total_supply as u128, "Supply mismatch after {:?}", op);
+                token.assert_invariants();
+            }
+        }
+    }
 }